@@ -3,9 +3,10 @@
 use crate::store::Store;
 use crate::types::{Item, Status};
 use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
 
 /// Specification for creating an item in a batch.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateSpec {
     pub title: String,
     pub priority: u8,
@@ -44,7 +45,7 @@ impl CreateSpec {
 }
 
 /// Result of a batch create operation.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchCreateResult {
     /// Successfully created items.
     pub created: Vec<Item>,
@@ -53,7 +54,7 @@ pub struct BatchCreateResult {
 }
 
 /// Result of a batch close operation.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BatchCloseResult {
     /// Successfully closed items.
     pub closed: Vec<Item>,
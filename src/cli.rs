@@ -1,6 +1,7 @@
 //! CLI argument parsing for Engram.
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use engram::{ExportFormat, ParentPolicy, Status};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -46,7 +47,11 @@ pub enum Command {
     List {
         /// Filter by status (open, in_progress, blocked, closed)
         #[arg(short, long)]
-        status: Option<String>,
+        status: Option<Status>,
+
+        /// Query, e.g. 'label:backend -label:wip priority<=1 updated>7d "login"'
+        #[arg(short = 'w', long = "where")]
+        query: Option<String>,
     },
 
     /// Show tasks that are ready to work on
@@ -63,6 +68,10 @@ pub enum Command {
         /// Reason for closing
         #[arg(short, long)]
         reason: Option<String>,
+
+        /// Also close all open subtasks
+        #[arg(long)]
+        tree: bool,
     },
 
     /// Get a task by ID
@@ -71,12 +80,81 @@ pub enum Command {
         id: String,
     },
 
+    /// Search task titles and descriptions
+    Search {
+        /// Words to search for (all must match)
+        #[arg(required = true)]
+        text: Vec<String>,
+
+        /// Filter by status (open, in_progress, blocked, closed)
+        #[arg(short, long)]
+        status: Option<Status>,
+
+        /// Only tasks with any of these labels (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        labels: Option<Vec<String>>,
+
+        /// Maximum number of results
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Export the task graph for diagrams
+    Graph {
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        /// Only this task and its subtasks
+        #[arg(short, long)]
+        root: Option<String>,
+
+        /// Filter by status (open, in_progress, blocked, closed)
+        #[arg(short, long, conflicts_with = "root")]
+        status: Option<Status>,
+
+        /// Only tasks with any of these labels (comma-separated)
+        #[arg(short, long, value_delimiter = ',', conflicts_with = "root")]
+        labels: Option<Vec<String>>,
+    },
+
+    /// Show the order in which a task's remaining work can be done
+    Plan {
+        /// Task ID
+        id: String,
+    },
+
+    /// Explain why a task is not ready
+    Why {
+        /// Task ID
+        id: String,
+    },
+
     /// Start working on a task (set status to in_progress)
     Start {
         /// Task ID
         id: String,
     },
 
+    /// Claim the highest-priority ready task
+    Next {
+        /// Agent claiming the task
+        #[arg(short, long)]
+        agent: String,
+
+        /// Only consider tasks with any of these labels (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        labels: Option<Vec<String>>,
+
+        /// Only consider tasks at or above this priority (0=critical, 4=low)
+        #[arg(short = 'p', long)]
+        max_priority: Option<u8>,
+
+        /// Lease length in seconds
+        #[arg(short, long, default_value_t = engram::DEFAULT_LEASE_TTL_SECS)]
+        ttl: u64,
+    },
+
     /// Add a blocking dependency
     Block {
         /// Task that is blocked
@@ -95,6 +173,17 @@ pub enum Command {
         child_id: String,
     },
 
+    /// Show or change the store's automation policy
+    Policy {
+        /// Move tasks to blocked and back as blocking dependencies open and close
+        #[arg(long)]
+        auto_block: Option<bool>,
+
+        /// What to do with a parent once all its subtasks close
+        #[arg(long, value_enum)]
+        on_children_closed: Option<ParentAction>,
+    },
+
     /// Run the daemon in foreground
     Daemon,
 
@@ -129,4 +218,72 @@ pub enum Command {
 
     /// Show event counts by kind
     EventCounts,
+
+    /// Stream changes as they happen (starts the daemon if needed)
+    Watch {
+        /// Only item changes with any of these labels (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        labels: Option<Vec<String>>,
+
+        /// Only changes touching these items (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        ids: Option<Vec<String>>,
+
+        /// Only events of these kinds (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        kinds: Option<Vec<String>>,
+    },
+
+    /// Git merge driver for .engram JSONL files
+    ///
+    /// Register with:
+    ///   git config merge.engram.driver "eg merge-driver %O %A %B"
+    ///   echo ".engram/*.jsonl merge=engram" >> .gitattributes
+    MergeDriver {
+        /// Common ancestor version (%O)
+        base: PathBuf,
+
+        /// Current branch version, overwritten with the result (%A)
+        ours: PathBuf,
+
+        /// Other branch version (%B)
+        theirs: PathBuf,
+    },
+}
+
+/// `--format` values for `graph`.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    #[value(name = "graphml")]
+    GraphMl,
+}
+
+/// `--on-children-closed` values for `policy`.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ParentAction {
+    Nothing,
+    Ready,
+    Close,
+}
+
+impl From<ParentAction> for ParentPolicy {
+    fn from(action: ParentAction) -> Self {
+        match action {
+            ParentAction::Nothing => ParentPolicy::Nothing,
+            ParentAction::Ready => ParentPolicy::Ready,
+            ParentAction::Close => ParentPolicy::Close,
+        }
+    }
+}
+
+impl From<GraphFormat> for ExportFormat {
+    fn from(format: GraphFormat) -> Self {
+        match format {
+            GraphFormat::Dot => ExportFormat::Dot,
+            GraphFormat::Mermaid => ExportFormat::Mermaid,
+            GraphFormat::GraphMl => ExportFormat::GraphMl,
+        }
+    }
 }
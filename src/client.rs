@@ -1,18 +1,78 @@
 //! Client for connecting to the engram daemon.
 
+use crate::batch::{BatchCloseResult, BatchCreateResult, CreateSpec};
+use crate::compact::{CompactConfig, CompactResult};
 use crate::daemon::{DaemonConfig, is_daemon_running, start_daemon};
-use crate::protocol::{Request, Response};
-use crate::types::{Edge, EdgeKind, Item, Status};
+use crate::eventquery::{EventCounts, TimelineEntry};
+use crate::graph::{Blocker, Direction, Related};
+use crate::protocol::{Envelope, ErrorKind, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION, Request, Response};
+use crate::store::StoreError;
+use crate::transaction::{TxOp, TxResult};
+use crate::types::{Change, ChangeFilter, Edge, EdgeKind, Event, EventFilter, Filter, Item, Page, SearchHit, Status};
+use chrono::{DateTime, Utc};
 use eyre::{Context, Result, bail};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+/// Stream of changes from a subscribed daemon connection.
+///
+/// Yields changes until the daemon closes the connection.
+pub struct Subscription {
+    reader: BufReader<UnixStream>,
+}
+
+impl Iterator for Subscription {
+    type Item = Result<Change>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => Some(match serde_json::from_str::<Envelope<Response>>(&line) {
+                Ok(Envelope {
+                    body: Response::Change { change },
+                    ..
+                }) => Ok(change),
+                Ok(Envelope {
+                    body: Response::Error { message, kind },
+                    ..
+                }) => Err(remote_error(message, kind)),
+                Ok(_) => Err(eyre::eyre!("Unexpected response")),
+                Err(e) => Err(e.into()),
+            }),
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+/// Rebuild the error behind an error response.
+///
+/// Store errors come back as `StoreError`, so `downcast_ref` behaves as it
+/// would against a local `Store`.
+fn remote_error(message: String, kind: Option<ErrorKind>) -> eyre::Report {
+    match kind {
+        Some(kind) => eyre::eyre!(StoreError::from(kind)),
+        None => eyre::eyre!(message),
+    }
+}
+
+/// Name sent to the daemon in the handshake.
+const CLIENT_NAME: &str = concat!("engram/", env!("CARGO_PKG_VERSION"));
+
 /// Client for communicating with the engram daemon.
+///
+/// Reads take `&self` like their `Store` counterparts; the connection is
+/// borrowed for the duration of each request.
 pub struct Client {
     root: PathBuf,
-    stream: UnixStream,
+    stream: RefCell<BufReader<UnixStream>>,
+    next_request_id: Cell<u64>,
+    protocol_version: u32,
+    capabilities: Vec<String>,
 }
 
 impl Client {
@@ -54,10 +114,41 @@ impl Client {
             .set_read_timeout(Some(Duration::from_secs(30)))
             .context("Failed to set read timeout")?;
 
-        Ok(Self {
+        let mut client = Self {
             root: root.to_path_buf(),
-            stream,
-        })
+            stream: RefCell::new(BufReader::new(stream)),
+            next_request_id: Cell::new(1),
+            protocol_version: PROTOCOL_VERSION,
+            capabilities: Vec::new(),
+        };
+        client.handshake().context("Handshake with daemon failed")?;
+
+        Ok(client)
+    }
+
+    /// Agree on a protocol version and learn the daemon's capabilities.
+    fn handshake(&mut self) -> Result<()> {
+        let response = self.request(Request::Hello {
+            protocol_version: PROTOCOL_VERSION,
+            client_name: CLIENT_NAME.to_string(),
+        })?;
+
+        match response {
+            Response::Welcome {
+                protocol_version,
+                capabilities,
+                ..
+            } if protocol_version >= MIN_PROTOCOL_VERSION => {
+                self.protocol_version = protocol_version;
+                self.capabilities = capabilities;
+                Ok(())
+            }
+            Response::Welcome { protocol_version, .. } => {
+                bail!("Daemon speaks protocol version {}, which is too old", protocol_version)
+            }
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
     }
 
     /// Get the store root path.
@@ -65,18 +156,60 @@ impl Client {
         &self.root
     }
 
+    /// Protocol version agreed with the daemon.
+    pub fn protocol_version(&self) -> u32 {
+        self.protocol_version
+    }
+
+    /// Whether the daemon offers an optional feature (e.g. "pipelining").
+    pub fn has_capability(&self, name: &str) -> bool {
+        self.capabilities.iter().any(|c| c == name)
+    }
+
     /// Send a request and receive a response.
-    fn request(&mut self, request: Request) -> Result<Response> {
-        let request_json = serde_json::to_string(&request)?;
-        writeln!(self.stream, "{}", request_json)?;
-        self.stream.flush()?;
+    fn request(&self, request: Request) -> Result<Response> {
+        Ok(self.pipeline(vec![request])?.remove(0))
+    }
 
-        let mut reader = BufReader::new(&self.stream);
-        let mut response_line = String::new();
-        reader.read_line(&mut response_line)?;
+    /// Send several requests before reading any reply.
+    ///
+    /// Saves a round trip per request. Responses are matched to requests by
+    /// ID and returned in request order.
+    pub fn pipeline(&self, requests: Vec<Request>) -> Result<Vec<Response>> {
+        let mut stream = self.stream.borrow_mut();
+        let mut slots: HashMap<u64, usize> = HashMap::new();
+        let mut batch = String::new();
+        for (index, request) in requests.into_iter().enumerate() {
+            let request_id = self.next_request_id.replace(self.next_request_id.get() + 1);
+            slots.insert(request_id, index);
+            batch.push_str(&serde_json::to_string(&Envelope {
+                request_id: Some(request_id),
+                body: request,
+            })?);
+            batch.push('\n');
+        }
+        stream.get_mut().write_all(batch.as_bytes())?;
+        stream.get_mut().flush()?;
+
+        let mut responses: Vec<Option<Response>> = (0..slots.len()).map(|_| None).collect();
+        for _ in 0..responses.len() {
+            let mut response_line = String::new();
+            if stream.read_line(&mut response_line)? == 0 {
+                bail!("Daemon closed the connection");
+            }
+
+            let envelope: Envelope<Response> = serde_json::from_str(&response_line)?;
+            let index = envelope
+                .request_id
+                .and_then(|id| slots.get(&id).copied())
+                .ok_or_else(|| eyre::eyre!("Response does not match any pending request"))?;
+            responses[index] = Some(envelope.body);
+        }
 
-        let response: Response = serde_json::from_str(&response_line)?;
-        Ok(response)
+        responses
+            .into_iter()
+            .map(|r| r.ok_or_else(|| eyre::eyre!("Missing response")))
+            .collect()
     }
 
     /// Create a new item.
@@ -90,7 +223,7 @@ impl Client {
 
         match response {
             Response::Item { item } => Ok(item),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
@@ -103,6 +236,19 @@ impl Client {
         description: Option<Option<&str>>,
         priority: Option<u8>,
         labels: Option<&[&str]>,
+    ) -> Result<Item> {
+        self.update_checked(id, None, title, description, priority, labels)
+    }
+
+    /// Update an item if it is still at `expected_version` (its `updated_at`).
+    pub fn update_checked(
+        &mut self,
+        id: &str,
+        expected_version: Option<DateTime<Utc>>,
+        title: Option<&str>,
+        description: Option<Option<&str>>,
+        priority: Option<u8>,
+        labels: Option<&[&str]>,
     ) -> Result<Item> {
         let response = self.request(Request::Update {
             id: id.to_string(),
@@ -110,39 +256,76 @@ impl Client {
             description: description.map(|d| d.map(String::from)),
             priority,
             labels: labels.map(|l| l.iter().map(|s| s.to_string()).collect()),
+            expected_version,
         })?;
 
         match response {
             Response::Item { item } => Ok(item),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// Set an item's status.
     pub fn set_status(&mut self, id: &str, status: Status) -> Result<Item> {
+        self.set_status_checked(id, None, status)
+    }
+
+    /// Change item status if it is still at `expected_version`.
+    pub fn set_status_checked(
+        &mut self,
+        id: &str,
+        expected_version: Option<DateTime<Utc>>,
+        status: Status,
+    ) -> Result<Item> {
         let response = self.request(Request::SetStatus {
             id: id.to_string(),
             status,
+            expected_version,
         })?;
 
         match response {
             Response::Item { item } => Ok(item),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// Close an item.
     pub fn close(&mut self, id: &str, reason: Option<&str>) -> Result<Item> {
+        self.close_checked(id, None, reason)
+    }
+
+    /// Close an item if it is still at `expected_version`.
+    pub fn close_checked(
+        &mut self,
+        id: &str,
+        expected_version: Option<DateTime<Utc>>,
+        reason: Option<&str>,
+    ) -> Result<Item> {
         let response = self.request(Request::Close {
             id: id.to_string(),
             reason: reason.map(String::from),
+            expected_version,
         })?;
 
         match response {
             Response::Item { item } => Ok(item),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Close an item and its open descendants.
+    pub fn close_tree(&mut self, id: &str, reason: Option<&str>) -> Result<Vec<Item>> {
+        let response = self.request(Request::CloseTree {
+            id: id.to_string(),
+            reason: reason.map(String::from),
+        })?;
+
+        match response {
+            Response::Items { items } => Ok(items),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
@@ -157,7 +340,7 @@ impl Client {
 
         match response {
             Response::Edge { edge } => Ok(edge),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
@@ -172,52 +355,458 @@ impl Client {
 
         match response {
             Response::Ok => Ok(()),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Claim an item for `agent` with a lease of `ttl`.
+    pub fn claim(&mut self, id: &str, agent: &str, ttl: Duration) -> Result<Item> {
+        let response = self.request(Request::Claim {
+            id: id.to_string(),
+            agent: agent.to_string(),
+            ttl_secs: ttl.as_secs(),
+        })?;
+
+        match response {
+            Response::Item { item } => Ok(item),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Renew `agent`'s lease on an item.
+    pub fn heartbeat(&mut self, id: &str, agent: &str, ttl: Duration) -> Result<Item> {
+        let response = self.request(Request::Heartbeat {
+            id: id.to_string(),
+            agent: agent.to_string(),
+            ttl_secs: ttl.as_secs(),
+        })?;
+
+        match response {
+            Response::Item { item } => Ok(item),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Claim the top ready item for `agent`, if any matches.
+    pub fn claim_next(
+        &mut self,
+        agent: &str,
+        labels: &[&str],
+        max_priority: Option<u8>,
+        ttl: Duration,
+    ) -> Result<Option<Item>> {
+        let response = self.request(Request::ClaimNext {
+            agent: agent.to_string(),
+            labels: labels.iter().map(|s| s.to_string()).collect(),
+            max_priority,
+            ttl_secs: Some(ttl.as_secs()),
+        })?;
+
+        match response {
+            Response::Item { item } => Ok(Some(item)),
+            Response::Ok => Ok(None),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Subscribe to changes matching `filter`.
+    ///
+    /// Consumes the client: the connection is dedicated to the stream.
+    pub fn subscribe(self, filter: ChangeFilter) -> Result<Subscription> {
+        let response = self.request(Request::Subscribe { filter })?;
+
+        match response {
+            Response::Subscribed => {
+                // Changes can follow the acknowledgement immediately, so keep the same reader
+                let reader = self.stream.into_inner();
+                reader
+                    .get_ref()
+                    .set_read_timeout(None)
+                    .context("Failed to clear read timeout")?;
+                Ok(Subscription { reader })
+            }
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// Get an item by ID.
-    pub fn get(&mut self, id: &str) -> Result<Option<Item>> {
+    pub fn get(&self, id: &str) -> Result<Option<Item>> {
         let response = self.request(Request::Get { id: id.to_string() })?;
 
         match response {
             Response::Item { item } => Ok(Some(item)),
             Response::NotFound { .. } => Ok(None),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// List items with optional status filter.
-    pub fn list(&mut self, status: Option<Status>) -> Result<Vec<Item>> {
+    pub fn list(&self, status: Option<Status>) -> Result<Vec<Item>> {
         let response = self.request(Request::List { status })?;
 
         match response {
             Response::Items { items } => Ok(items),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// Get ready items.
-    pub fn ready(&mut self) -> Result<Vec<Item>> {
+    pub fn ready(&self) -> Result<Vec<Item>> {
         let response = self.request(Request::Ready)?;
 
         match response {
             Response::Items { items } => Ok(items),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// Get blocked items.
-    pub fn blocked(&mut self) -> Result<Vec<Item>> {
+    pub fn blocked(&self) -> Result<Vec<Item>> {
         let response = self.request(Request::Blocked)?;
 
         match response {
             Response::Items { items } => Ok(items),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Return items with expired leases to Open.
+    pub fn expire_leases(&mut self) -> Result<Vec<Item>> {
+        let response = self.request(Request::ExpireLeases)?;
+
+        match response {
+            Response::Items { items } => Ok(items),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Query items matching a filter.
+    pub fn query_with_filter(&self, filter: &Filter) -> Result<Vec<Item>> {
+        let response = self.request(Request::Query {
+            filter: filter.clone(),
+            q: None,
+        })?;
+
+        match response {
+            Response::Items { items } => Ok(items),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// One page of items matching a filter, after `cursor` (None for the
+    /// first page).
+    pub fn query_page(&self, filter: &Filter, cursor: Option<&str>) -> Result<Page<Item>> {
+        let response = self.request(Request::QueryPage {
+            filter: filter.clone(),
+            cursor: cursor.map(String::from),
+        })?;
+
+        match response {
+            Response::ItemPage { page } => Ok(page),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Query items matching a query string (see `query::parse_query`).
+    ///
+    /// The daemon parses the string; a syntax error comes back as an error
+    /// naming the offending column.
+    pub fn query_where(&self, q: &str) -> Result<Vec<Item>> {
+        let response = self.request(Request::Query {
+            filter: Filter::new(),
+            q: Some(q.to_string()),
+        })?;
+
+        match response {
+            Response::Items { items } => Ok(items),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Full-text search titles and descriptions, best match first.
+    pub fn search(&self, text: &str, filter: &Filter) -> Result<Vec<SearchHit>> {
+        let response = self.request(Request::Search {
+            text: text.to_string(),
+            filter: filter.clone(),
+        })?;
+
+        match response {
+            Response::SearchHits { hits } => Ok(hits),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Count items matching a filter (ignores limit and offset).
+    pub fn count(&self, filter: &Filter) -> Result<usize> {
+        let response = self.request(Request::Count { filter: filter.clone() })?;
+
+        match response {
+            Response::Count { count } => Ok(count),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Record an event.
+    pub fn record_event(
+        &mut self,
+        kind: &str,
+        source_task: Option<&str>,
+        target_task: Option<&str>,
+        payload: serde_json::Value,
+    ) -> Result<Event> {
+        let response = self.request(Request::RecordEvent {
+            kind: kind.to_string(),
+            source_task: source_task.map(String::from),
+            target_task: target_task.map(String::from),
+            payload,
+        })?;
+
+        match response {
+            Response::Event { event } => Ok(event),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Record an event from an existing Event struct.
+    pub fn record_event_raw(&mut self, event: &Event) -> Result<()> {
+        let response = self.request(Request::RecordEventRaw { event: event.clone() })?;
+
+        match response {
+            Response::Ok => Ok(()),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get an event by ID.
+    pub fn get_event(&self, id: &str) -> Result<Option<Event>> {
+        let response = self.request(Request::GetEvent { id: id.to_string() })?;
+
+        match response {
+            Response::Event { event } => Ok(Some(event)),
+            Response::NotFound { .. } => Ok(None),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Query events with filters.
+    pub fn query_events(&self, filter: EventFilter) -> Result<Vec<Event>> {
+        let response = self.request(Request::QueryEvents { filter })?;
+
+        match response {
+            Response::Events { events } => Ok(events),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// One page of events, most recent first, after `cursor` (None for the
+    /// first page).
+    pub fn query_events_page(&self, filter: EventFilter, cursor: Option<&str>) -> Result<Page<Event>> {
+        let response = self.request(Request::QueryEventsPage {
+            filter,
+            cursor: cursor.map(String::from),
+        })?;
+
+        match response {
+            Response::EventPage { page } => Ok(page),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get recent events.
+    pub fn recent_events(&self, limit: usize) -> Result<Vec<Event>> {
+        let response = self.request(Request::RecentEvents { limit })?;
+
+        match response {
+            Response::Events { events } => Ok(events),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get events for a specific task (as source or target).
+    pub fn task_events(&self, task_id: &str, limit: usize) -> Result<Vec<Event>> {
+        let response = self.request(Request::TaskEvents {
+            task_id: task_id.to_string(),
+            limit,
+        })?;
+
+        match response {
+            Response::Events { events } => Ok(events),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get event counts by kind.
+    pub fn event_counts(&self) -> Result<EventCounts> {
+        let response = self.request(Request::EventCounts)?;
+
+        match response {
+            Response::EventCounts { counts } => Ok(counts),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get event counts for a specific task.
+    pub fn task_event_counts(&self, task_id: &str) -> Result<EventCounts> {
+        let response = self.request(Request::TaskEventCounts {
+            task_id: task_id.to_string(),
+        })?;
+
+        match response {
+            Response::EventCounts { counts } => Ok(counts),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get events grouped into hourly buckets.
+    pub fn event_timeline(&self, since: DateTime<Utc>, limit: usize) -> Result<Vec<TimelineEntry>> {
+        let response = self.request(Request::EventTimeline { since, limit })?;
+
+        match response {
+            Response::Timeline { entries } => Ok(entries),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get all unique event kinds.
+    pub fn event_kinds(&self) -> Result<Vec<String>> {
+        let response = self.request(Request::EventKinds)?;
+
+        match response {
+            Response::EventKinds { kinds } => Ok(kinds),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Create multiple items.
+    pub fn batch_create(&mut self, specs: Vec<CreateSpec>) -> Result<BatchCreateResult> {
+        let response = self.request(Request::BatchCreate { specs })?;
+
+        match response {
+            Response::BatchCreated { result } => Ok(result),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Close multiple items.
+    pub fn batch_close(&mut self, ids: &[&str], reason: Option<&str>) -> Result<BatchCloseResult> {
+        let response = self.request(Request::BatchClose {
+            ids: ids.iter().map(|s| s.to_string()).collect(),
+            reason: reason.map(String::from),
+        })?;
+
+        match response {
+            Response::BatchClosed { result } => Ok(result),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Set status for multiple items.
+    pub fn batch_set_status(&mut self, ids: &[&str], status: Status) -> Result<Vec<Item>> {
+        let response = self.request(Request::BatchSetStatus {
+            ids: ids.iter().map(|s| s.to_string()).collect(),
+            status,
+        })?;
+
+        match response {
+            Response::Items { items } => Ok(items),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Compact old closed items.
+    pub fn compact(&mut self, config: &CompactConfig) -> Result<CompactResult> {
+        let response = self.request(Request::Compact { config: config.clone() })?;
+
+        match response {
+            Response::Compacted { result } => Ok(result),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Get items eligible for compaction.
+    pub fn get_compactable_items(&self, older_than_days: u32) -> Result<Vec<Item>> {
+        let response = self.request(Request::CompactableItems { older_than_days })?;
+
+        match response {
+            Response::Items { items } => Ok(items),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Items reachable from `id` in `direction`, nearest first.
+    pub fn traverse(
+        &self,
+        id: &str,
+        direction: Direction,
+        kinds: &[EdgeKind],
+        depth: Option<usize>,
+    ) -> Result<Vec<Related>> {
+        let response = self.request(Request::Traverse {
+            id: id.to_string(),
+            direction,
+            kinds: kinds.to_vec(),
+            depth,
+        })?;
+
+        match response {
+            Response::Related { related } => Ok(related),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Explain why an item is not ready.
+    pub fn explain_blocked(&self, id: &str) -> Result<Vec<Blocker>> {
+        let response = self.request(Request::ExplainBlocked { id: id.to_string() })?;
+
+        match response {
+            Response::Blockers { blockers } => Ok(blockers),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
+            _ => bail!("Unexpected response"),
+        }
+    }
+
+    /// Run several operations as one transaction.
+    ///
+    /// Either every op commits or none does. IDs of the form `"$N"` refer to
+    /// the item created by op `N`.
+    pub fn transaction(&mut self, ops: Vec<TxOp>) -> Result<Vec<TxResult>> {
+        let response = self.request(Request::Transaction { ops })?;
+
+        match response {
+            Response::Transaction { results } => Ok(results),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
@@ -228,7 +817,7 @@ impl Client {
 
         match response {
             Response::Ok => Ok(()),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
@@ -239,18 +828,18 @@ impl Client {
 
         match response {
             Response::Ok => Ok(()),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
 
     /// Ping the daemon.
-    pub fn ping(&mut self) -> Result<()> {
+    pub fn ping(&self) -> Result<()> {
         let response = self.request(Request::Ping)?;
 
         match response {
             Response::Pong => Ok(()),
-            Response::Error { message } => bail!("{}", message),
+            Response::Error { message, kind } => Err(remote_error(message, kind)),
             _ => bail!("Unexpected response"),
         }
     }
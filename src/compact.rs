@@ -7,9 +7,11 @@ use crate::store::Store;
 use crate::types::{Item, Status};
 use chrono::{Duration, Utc};
 use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
 
 /// Configuration for compaction.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CompactConfig {
     /// Items closed longer than this are eligible for compaction.
     pub older_than_days: u32,
@@ -46,7 +48,7 @@ impl CompactConfig {
 }
 
 /// Result of a compaction operation.
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompactResult {
     /// Number of items that were compacted.
     pub compacted_count: usize,
@@ -5,17 +5,25 @@
 //! - Lock management (single writer prevents corruption)
 //! - Background flush with configurable interval
 
-use crate::protocol::{Request, Response};
-use crate::store::Store;
+use crate::batch::StoreBatchExt;
+use crate::compact::StoreCompactExt;
+use crate::eventquery::StoreEventExt;
+use crate::graph::StoreGraphExt;
+use crate::protocol::{Envelope, Request, Response};
+use crate::query::{StoreQueryExt, parse_query};
+use crate::store::{DEFAULT_LEASE_TTL_SECS, Store, lease_ttl};
+use crate::transaction::StoreTransactionExt;
+use crate::types::{Change, ChangeFilter};
 use eyre::{Context, Result};
 use std::fs;
-use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
-use tokio::sync::mpsc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::sync::{broadcast, mpsc};
 use tokio::time::interval;
 
 /// Socket file name within the .engram directory.
@@ -27,6 +35,24 @@ const PID_FILE: &str = "daemon.pid";
 /// Default flush interval in milliseconds.
 const DEFAULT_FLUSH_INTERVAL_MS: u64 = 100;
 
+/// Changes buffered per subscriber before it starts dropping them.
+const CHANGE_BUFFER: usize = 1024;
+
+/// Default interval between expired-lease sweeps in milliseconds.
+const DEFAULT_LEASE_SWEEP_INTERVAL_MS: u64 = 1000;
+
+/// When the daemon's writes reach disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Write and fsync each record before responding.
+    PerWrite,
+    /// Buffer records and write them with one fsync per file every flush interval.
+    #[default]
+    Interval,
+    /// Buffer records until a Flush request or shutdown.
+    OnFlush,
+}
+
 /// Configuration for the daemon.
 #[derive(Debug, Clone)]
 pub struct DaemonConfig {
@@ -35,6 +61,12 @@ pub struct DaemonConfig {
 
     /// Flush interval for pending writes
     pub flush_interval: Duration,
+
+    /// When buffered writes are flushed
+    pub durability: Durability,
+
+    /// How often expired claims are returned to Open
+    pub lease_sweep_interval: Duration,
 }
 
 impl DaemonConfig {
@@ -43,6 +75,8 @@ impl DaemonConfig {
         Self {
             root: root.into(),
             flush_interval: Duration::from_millis(DEFAULT_FLUSH_INTERVAL_MS),
+            durability: Durability::default(),
+            lease_sweep_interval: Duration::from_millis(DEFAULT_LEASE_SWEEP_INTERVAL_MS),
         }
     }
 
@@ -62,17 +96,25 @@ pub struct Daemon {
     config: DaemonConfig,
     store: Store,
     shutdown: Arc<AtomicBool>,
+    changes: broadcast::Sender<Change>,
 }
 
 impl Daemon {
     /// Create a new daemon instance.
     pub fn new(config: DaemonConfig) -> Result<Self> {
-        let store = Store::open(&config.root).context("Failed to open store")?;
+        let mut store = Store::open(&config.root).context("Failed to open store")?;
+        store
+            .set_write_buffering(config.durability != Durability::PerWrite)
+            .context("Failed to configure write buffering")?;
+        store.track_changes();
+
+        let (changes, _) = broadcast::channel(CHANGE_BUFFER);
 
         Ok(Self {
             config,
             store,
             shutdown: Arc::new(AtomicBool::new(false)),
+            changes,
         })
     }
 
@@ -107,25 +149,55 @@ impl Daemon {
         // Spawn connection acceptor task
         let shutdown_flag = Arc::clone(&self.shutdown);
         let tx_clone = tx.clone();
+        let changes = self.changes.clone();
         tokio::spawn(async move {
-            Self::accept_connections(listener, tx_clone, shutdown_flag).await;
+            Self::accept_connections(listener, tx_clone, changes, shutdown_flag).await;
         });
 
         // Main event loop
         let mut flush_interval = interval(self.config.flush_interval);
+        let mut lease_sweep = interval(self.config.lease_sweep_interval);
 
         loop {
             tokio::select! {
                 // Handle incoming request
                 Some((request, response_tx)) = rx.recv() => {
+                    let is_shutdown = matches!(request, Request::Shutdown);
                     let response = self.handle_request(request);
+                    self.publish_changes();
                     let _ = response_tx.send(response).await;
+
+                    // Let the connection write the reply before the runtime goes away
+                    if is_shutdown {
+                        let _ = tokio::time::timeout(Duration::from_secs(1), response_tx.closed()).await;
+                    }
                 }
 
-                // Periodic flush (for future write coalescing)
+                // Periodic flush of coalesced writes, and pickup of records
+                // other processes appended so subscribers see them too
                 _ = flush_interval.tick() => {
-                    // Currently writes are immediate, but this is where
-                    // we would flush pending writes in a batched mode
+                    if self.config.durability == Durability::Interval
+                        && let Err(e) = self.store.flush()
+                    {
+                        log::error!("Flush failed: {}", e);
+                    }
+                    if let Err(e) = self.store.storage().refresh() {
+                        log::warn!("Refresh failed: {}", e);
+                    }
+                    self.publish_changes();
+                }
+
+                // Release claims whose holders stopped heartbeating
+                _ = lease_sweep.tick() => {
+                    match self.store.expire_leases() {
+                        Ok(released) => {
+                            for item in released {
+                                log::info!("Lease expired on {}", item.id);
+                            }
+                        }
+                        Err(e) => log::warn!("Lease sweep failed: {}", e),
+                    }
+                    self.publish_changes();
                 }
             }
 
@@ -137,6 +209,9 @@ impl Daemon {
         }
 
         // Cleanup
+        if let Err(e) = self.store.flush() {
+            log::error!("Final flush failed: {}", e);
+        }
         fs::remove_file(&socket_path).ok();
         fs::remove_file(&pid_path).ok();
 
@@ -147,6 +222,7 @@ impl Daemon {
     async fn accept_connections(
         listener: UnixListener,
         tx: mpsc::Sender<(Request, mpsc::Sender<Response>)>,
+        changes: broadcast::Sender<Change>,
         shutdown: Arc<AtomicBool>,
     ) {
         loop {
@@ -158,8 +234,9 @@ impl Daemon {
             match listener.accept() {
                 Ok((stream, _)) => {
                     let tx_clone = tx.clone();
+                    let changes = changes.clone();
                     tokio::spawn(async move {
-                        if let Err(e) = Self::handle_connection(stream, tx_clone).await {
+                        if let Err(e) = Self::handle_connection(stream, tx_clone, changes).await {
                             log::warn!("Connection error: {}", e);
                         }
                     });
@@ -177,19 +254,55 @@ impl Daemon {
     }
 
     /// Handle a single client connection.
-    async fn handle_connection(stream: UnixStream, tx: mpsc::Sender<(Request, mpsc::Sender<Response>)>) -> Result<()> {
-        stream.set_nonblocking(false)?;
-
-        let reader = BufReader::new(stream.try_clone()?);
-        let mut writer = stream;
+    ///
+    /// Uses async socket I/O so that idle connections and subscriptions don't
+    /// tie up runtime threads.
+    async fn handle_connection(
+        stream: UnixStream,
+        tx: mpsc::Sender<(Request, mpsc::Sender<Response>)>,
+        changes: broadcast::Sender<Change>,
+    ) -> Result<()> {
+        stream.set_nonblocking(true)?;
+        let stream = tokio::net::UnixStream::from_std(stream)?;
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = tokio::io::BufReader::new(reader).lines();
 
-        for line in reader.lines() {
-            let line = line.context("Failed to read line")?;
+        while let Some(line) = lines.next_line().await.context("Failed to read line")? {
             if line.is_empty() {
                 continue;
             }
 
-            let request: Request = serde_json::from_str(&line).context("Failed to parse request")?;
+            // Answer requests we cannot parse (e.g. from a newer client) instead of hanging up
+            let Envelope {
+                request_id,
+                body: request,
+            } = match serde_json::from_str::<Envelope<Request>>(&line) {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    let request_id = serde_json::from_str::<serde_json::Value>(&line)
+                        .ok()
+                        .and_then(|v| v.get("request_id").and_then(|id| id.as_u64()));
+                    let response = Response::error(format!("Invalid request: {}", e));
+                    write_response(&mut writer, request_id, &response).await?;
+                    continue;
+                }
+            };
+
+            // The handshake is answered here; it has nothing to ask of the store
+            if let Request::Hello {
+                protocol_version,
+                client_name,
+            } = request
+            {
+                log::debug!("Client {} speaks protocol version {}", client_name, protocol_version);
+                write_response(&mut writer, request_id, &Response::welcome(protocol_version)).await?;
+                continue;
+            }
+
+            // A subscription takes over the connection
+            if let Request::Subscribe { filter } = request {
+                return Self::stream_changes(writer, request_id, changes.subscribe(), filter).await;
+            }
 
             // Check for shutdown request
             let is_shutdown = matches!(request, Request::Shutdown);
@@ -201,9 +314,7 @@ impl Daemon {
                 .context("Failed to send request to daemon")?;
 
             if let Some(response) = resp_rx.recv().await {
-                let response_json = serde_json::to_string(&response)?;
-                writeln!(writer, "{}", response_json)?;
-                writer.flush()?;
+                write_response(&mut writer, request_id, &response).await?;
             }
 
             if is_shutdown {
@@ -214,6 +325,42 @@ impl Daemon {
         Ok(())
     }
 
+    /// Write matching changes to a subscribed client until it disconnects.
+    ///
+    /// Every response carries the ID of the `Subscribe` request.
+    async fn stream_changes(
+        mut writer: OwnedWriteHalf,
+        request_id: Option<u64>,
+        mut changes: broadcast::Receiver<Change>,
+        filter: ChangeFilter,
+    ) -> Result<()> {
+        write_response(&mut writer, request_id, &Response::Subscribed).await?;
+
+        loop {
+            let response = match changes.recv().await {
+                Ok(change) if filter.matches(&change) => Response::Change { change },
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    Response::error(format!("subscriber lagged, {} changes dropped", skipped))
+                }
+                Err(broadcast::error::RecvError::Closed) => return Ok(()),
+            };
+
+            // A failed write means the client went away
+            if write_response(&mut writer, request_id, &response).await.is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Send changes made by the last operation to subscribers.
+    fn publish_changes(&mut self) {
+        for change in self.store.take_changes() {
+            // No receivers is fine: nobody is watching
+            let _ = self.changes.send(change);
+        }
+    }
+
     /// Handle a single request.
     fn handle_request(&mut self, request: Request) -> Response {
         match request {
@@ -226,7 +373,7 @@ impl Daemon {
                 let label_refs: Vec<&str> = labels.iter().map(|s| s.as_str()).collect();
                 match self.store.create(&title, priority, &label_refs, description.as_deref()) {
                     Ok(item) => Response::Item { item },
-                    Err(e) => Response::error(e.to_string()),
+                    Err(e) => Response::from_report(&e),
                 }
             }
 
@@ -236,6 +383,7 @@ impl Daemon {
                 description,
                 priority,
                 labels,
+                expected_version,
             } => {
                 let labels_refs: Option<Vec<&str>> = labels.as_ref().map(|l| l.iter().map(|s| s.as_str()).collect());
                 // Convert Option<Option<String>> to Option<Option<&str>>
@@ -244,72 +392,287 @@ impl Daemon {
                     Some(None) => Some(None),
                     None => None,
                 };
-                match self
-                    .store
-                    .update(&id, title.as_deref(), desc_refs, priority, labels_refs.as_deref())
-                {
+                match self.store.update_checked(
+                    &id,
+                    expected_version,
+                    title.as_deref(),
+                    desc_refs,
+                    priority,
+                    labels_refs.as_deref(),
+                ) {
                     Ok(item) => Response::Item { item },
-                    Err(e) => Response::error(e.to_string()),
+                    Err(e) => Response::from_report(&e),
                 }
             }
 
-            Request::SetStatus { id, status } => match self.store.set_status(&id, status) {
+            Request::SetStatus {
+                id,
+                status,
+                expected_version,
+            } => match self.store.set_status_checked(&id, expected_version, status) {
                 Ok(item) => Response::Item { item },
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
             },
 
-            Request::Close { id, reason } => match self.store.close(&id, reason.as_deref()) {
+            Request::Close {
+                id,
+                reason,
+                expected_version,
+            } => match self.store.close_checked(&id, expected_version, reason.as_deref()) {
                 Ok(item) => Response::Item { item },
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::CloseTree { id, reason } => match self.store.close_tree(&id, reason.as_deref()) {
+                Ok(items) => Response::Items { items },
+                Err(e) => Response::from_report(&e),
             },
 
             Request::AddEdge { from_id, to_id, kind } => match self.store.add_edge(&from_id, &to_id, kind) {
                 Ok(edge) => Response::Edge { edge },
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
             },
 
             Request::RemoveEdge { from_id, to_id, kind } => match self.store.remove_edge(&from_id, &to_id, kind) {
                 Ok(()) => Response::Ok,
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
             },
 
+            Request::Claim { id, agent, ttl_secs } => {
+                match lease_ttl(ttl_secs).and_then(|ttl| self.store.claim(&id, &agent, ttl)) {
+                    Ok(item) => Response::Item { item },
+                    Err(e) => Response::from_report(&e),
+                }
+            }
+
+            Request::Heartbeat { id, agent, ttl_secs } => {
+                match lease_ttl(ttl_secs).and_then(|ttl| self.store.heartbeat(&id, &agent, ttl)) {
+                    Ok(item) => Response::Item { item },
+                    Err(e) => Response::from_report(&e),
+                }
+            }
+
+            Request::ClaimNext {
+                agent,
+                labels,
+                max_priority,
+                ttl_secs,
+            } => {
+                let label_refs: Vec<&str> = labels.iter().map(|s| s.as_str()).collect();
+                let ttl = lease_ttl(ttl_secs.unwrap_or(DEFAULT_LEASE_TTL_SECS));
+                match ttl.and_then(|ttl| self.store.claim_next(&agent, &label_refs, max_priority, ttl)) {
+                    Ok(Some(item)) => Response::Item { item },
+                    Ok(None) => Response::Ok,
+                    Err(e) => Response::from_report(&e),
+                }
+            }
+
             Request::Get { id } => match self.store.get(&id) {
                 Ok(Some(item)) => Response::Item { item },
                 Ok(None) => Response::NotFound { id },
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
             },
 
             Request::List { status } => match self.store.list(status) {
                 Ok(items) => Response::Items { items },
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
             },
 
             Request::Ready => match self.store.ready() {
                 Ok(items) => Response::Items { items },
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
             },
 
             Request::Blocked => match self.store.blocked() {
                 Ok(items) => Response::Items { items },
-                Err(e) => Response::error(e.to_string()),
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::ExpireLeases => match self.store.expire_leases() {
+                Ok(items) => Response::Items { items },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::Query { filter, q } => {
+                let result = match q.as_deref().map(parse_query) {
+                    Some(Err(e)) => Err(eyre::Report::new(e)),
+                    Some(Ok(Some(predicate))) => self.store.query_with_filter(&filter.matching(predicate)),
+                    _ => self.store.query_with_filter(&filter),
+                };
+                match result {
+                    Ok(items) => Response::Items { items },
+                    Err(e) => Response::from_report(&e),
+                }
+            }
+
+            Request::QueryPage { filter, cursor } => {
+                match self.store.storage().query_items_page(&filter, cursor.as_deref()) {
+                    Ok(page) => Response::ItemPage { page },
+                    Err(e) => Response::from_report(&e),
+                }
+            }
+
+            Request::Count { filter } => match self.store.storage().count_items(&filter) {
+                Ok(count) => Response::Count { count },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::Search { text, filter } => match self.store.storage().search_items(&text, &filter) {
+                Ok(hits) => Response::SearchHits { hits },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::RecordEvent {
+                kind,
+                source_task,
+                target_task,
+                payload,
+            } => match self
+                .store
+                .record_event(&kind, source_task.as_deref(), target_task.as_deref(), payload)
+            {
+                Ok(event) => Response::Event { event },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::RecordEventRaw { event } => match self.store.record_event_raw(&event) {
+                Ok(()) => Response::Ok,
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::GetEvent { id } => match self.store.get_event(&id) {
+                Ok(Some(event)) => Response::Event { event },
+                Ok(None) => Response::NotFound { id },
+                Err(e) => Response::from_report(&e),
             },
 
-            Request::Flush => {
-                // Currently a no-op since writes are immediate
-                // In future, this would flush any pending buffered writes
-                Response::Ok
+            Request::QueryEvents { filter } => match self.store.query_events(filter) {
+                Ok(events) => Response::Events { events },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::QueryEventsPage { filter, cursor } => {
+                match self.store.storage().query_events_page(&filter, cursor.as_deref()) {
+                    Ok(page) => Response::EventPage { page },
+                    Err(e) => Response::from_report(&e),
+                }
+            }
+
+            Request::RecentEvents { limit } => match self.store.recent_events(limit) {
+                Ok(events) => Response::Events { events },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::TaskEvents { task_id, limit } => match self.store.task_events(&task_id, limit) {
+                Ok(events) => Response::Events { events },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::EventCounts => match self.store.event_counts() {
+                Ok(counts) => Response::EventCounts { counts },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::TaskEventCounts { task_id } => match self.store.task_event_counts(&task_id) {
+                Ok(counts) => Response::EventCounts { counts },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::EventTimeline { since, limit } => match self.store.event_timeline(since, limit) {
+                Ok(entries) => Response::Timeline { entries },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::EventKinds => match self.store.event_kinds() {
+                Ok(kinds) => Response::EventKinds { kinds },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::BatchCreate { specs } => match self.store.batch_create(specs) {
+                Ok(result) => Response::BatchCreated { result },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::BatchClose { ids, reason } => {
+                let id_refs: Vec<&str> = ids.iter().map(|s| s.as_str()).collect();
+                match self.store.batch_close(&id_refs, reason.as_deref()) {
+                    Ok(result) => Response::BatchClosed { result },
+                    Err(e) => Response::from_report(&e),
+                }
+            }
+
+            Request::BatchSetStatus { ids, status } => {
+                let id_refs: Vec<&str> = ids.iter().map(|s| s.as_str()).collect();
+                match self.store.batch_set_status(&id_refs, status) {
+                    Ok(items) => Response::Items { items },
+                    Err(e) => Response::from_report(&e),
+                }
             }
 
+            Request::Compact { config } => match self.store.compact(&config) {
+                Ok(result) => Response::Compacted { result },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::CompactableItems { older_than_days } => match self.store.get_compactable_items(older_than_days) {
+                Ok(items) => Response::Items { items },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::Traverse {
+                id,
+                direction,
+                kinds,
+                depth,
+            } => match self.store.traverse(&id, direction, &kinds, depth) {
+                Ok(related) => Response::Related { related },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::ExplainBlocked { id } => match self.store.explain_blocked(&id) {
+                Ok(blockers) => Response::Blockers { blockers },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::Transaction { ops } => match self.store.apply_ops(ops) {
+                Ok(results) => Response::Transaction { results },
+                Err(e) => Response::from_report(&e),
+            },
+
+            Request::Flush => match self.store.flush() {
+                Ok(()) => Response::Ok,
+                Err(e) => Response::from_report(&e),
+            },
+
             Request::Shutdown => {
                 self.shutdown.store(true, Ordering::Relaxed);
-                Response::Ok
+                match self.store.flush() {
+                    Ok(()) => Response::Ok,
+                    Err(e) => Response::from_report(&e),
+                }
             }
 
+            Request::Subscribe { .. } => Response::error("Subscribe is handled by the connection, not the store"),
+
+            Request::Hello { protocol_version, .. } => Response::welcome(protocol_version),
+
             Request::Ping => Response::Pong,
         }
     }
 }
 
+/// Write a response as one JSON line, tagged with the ID of its request.
+async fn write_response(writer: &mut OwnedWriteHalf, request_id: Option<u64>, response: &Response) -> Result<()> {
+    let mut line = serde_json::to_string(&Envelope {
+        request_id,
+        body: response,
+    })?;
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
 /// Check if a daemon is running for the given store path.
 pub fn is_daemon_running(root: &Path) -> bool {
     let config = DaemonConfig::new(root);
@@ -364,6 +727,13 @@ pub fn start_daemon(root: &Path) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::batch::CreateSpec;
+    use crate::client::Client;
+    use crate::graph::Direction;
+    use crate::protocol::PROTOCOL_VERSION;
+    use crate::store::StoreError;
+    use crate::types::{EdgeKind, Filter, ValidationError};
+    use std::io::Write;
     use tempfile::TempDir;
 
     fn setup_test_store() -> (TempDir, PathBuf) {
@@ -373,6 +743,24 @@ mod tests {
         (temp_dir, root)
     }
 
+    /// Run a daemon for `root` on a background thread and wait for its socket.
+    fn spawn_daemon(root: &Path) -> std::thread::JoinHandle<()> {
+        let mut daemon = Daemon::new(DaemonConfig::new(root)).unwrap();
+        let server = std::thread::spawn(move || {
+            let rt = tokio::runtime::Runtime::new().unwrap();
+            rt.block_on(daemon.run()).unwrap();
+        });
+
+        let socket_path = DaemonConfig::new(root).socket_path();
+        for _ in 0..100 {
+            if socket_path.exists() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        server
+    }
+
     #[test]
     fn test_daemon_config() {
         let config = DaemonConfig::new("/test/path");
@@ -388,6 +776,283 @@ mod tests {
         assert!(daemon.is_ok());
     }
 
+    #[test]
+    fn test_flush_request_writes_buffered_records() {
+        let (_temp_dir, root) = setup_test_store();
+        let mut config = DaemonConfig::new(&root);
+        config.durability = Durability::OnFlush;
+        let mut daemon = Daemon::new(config).unwrap();
+
+        let items_path = root.join(".engram").join("items.jsonl");
+        for title in ["One", "Two"] {
+            let response = daemon.handle_request(Request::Create {
+                title: title.to_string(),
+                priority: 2,
+                labels: vec![],
+                description: None,
+            });
+            assert!(matches!(response, Response::Item { .. }));
+        }
+
+        // Buffered records are readable but not yet on disk
+        assert!(matches!(daemon.handle_request(Request::Ready), Response::Items { items } if items.len() == 2));
+        assert_eq!(fs::read_to_string(&items_path).unwrap(), "");
+
+        assert!(matches!(daemon.handle_request(Request::Flush), Response::Ok));
+        assert_eq!(fs::read_to_string(&items_path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_direct_writes_proceed_while_daemon_buffers() {
+        let (_temp_dir, root) = setup_test_store();
+        let mut config = DaemonConfig::new(&root);
+        config.durability = Durability::OnFlush;
+        let mut daemon = Daemon::new(config).unwrap();
+
+        let response = daemon.handle_request(Request::Create {
+            title: "Buffered".to_string(),
+            priority: 2,
+            labels: vec![],
+            description: None,
+        });
+        assert!(matches!(response, Response::Item { .. }));
+
+        // A CLI-style writer must not wait for the daemon's flush
+        let mut store = Store::open(&root).unwrap();
+        store.create("Direct", 2, &[], None).unwrap();
+        assert_eq!(store.list(None).unwrap().len(), 2);
+        drop(store);
+
+        assert!(matches!(daemon.handle_request(Request::Flush), Response::Ok));
+        let items_path = root.join(".engram").join("items.jsonl");
+        assert_eq!(fs::read_to_string(&items_path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_extension_requests() {
+        let (_temp_dir, root) = setup_test_store();
+        let mut daemon = Daemon::new(DaemonConfig::new(&root)).unwrap();
+
+        let specs = vec![
+            CreateSpec::new("Backend").with_labels(["api"]),
+            CreateSpec::new("Frontend").with_labels(["ui"]),
+        ];
+        let Response::BatchCreated { result } = daemon.handle_request(Request::BatchCreate { specs }) else {
+            panic!("Expected BatchCreated");
+        };
+        assert_eq!(result.created.len(), 2);
+
+        let filter = Filter::new().label("api");
+        let response = daemon.handle_request(Request::Query {
+            filter: filter.clone(),
+            q: None,
+        });
+        assert!(matches!(response, Response::Items { items } if items.len() == 1));
+        let response = daemon.handle_request(Request::Count { filter });
+        assert!(matches!(response, Response::Count { count: 1 }));
+        let response = daemon.handle_request(Request::Query {
+            filter: Filter::new(),
+            q: Some("label:api OR label:ui".to_string()),
+        });
+        assert!(matches!(response, Response::Items { items } if items.len() == 2));
+        let response = daemon.handle_request(Request::Query {
+            filter: Filter::new(),
+            q: Some("label:".to_string()),
+        });
+        assert!(matches!(response, Response::Error { message, .. } if message.contains("column 7")));
+        let response = daemon.handle_request(Request::QueryPage {
+            filter: Filter::new().limit(1),
+            cursor: None,
+        });
+        assert!(matches!(response, Response::ItemPage { page } if page.results.len() == 1 && page.next.is_some()));
+        let response = daemon.handle_request(Request::Search {
+            text: "frontend".to_string(),
+            filter: Filter::new(),
+        });
+        assert!(matches!(response, Response::SearchHits { hits } if hits[0].item.title == "Frontend"));
+
+        let response = daemon.handle_request(Request::RecordEvent {
+            kind: "task_started".to_string(),
+            source_task: Some(result.created[0].id.clone()),
+            target_task: None,
+            payload: serde_json::Value::Null,
+        });
+        let Response::Event { event } = response else {
+            panic!("Expected Event");
+        };
+        let response = daemon.handle_request(Request::GetEvent { id: event.id.clone() });
+        assert!(matches!(response, Response::Event { event: found } if found.id == event.id));
+
+        let response = daemon.handle_request(Request::EventCounts);
+        assert!(matches!(response, Response::EventCounts { counts } if counts.total == 1));
+        let response = daemon.handle_request(Request::EventKinds);
+        assert!(matches!(response, Response::EventKinds { kinds } if kinds == vec!["task_started"]));
+
+        let (backend, frontend) = (&result.created[0].id, &result.created[1].id);
+        daemon.handle_request(Request::AddEdge {
+            from_id: frontend.clone(),
+            to_id: backend.clone(),
+            kind: EdgeKind::Blocks,
+        });
+        let response = daemon.handle_request(Request::Traverse {
+            id: backend.clone(),
+            direction: Direction::Incoming,
+            kinds: vec![],
+            depth: None,
+        });
+        assert!(matches!(response, Response::Related { related } if related[0].item.id == *frontend));
+
+        let ids = result.created.iter().map(|i| i.id.clone()).collect();
+        let response = daemon.handle_request(Request::BatchClose { ids, reason: None });
+        assert!(matches!(response, Response::BatchClosed { result } if result.closed.len() == 2));
+    }
+
+    #[test]
+    fn test_subscribe_streams_matching_changes() {
+        let (_temp_dir, root) = setup_test_store();
+        let server = spawn_daemon(&root);
+
+        let mut subscription = Client::connect(&root, false)
+            .unwrap()
+            .subscribe(ChangeFilter::new().label("watched"))
+            .unwrap();
+
+        let mut client = Client::connect(&root, false).unwrap();
+        client.create("Ignored", 2, &[], None).unwrap();
+        let watched = client.create("Watched", 2, &["watched"], None).unwrap();
+        client.close(&watched.id, None).unwrap();
+
+        let change = subscription.next().unwrap().unwrap();
+        assert!(matches!(change, Change::ItemCreated { item } if item.id == watched.id));
+        let change = subscription.next().unwrap().unwrap();
+        assert!(matches!(change, Change::ItemClosed { item } if item.id == watched.id));
+
+        client.shutdown().unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_subscribe_sees_writes_from_other_processes() {
+        let (_temp_dir, root) = setup_test_store();
+        let server = spawn_daemon(&root);
+
+        let mut subscription = Client::connect(&root, false)
+            .unwrap()
+            .subscribe(ChangeFilter::new().label("watched"))
+            .unwrap();
+
+        // A CLI-style writer going straight to the files, not the daemon
+        let mut store = Store::open(&root).unwrap();
+        let watched = store.create("Watched", 2, &["watched"], None).unwrap();
+        store.update(&watched.id, Some("Renamed"), None, None, None).unwrap();
+        store.close(&watched.id, None).unwrap();
+        drop(store);
+
+        let change = subscription.next().unwrap().unwrap();
+        assert!(matches!(change, Change::ItemCreated { item } if item.id == watched.id));
+        let change = subscription.next().unwrap().unwrap();
+        assert!(matches!(change, Change::ItemUpdated { item } if item.title == "Renamed"));
+        let change = subscription.next().unwrap().unwrap();
+        assert!(matches!(change, Change::ItemClosed { item } if item.id == watched.id));
+
+        Client::connect(&root, false).unwrap().shutdown().unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_handshake_and_pipelined_requests() {
+        let (_temp_dir, root) = setup_test_store();
+        let server = spawn_daemon(&root);
+
+        let mut client = Client::connect(&root, false).unwrap();
+        assert_eq!(client.protocol_version(), PROTOCOL_VERSION);
+        assert!(client.has_capability("pipelining"));
+
+        let responses = client
+            .pipeline(vec![
+                Request::Create {
+                    title: "First".to_string(),
+                    priority: 2,
+                    labels: vec![],
+                    description: None,
+                },
+                Request::Ping,
+                Request::Get {
+                    id: "eg-missing0001".to_string(),
+                },
+            ])
+            .unwrap();
+        assert!(matches!(&responses[0], Response::Item { item } if item.title == "First"));
+        assert!(matches!(responses[1], Response::Pong));
+        assert!(matches!(responses[2], Response::NotFound { .. }));
+
+        // A line the daemon cannot parse gets an error, not a dropped connection
+        let mut raw = UnixStream::connect(DaemonConfig::new(&root).socket_path()).unwrap();
+        raw.write_all(b"{\"request_id\":9,\"type\":\"FromTheFuture\"}\n{\"type\":\"Ping\"}\n")
+            .unwrap();
+        let mut lines = std::io::BufRead::lines(std::io::BufReader::new(raw));
+        let first: Envelope<Response> = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        assert_eq!(first.request_id, Some(9));
+        assert!(matches!(first.body, Response::Error { .. }));
+        assert_eq!(lines.next().unwrap().unwrap(), r#"{"type":"Pong"}"#);
+
+        client.shutdown().unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_client_rebuilds_store_errors() {
+        let (_temp_dir, root) = setup_test_store();
+        let server = spawn_daemon(&root);
+
+        let mut client = Client::connect(&root, false).unwrap();
+        let item = client.create("Task", 2, &[], None).unwrap();
+
+        let err = client.close("eg-missing0001", None).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::ItemNotFound(id)) if id == "eg-missing0001"
+        ));
+
+        let err = client.add_edge(&item.id, &item.id, EdgeKind::Blocks).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::SelfReferentialEdge)
+        ));
+
+        let err = client.create("", 2, &[], None).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::Validation(ValidationError::EmptyTitle))
+        ));
+
+        let err = client
+            .claim(&item.id, "agent-a", std::time::Duration::from_secs(u64::MAX))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::Validation(ValidationError::InvalidLeaseTtl))
+        ));
+
+        let err = client
+            .claim_next("agent-a", &[], None, std::time::Duration::from_secs(u64::MAX))
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::Validation(ValidationError::InvalidLeaseTtl))
+        ));
+
+        client.update(&item.id, Some("Renamed"), None, None, None).unwrap();
+        let err = client.close_checked(&item.id, Some(item.updated_at), None).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::Conflict { current }) if current.title == "Renamed"
+        ));
+
+        client.shutdown().unwrap();
+        server.join().unwrap();
+    }
+
     #[test]
     fn test_is_daemon_running_false() {
         let (_temp_dir, root) = setup_test_store();
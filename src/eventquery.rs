@@ -2,9 +2,10 @@
 
 use crate::storage::Storage;
 use crate::store::Store;
-use crate::types::{Event, EventFilter};
+use crate::types::{Event, EventFilter, Page};
 use chrono::{DateTime, Timelike, Utc};
 use eyre::Result;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Query builder for fluent event queries.
@@ -62,10 +63,19 @@ impl<'a> EventQuery<'a> {
     pub fn execute(self) -> Result<Vec<Event>> {
         self.storage.query_events(&self.filter)
     }
+
+    /// Execute the query one page at a time, most recent first.
+    ///
+    /// Returns up to `limit` events (`DEFAULT_PAGE_SIZE` if unset) after
+    /// `cursor`, which is None for the first page and the previous page's
+    /// `next` after that. Events recorded meanwhile don't shift later pages.
+    pub fn execute_page(self, cursor: Option<&str>) -> Result<Page<Event>> {
+        self.storage.query_events_page(&self.filter, cursor)
+    }
 }
 
 /// Event counts grouped by kind.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct EventCounts {
     /// Map of event kind to count.
     pub by_kind: HashMap<String, usize>,
@@ -74,7 +84,7 @@ pub struct EventCounts {
 }
 
 /// Timeline entry representing events in a time bucket.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimelineEntry {
     /// Start of the time bucket.
     pub start: DateTime<Utc>,
@@ -160,7 +170,7 @@ impl StoreEventExt for Store {
             })
             .collect();
 
-        timeline.sort_by(|a, b| b.start.cmp(&a.start)); // Most recent first
+        timeline.sort_by_key(|entry| std::cmp::Reverse(entry.start)); // Most recent first
 
         Ok(timeline)
     }
@@ -283,4 +293,50 @@ mod tests {
         let total_events: usize = timeline.iter().map(|e| e.events.len()).sum();
         assert_eq!(total_events, 2);
     }
+
+    #[test]
+    fn test_event_pages_ignore_new_events() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        let mut recorded = Vec::new();
+        for i in 0..5 {
+            let event = store
+                .record_event("tick", None, None, serde_json::json!({ "n": i }))
+                .unwrap();
+            recorded.push(event.id);
+        }
+
+        let first = store.event_query().kind("tick").limit(2).execute_page(None).unwrap();
+        assert_eq!(first.results.len(), 2);
+
+        // A newer event sorts before the cursor, so later pages don't shift
+        store
+            .record_event("tick", None, None, serde_json::json!({ "n": 5 }))
+            .unwrap();
+
+        let mut seen: Vec<String> = first.results.into_iter().map(|e| e.id).collect();
+        let mut cursor = first.next;
+        while let Some(token) = cursor {
+            let page = store
+                .event_query()
+                .kind("tick")
+                .limit(2)
+                .execute_page(Some(&token))
+                .unwrap();
+            seen.extend(page.results.into_iter().map(|e| e.id));
+            cursor = page.next;
+        }
+
+        recorded.reverse();
+        assert_eq!(seen, recorded);
+
+        let all = store
+            .event_query()
+            .kind("tick")
+            .limit(usize::MAX)
+            .execute_page(None)
+            .unwrap();
+        assert_eq!(all.results.len(), 6);
+        assert!(all.next.is_none());
+    }
 }
@@ -0,0 +1,272 @@
+//! Render the task graph as Graphviz DOT, Mermaid or GraphML.
+//!
+//! Edges are drawn from the prerequisite to the item that waits on it: a
+//! blocker points at what it blocks and a parent at its children. Nodes are
+//! coloured by status and edges styled by kind, matching across formats.
+
+use crate::graph::StoreGraphExt;
+use crate::store::Store;
+use crate::types::{Edge, EdgeKind, Filter, Item, Status};
+use eyre::Result;
+use std::collections::HashSet;
+use std::fmt::Write;
+
+/// Output format for `render`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Graphviz DOT.
+    Dot,
+    /// Mermaid `flowchart`.
+    Mermaid,
+    /// GraphML XML.
+    GraphMl,
+}
+
+/// Which part of the graph to export.
+#[derive(Debug, Clone)]
+pub enum Selection {
+    /// Items matching a filter.
+    Filter(Box<Filter>),
+    /// An item and its descendants along `ParentChild` edges.
+    Subtree(String),
+}
+
+/// Extension trait to export the graph from Store.
+pub trait StoreExportExt {
+    /// Render the selected items, and the edges between them, as `format`.
+    fn export_graph(&self, selection: &Selection, format: ExportFormat) -> Result<String>;
+}
+
+impl StoreExportExt for Store {
+    fn export_graph(&self, selection: &Selection, format: ExportFormat) -> Result<String> {
+        let items = match selection {
+            Selection::Filter(filter) => self.storage().query_items(filter)?,
+            Selection::Subtree(root) => self
+                .subtree(root, &[EdgeKind::ParentChild])?
+                .into_iter()
+                .map(|r| r.item)
+                .collect(),
+        };
+
+        let ids: HashSet<&str> = items.iter().map(|i| i.id.as_str()).collect();
+        let edges: Vec<Edge> = self
+            .storage()
+            .list_edges()?
+            .into_iter()
+            .filter(|e| ids.contains(e.from_id.as_str()) && ids.contains(e.to_id.as_str()))
+            .collect();
+
+        Ok(render(&items, &edges, format))
+    }
+}
+
+/// Render `items` and `edges` as `format`.
+///
+/// Edges are drawn as given, even if an endpoint is missing from `items`.
+pub fn render(items: &[Item], edges: &[Edge], format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Dot => render_dot(items, edges),
+        ExportFormat::Mermaid => render_mermaid(items, edges),
+        ExportFormat::GraphMl => render_graphml(items, edges),
+    }
+}
+
+/// Fill colour for a node.
+fn status_color(status: Status) -> &'static str {
+    match status {
+        Status::Open => "#b7e1a1",
+        Status::InProgress => "#ffe08a",
+        Status::Blocked => "#f4a6a6",
+        Status::Closed => "#a6c8f4",
+    }
+}
+
+/// Status name as used in JSONL.
+fn status_name(status: Status) -> &'static str {
+    match status {
+        Status::Open => "open",
+        Status::InProgress => "in_progress",
+        Status::Blocked => "blocked",
+        Status::Closed => "closed",
+    }
+}
+
+/// Edge kind name as used in JSONL.
+fn kind_name(kind: EdgeKind) -> &'static str {
+    match kind {
+        EdgeKind::Blocks => "blocks",
+        EdgeKind::ParentChild => "parent_child",
+        EdgeKind::Related => "related",
+    }
+}
+
+fn render_dot(items: &[Item], edges: &[Edge]) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+
+    let mut out = String::from("digraph engram {\n    rankdir=LR;\n    node [shape=box, style=\"rounded,filled\"];\n");
+    for item in items {
+        let _ = writeln!(
+            out,
+            "    \"{}\" [label=\"{}\\n{}\", fillcolor=\"{}\"];",
+            item.id,
+            item.id,
+            escape(&item.title),
+            status_color(item.status)
+        );
+    }
+    for edge in edges {
+        let style = match edge.kind {
+            EdgeKind::Blocks => "style=bold",
+            EdgeKind::ParentChild => "style=dashed",
+            EdgeKind::Related => "style=dotted, dir=none",
+        };
+        let _ = writeln!(
+            out,
+            "    \"{}\" -> \"{}\" [label=\"{}\", {}];",
+            edge.to_id,
+            edge.from_id,
+            kind_name(edge.kind),
+            style
+        );
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn render_mermaid(items: &[Item], edges: &[Edge]) -> String {
+    // Mermaid node IDs may not contain '-'
+    let node = |id: &str| id.replace('-', "_");
+    let escape = |s: &str| s.replace('"', "#quot;");
+
+    let mut out = String::from("flowchart LR\n");
+    for item in items {
+        let _ = writeln!(
+            out,
+            "    {}[\"{}: {}\"]:::{}",
+            node(&item.id),
+            item.id,
+            escape(&item.title),
+            status_name(item.status)
+        );
+    }
+    for edge in edges {
+        let arrow = match edge.kind {
+            EdgeKind::Blocks => "==>|blocks|",
+            EdgeKind::ParentChild => "-->|parent_child|",
+            EdgeKind::Related => "-.-|related|",
+        };
+        let _ = writeln!(out, "    {} {} {}", node(&edge.to_id), arrow, node(&edge.from_id));
+    }
+    for status in [Status::Open, Status::InProgress, Status::Blocked, Status::Closed] {
+        let _ = writeln!(
+            out,
+            "    classDef {} fill:{}",
+            status_name(status),
+            status_color(status)
+        );
+    }
+    out
+}
+
+fn render_graphml(items: &[Item], edges: &[Edge]) -> String {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+        "  <key id=\"title\" for=\"node\" attr.name=\"title\" attr.type=\"string\"/>\n",
+        "  <key id=\"status\" for=\"node\" attr.name=\"status\" attr.type=\"string\"/>\n",
+        "  <key id=\"priority\" for=\"node\" attr.name=\"priority\" attr.type=\"int\"/>\n",
+        "  <key id=\"color\" for=\"node\" attr.name=\"color\" attr.type=\"string\"/>\n",
+        "  <key id=\"kind\" for=\"edge\" attr.name=\"kind\" attr.type=\"string\"/>\n",
+        "  <graph id=\"engram\" edgedefault=\"directed\">\n",
+    ));
+    for item in items {
+        let _ = writeln!(out, "    <node id=\"{}\">", escape(&item.id));
+        let _ = writeln!(out, "      <data key=\"title\">{}</data>", escape(&item.title));
+        let _ = writeln!(out, "      <data key=\"status\">{}</data>", status_name(item.status));
+        let _ = writeln!(out, "      <data key=\"priority\">{}</data>", item.priority);
+        let _ = writeln!(out, "      <data key=\"color\">{}</data>", status_color(item.status));
+        out.push_str("    </node>\n");
+    }
+    for edge in edges {
+        let _ = writeln!(
+            out,
+            "    <edge source=\"{}\" target=\"{}\">",
+            escape(&edge.to_id),
+            escape(&edge.from_id)
+        );
+        let _ = writeln!(out, "      <data key=\"kind\">{}</data>", kind_name(edge.kind));
+        out.push_str("    </edge>\n");
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup_graph() -> (TempDir, Store, Item, Item, Item) {
+        let temp_dir = TempDir::new().unwrap();
+        let mut store = Store::init(temp_dir.path()).unwrap();
+        let epic = store.create("Epic", 1, &["plan"], None).unwrap();
+        let task = store.create("Say \"hi\" <now>", 2, &["plan"], None).unwrap();
+        let other = store.create("Elsewhere", 2, &[], None).unwrap();
+        store.add_edge(&task.id, &epic.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&task.id, &other.id, EdgeKind::Blocks).unwrap();
+        store.close(&other.id, None).unwrap();
+        (temp_dir, store, epic, task, other)
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let (_temp_dir, store, epic, task, _) = setup_graph();
+        let all = Selection::Filter(Box::new(Filter::new()));
+
+        let dot = store.export_graph(&all, ExportFormat::Dot).unwrap();
+        assert!(dot.starts_with("digraph engram {"));
+        assert!(dot.contains(&format!(
+            "\"{}\" -> \"{}\" [label=\"parent_child\", style=dashed]",
+            epic.id, task.id
+        )));
+        assert!(dot.contains("Say \\\"hi\\\" <now>"));
+        assert!(dot.contains("fillcolor=\"#a6c8f4\""));
+
+        let mermaid = store.export_graph(&all, ExportFormat::Mermaid).unwrap();
+        assert!(mermaid.starts_with("flowchart LR\n"));
+        assert!(mermaid.contains(&format!("==>|blocks| {}", task.id.replace('-', "_"))));
+        assert!(mermaid.contains(":::closed"));
+
+        let graphml = store.export_graph(&all, ExportFormat::GraphMl).unwrap();
+        assert!(graphml.contains("Say &quot;hi&quot; &lt;now&gt;"));
+        assert_eq!(graphml.matches("<edge ").count(), 2);
+    }
+
+    #[test]
+    fn test_export_selection() {
+        let (_temp_dir, store, epic, task, other) = setup_graph();
+
+        // Only edges with both ends selected are drawn
+        let subtree = store
+            .export_graph(&Selection::Subtree(epic.id.clone()), ExportFormat::Dot)
+            .unwrap();
+        assert!(subtree.contains(&task.id));
+        assert!(!subtree.contains(&other.id));
+        assert_eq!(subtree.matches(" -> ").count(), 1);
+
+        let labelled = store
+            .export_graph(
+                &Selection::Filter(Box::new(Filter::new().label("plan"))),
+                ExportFormat::GraphMl,
+            )
+            .unwrap();
+        assert_eq!(labelled.matches("<node ").count(), 2);
+    }
+}
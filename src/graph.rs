@@ -0,0 +1,296 @@
+//! Graph traversal: ancestors, descendants and dependency closure.
+//!
+//! Edges point from the dependent item to the item it depends on: `from_id`
+//! is blocked by, or is a child of, `to_id`. Following edges forwards walks
+//! towards what an item waits on; following them backwards walks towards what
+//! waits on it. Every traversal runs as one recursive query over the SQLite
+//! cache and takes the edge kinds to follow, where an empty list means all.
+//!
+//! `explain_blocked` answers the narrower question of why `ready()` leaves an
+//! item out, using the same conditions as `ready()` itself.
+
+use crate::store::{Store, StoreError};
+use crate::types::{EdgeKind, Item};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Which way to follow edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// From `from_id` to `to_id`: towards blockers and parents.
+    Outgoing,
+    /// From `to_id` to `from_id`: towards blocked items and children.
+    Incoming,
+}
+
+/// An item reached by a traversal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Related {
+    /// The item.
+    pub item: Item,
+    /// Number of edges on the shortest path from the starting item.
+    pub depth: usize,
+}
+
+/// An unfinished item holding up another, with whatever holds it up in turn.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Blocker {
+    /// The holding item, with its status and assignee.
+    pub item: Item,
+    /// `Blocks` for a blocker, `ParentChild` for an unfinished child.
+    pub kind: EdgeKind,
+    /// What holds up this item (empty at the leaves, and when `repeated`).
+    pub blocked_by: Vec<Blocker>,
+    /// The item appears earlier in the tree, where its blockers are listed.
+    #[serde(default)]
+    pub repeated: bool,
+}
+
+/// Extension trait for graph traversal on Store.
+pub trait StoreGraphExt {
+    /// Items reachable from `id` in `direction`, nearest first.
+    ///
+    /// Walks at most `depth` edges, or the whole graph if None. The starting
+    /// item is not included.
+    fn traverse(
+        &self,
+        id: &str,
+        direction: Direction,
+        kinds: &[EdgeKind],
+        depth: Option<usize>,
+    ) -> Result<Vec<Related>>;
+
+    /// Everything `id` depends on, directly or transitively.
+    fn dependencies(&self, id: &str, kinds: &[EdgeKind], depth: Option<usize>) -> Result<Vec<Related>> {
+        self.traverse(id, Direction::Outgoing, kinds, depth)
+    }
+
+    /// Everything that depends on `id`, directly or transitively.
+    fn dependents(&self, id: &str, kinds: &[EdgeKind], depth: Option<usize>) -> Result<Vec<Related>> {
+        self.traverse(id, Direction::Incoming, kinds, depth)
+    }
+
+    /// Items with an edge pointing at `id`.
+    fn children(&self, id: &str, kinds: &[EdgeKind]) -> Result<Vec<Item>> {
+        let related = self.traverse(id, Direction::Incoming, kinds, Some(1))?;
+        Ok(related.into_iter().map(|r| r.item).collect())
+    }
+
+    /// The item `id` points at, if any.
+    ///
+    /// An item may have several parents; this returns the first in priority
+    /// order. Use `dependencies` with a depth of 1 to get all of them.
+    fn parent(&self, id: &str, kinds: &[EdgeKind]) -> Result<Option<Item>> {
+        let related = self.traverse(id, Direction::Outgoing, kinds, Some(1))?;
+        Ok(related.into_iter().next().map(|r| r.item))
+    }
+
+    /// `id` and all of its descendants, with `id` itself at depth 0.
+    fn subtree(&self, id: &str, kinds: &[EdgeKind]) -> Result<Vec<Related>>;
+
+    /// Why `id` is not ready: its unfinished blockers and children, each
+    /// expanded down to the leaves.
+    ///
+    /// Empty if nothing holds the item up; it may still be left out of
+    /// `ready()` because its own status is not Open.
+    fn explain_blocked(&self, id: &str) -> Result<Vec<Blocker>>;
+}
+
+impl StoreGraphExt for Store {
+    fn traverse(
+        &self,
+        id: &str,
+        direction: Direction,
+        kinds: &[EdgeKind],
+        depth: Option<usize>,
+    ) -> Result<Vec<Related>> {
+        if self.get(id)?.is_none() {
+            return Err(eyre::eyre!(StoreError::ItemNotFound(id.to_string())));
+        }
+
+        let found = self
+            .storage()
+            .reachable(id, kinds, depth, direction == Direction::Incoming)?;
+        Ok(found.into_iter().map(|(item, depth)| Related { item, depth }).collect())
+    }
+
+    fn subtree(&self, id: &str, kinds: &[EdgeKind]) -> Result<Vec<Related>> {
+        let root = self
+            .get(id)?
+            .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(id.to_string())))?;
+
+        let mut tree = vec![Related { item: root, depth: 0 }];
+        tree.extend(self.traverse(id, Direction::Incoming, kinds, None)?);
+        Ok(tree)
+    }
+
+    fn explain_blocked(&self, id: &str) -> Result<Vec<Blocker>> {
+        if self.get(id)?.is_none() {
+            return Err(eyre::eyre!(StoreError::ItemNotFound(id.to_string())));
+        }
+
+        let mut expanded = HashSet::from([id.to_string()]);
+        holders_of(self, id, &mut expanded)
+    }
+}
+
+/// Expand what holds up `id`, depth first.
+///
+/// Each item is expanded only where it is first reached; later occurrences are
+/// marked `repeated`. That keeps shared dependencies from being walked once
+/// per path, and stops cycles.
+fn holders_of(store: &Store, id: &str, expanded: &mut HashSet<String>) -> Result<Vec<Blocker>> {
+    let mut blockers = Vec::new();
+    for (kind, item) in store.storage().holders(id)? {
+        let repeated = !expanded.insert(item.id.clone());
+        let blocked_by = if repeated {
+            Vec::new()
+        } else {
+            holders_of(store, &item.id, expanded)?
+        };
+        blockers.push(Blocker {
+            item,
+            kind,
+            blocked_by,
+            repeated,
+        });
+    }
+    Ok(blockers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup_test_store() -> (TempDir, Store) {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Store::init(temp_dir.path()).unwrap();
+        (temp_dir, store)
+    }
+
+    fn ids(related: &[Related]) -> Vec<(&str, usize)> {
+        related.iter().map(|r| (r.item.id.as_str(), r.depth)).collect()
+    }
+
+    #[test]
+    fn test_dependency_closure() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let schema = store.create("Schema", 1, &[], None).unwrap();
+        let api = store.create("API", 2, &[], None).unwrap();
+        let ui = store.create("UI", 2, &[], None).unwrap();
+        let notes = store.create("Notes", 3, &[], None).unwrap();
+        store.add_edge(&api.id, &schema.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&ui.id, &api.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&ui.id, &schema.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&ui.id, &notes.id, EdgeKind::Related).unwrap();
+
+        // Schema is reachable in one step and in two; the shortest path wins
+        let all = store.dependencies(&ui.id, &[EdgeKind::Blocks], None).unwrap();
+        assert_eq!(ids(&all), vec![(schema.id.as_str(), 1), (api.id.as_str(), 1)]);
+
+        let any_kind = store.dependencies(&ui.id, &[], Some(1)).unwrap();
+        assert_eq!(any_kind.len(), 3);
+
+        let waiting = store.dependents(&schema.id, &[EdgeKind::Blocks], None).unwrap();
+        assert_eq!(waiting.len(), 2);
+        assert!(store.dependencies("eg-missing", &[], None).is_err());
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let epic = store.create("Epic", 1, &[], None).unwrap();
+        let story = store.create("Story", 2, &[], None).unwrap();
+        let task = store.create("Task", 2, &[], None).unwrap();
+        let kinds = [EdgeKind::ParentChild];
+        store.add_edge(&story.id, &epic.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&task.id, &story.id, EdgeKind::ParentChild).unwrap();
+
+        let children = store.children(&epic.id, &kinds).unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].id, story.id);
+        assert_eq!(store.parent(&task.id, &kinds).unwrap().unwrap().id, story.id);
+        assert!(store.parent(&epic.id, &kinds).unwrap().is_none());
+
+        let tree = store.subtree(&epic.id, &kinds).unwrap();
+        assert_eq!(
+            ids(&tree),
+            vec![(epic.id.as_str(), 0), (story.id.as_str(), 1), (task.id.as_str(), 2)]
+        );
+    }
+
+    #[test]
+    fn test_explain_blocked_agrees_with_ready() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let epic = store.create("Epic", 1, &[], None).unwrap();
+        let story = store.create("Story", 2, &[], None).unwrap();
+        let design = store.create("Design", 2, &[], None).unwrap();
+        let done = store.create("Done", 2, &[], None).unwrap();
+        store.add_edge(&story.id, &epic.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&story.id, &design.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&story.id, &done.id, EdgeKind::Blocks).unwrap();
+        store.close(&done.id, None).unwrap();
+        store
+            .claim(&design.id, "agent-1", chrono::Duration::minutes(5))
+            .unwrap();
+
+        let why = store.explain_blocked(&epic.id).unwrap();
+        assert_eq!(why.len(), 1);
+        assert_eq!(
+            (why[0].item.id.as_str(), why[0].kind),
+            (story.id.as_str(), EdgeKind::ParentChild)
+        );
+        let leaf = &why[0].blocked_by[0];
+        assert_eq!(
+            (leaf.item.id.as_str(), leaf.kind),
+            (design.id.as_str(), EdgeKind::Blocks)
+        );
+        assert_eq!(leaf.item.assignee.as_deref(), Some("agent-1"));
+        assert!(leaf.blocked_by.is_empty());
+
+        // Every Open item is either ready or has an explanation, never both
+        let ready: Vec<String> = store.ready().unwrap().into_iter().map(|i| i.id).collect();
+        for item in store.list(Some(crate::types::Status::Open)).unwrap() {
+            let held = !store.explain_blocked(&item.id).unwrap().is_empty();
+            assert_ne!(held, ready.contains(&item.id), "{}", item.title);
+        }
+    }
+
+    #[test]
+    fn test_explain_blocked_expands_shared_blockers_once() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let top = store.create("Top", 1, &[], None).unwrap();
+        let left = store.create("Left", 2, &[], None).unwrap();
+        let right = store.create("Right", 2, &[], None).unwrap();
+        let shared = store.create("Shared", 2, &[], None).unwrap();
+        let base = store.create("Base", 2, &[], None).unwrap();
+        store.add_edge(&top.id, &left.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&top.id, &right.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&left.id, &shared.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&right.id, &shared.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&shared.id, &base.id, EdgeKind::Blocks).unwrap();
+
+        let why = store.explain_blocked(&top.id).unwrap();
+        let under: Vec<&Blocker> = why.iter().map(|b| &b.blocked_by[0]).collect();
+        assert!(under.iter().all(|b| b.item.id == shared.id));
+        assert!(!under[0].repeated);
+        assert_eq!(under[0].blocked_by[0].item.id, base.id);
+        assert!(under[1].repeated);
+        assert!(under[1].blocked_by.is_empty());
+    }
+
+    #[test]
+    fn test_related_cycles_terminate() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let a = store.create("A", 2, &[], None).unwrap();
+        let b = store.create("B", 2, &[], None).unwrap();
+        store.add_edge(&a.id, &b.id, EdgeKind::Related).unwrap();
+        store.add_edge(&b.id, &a.id, EdgeKind::Related).unwrap();
+
+        let reached = store.dependencies(&a.id, &[EdgeKind::Related], None).unwrap();
+        assert_eq!(ids(&reached), vec![(b.id.as_str(), 1)]);
+    }
+}
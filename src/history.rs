@@ -0,0 +1,216 @@
+//! Item history and point-in-time views.
+//!
+//! The JSONL files keep every version of every item, while the SQLite cache
+//! only holds the latest. This module reads the logs directly to answer
+//! "how did this item change?" and "what did the graph look like then?".
+
+use crate::storage::Storage;
+use crate::store::{Store, StoreError};
+use crate::types::{Item, Status};
+use chrono::{DateTime, Utc};
+use eyre::Result;
+use serde_json::Value;
+
+/// A change to a single field between two revisions of an item.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// Field name as serialized in JSONL (e.g. "status", "labels").
+    pub field: String,
+    /// Previous value (Null if the field was unset).
+    pub old: Value,
+    /// New value (Null if the field was cleared).
+    pub new: Value,
+}
+
+/// One recorded version of an item.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// The item as written in this revision.
+    pub item: Item,
+    /// Fields that differ from the previous revision (every set field for the first).
+    pub changes: Vec<FieldChange>,
+}
+
+/// Read-only view of the graph as it stood at a point in time.
+pub struct Snapshot {
+    storage: Storage,
+    as_of: DateTime<Utc>,
+}
+
+impl Snapshot {
+    /// The point in time this snapshot represents.
+    pub fn as_of(&self) -> DateTime<Utc> {
+        self.as_of
+    }
+
+    /// Get an item by ID.
+    pub fn get(&self, id: &str) -> Result<Option<Item>> {
+        self.storage.get_item(id)
+    }
+
+    /// List items with optional status filter.
+    pub fn list(&self, status_filter: Option<Status>) -> Result<Vec<Item>> {
+        self.storage.list_items(status_filter)
+    }
+
+    /// Get items that were ready to work on.
+    pub fn ready(&self) -> Result<Vec<Item>> {
+        self.storage.ready()
+    }
+
+    /// Get items that were blocked.
+    pub fn blocked(&self) -> Result<Vec<Item>> {
+        self.storage.blocked()
+    }
+}
+
+/// Extension trait to add history methods to Store.
+pub trait StoreHistoryExt {
+    /// Get every recorded revision of an item, oldest first.
+    fn history(&self, id: &str) -> Result<Vec<Revision>>;
+
+    /// Get a read-only view of the graph as of the given time.
+    fn as_of(&self, timestamp: DateTime<Utc>) -> Result<Snapshot>;
+}
+
+impl StoreHistoryExt for Store {
+    fn history(&self, id: &str) -> Result<Vec<Revision>> {
+        let mut revisions: Vec<Revision> = Vec::new();
+
+        for item in self.storage().item_log()?.into_iter().filter(|i| i.id == id) {
+            let previous = revisions.last().map(|r| &r.item);
+
+            // Skip records that were written twice (e.g. by a merge)
+            if previous == Some(&item) {
+                continue;
+            }
+
+            let changes = diff_items(previous, &item);
+            revisions.push(Revision { item, changes });
+        }
+
+        if revisions.is_empty() {
+            return Err(eyre::eyre!(StoreError::ItemNotFound(id.to_string())));
+        }
+
+        Ok(revisions)
+    }
+
+    fn as_of(&self, timestamp: DateTime<Utc>) -> Result<Snapshot> {
+        let storage = self.storage().snapshot_at(timestamp)?;
+        Ok(Snapshot {
+            storage,
+            as_of: timestamp,
+        })
+    }
+}
+
+/// Compute field-level changes from `old` to `new`.
+///
+/// `updated_at` is omitted since it changes on every revision.
+fn diff_items(old: Option<&Item>, new: &Item) -> Vec<FieldChange> {
+    let to_map = |item: &Item| match serde_json::to_value(item) {
+        Ok(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+
+    let old_map = old.map(to_map).unwrap_or_default();
+    let new_map = to_map(new);
+
+    let mut fields: Vec<&String> = old_map.keys().chain(new_map.keys()).collect();
+    fields.sort();
+    fields.dedup();
+
+    fields
+        .into_iter()
+        .filter(|field| field.as_str() != "updated_at")
+        .filter_map(|field| {
+            let old_value = old_map.get(field).cloned().unwrap_or(Value::Null);
+            let new_value = new_map.get(field).cloned().unwrap_or(Value::Null);
+            (old_value != new_value).then(|| FieldChange {
+                field: field.clone(),
+                old: old_value,
+                new: new_value,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EdgeKind;
+    use tempfile::TempDir;
+
+    fn setup_test_store() -> (TempDir, Store) {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Store::init(temp_dir.path()).unwrap();
+        (temp_dir, store)
+    }
+
+    #[test]
+    fn test_history_records_each_revision() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        let item = store.create("Original", 2, &[], None).unwrap();
+        store.update(&item.id, Some("Renamed"), None, Some(1), None).unwrap();
+        store.close(&item.id, Some("Done")).unwrap();
+
+        let history = store.history(&item.id).unwrap();
+        assert_eq!(history.len(), 3);
+
+        // First revision lists every field that was set
+        assert!(history[0].changes.iter().any(|c| c.field == "title" && c.old.is_null()));
+
+        let renamed: Vec<&str> = history[1].changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(renamed, vec!["priority", "title"]);
+        assert_eq!(history[1].changes[1].old, Value::from("Original"));
+        assert_eq!(history[1].changes[1].new, Value::from("Renamed"));
+
+        let closed: Vec<&str> = history[2].changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(closed, vec!["close_reason", "closed_at", "status"]);
+    }
+
+    #[test]
+    fn test_history_unknown_item() {
+        let (_temp_dir, store) = setup_test_store();
+
+        let err = store.history("eg-missing0001").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::ItemNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_as_of_sees_past_graph() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        let blocker = store.create("Blocker", 1, &[], None).unwrap();
+        let blocked = store.create("Blocked", 2, &[], None).unwrap();
+        store.add_edge(&blocked.id, &blocker.id, EdgeKind::Blocks).unwrap();
+
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let before_close = Utc::now();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        store.close(&blocker.id, None).unwrap();
+        store.create("Later", 2, &[], None).unwrap();
+
+        let snapshot = store.as_of(before_close).unwrap();
+        assert_eq!(snapshot.as_of(), before_close);
+        assert_eq!(snapshot.list(None).unwrap().len(), 2);
+        assert_eq!(snapshot.get(&blocker.id).unwrap().unwrap().status, Status::Open);
+
+        let ready = snapshot.ready().unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].id, blocker.id);
+
+        let blocked_then = snapshot.blocked().unwrap();
+        assert_eq!(blocked_then.len(), 1);
+        assert_eq!(blocked_then[0].id, blocked.id);
+
+        // The live store has moved on
+        assert_eq!(store.ready().unwrap().len(), 2);
+    }
+}
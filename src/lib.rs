@@ -39,20 +39,40 @@ pub mod client;
 pub mod compact;
 pub mod daemon;
 pub mod eventquery;
+pub mod export;
+pub mod graph;
+pub mod history;
+pub mod memory;
+pub mod merge;
+pub mod plan;
 pub mod protocol;
 pub mod query;
+pub mod taskgraph;
+pub mod transaction;
 pub mod vacuum;
 
 // Re-export public API
 pub use batch::{BatchCloseResult, BatchCreateResult, CreateSpec, StoreBatchExt};
 pub use builder::{ItemBuilder, StoreBuilderExt};
-pub use client::Client;
+pub use client::{Client, Subscription};
 pub use compact::{CompactConfig, CompactResult, StoreCompactExt};
-pub use daemon::{Daemon, DaemonConfig, is_daemon_running, start_daemon};
+pub use daemon::{Daemon, DaemonConfig, Durability, is_daemon_running, start_daemon};
 pub use eventquery::{EventCounts, EventQuery, StoreEventExt, TimelineEntry};
+pub use export::{ExportFormat, Selection, StoreExportExt, render};
+pub use graph::{Blocker, Direction, Related, StoreGraphExt};
+pub use history::{FieldChange, Revision, Snapshot, StoreHistoryExt};
 pub use id::generate_event_id;
-pub use protocol::{Request, Response};
-pub use query::{Query, StoreQueryExt};
-pub use store::{Store, StoreError};
-pub use types::{Edge, EdgeKind, Event, EventFilter, Filter, Item, Status, ValidationError};
+pub use memory::MemoryGraph;
+pub use merge::{MergeResult, RecordKind, merge_files, merge_jsonl};
+pub use plan::{CriticalPath, StorePlanExt};
+pub use protocol::{Envelope, ErrorKind, PROTOCOL_VERSION, Request, Response};
+pub use query::{Query, QueryParseError, StoreQueryExt, parse_filter, parse_query};
+pub use storage::{DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE, RECORD_VERSION, SCHEMA_VERSION};
+pub use store::{DEFAULT_LEASE_TTL_SECS, Store, StoreError, lease_ttl};
+pub use taskgraph::{TaskGraph, connect_or_open};
+pub use transaction::{StoreTransactionExt, TxOp, TxResult};
+pub use types::{
+    Change, ChangeFilter, Comparison, Edge, EdgeKind, Event, EventFilter, Filter, Item, OrderBy, Page, ParentPolicy,
+    Policy, Predicate, SearchHit, SortColumn, SortDirection, Status, ValidationError,
+};
 pub use vacuum::{VacuumResult, vacuum};
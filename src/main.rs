@@ -2,16 +2,31 @@
 
 use clap::Parser;
 use colored::*;
-use engram::{Client, Daemon, DaemonConfig, EdgeKind, Status, Store, StoreEventExt, is_daemon_running};
+use engram::{
+    Blocker, Change, ChangeFilter, Client, Daemon, DaemonConfig, EdgeKind, Filter, Selection, Status, Store,
+    StoreEventExt, StoreExportExt, StoreGraphExt, StorePlanExt, StoreQueryExt, connect_or_open, is_daemon_running,
+    lease_ttl, merge_files, parse_filter,
+};
 use eyre::{Context, Result};
 use log::info;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 mod cli;
 
 use cli::{Cli, Command};
 
+/// Connect to the daemon serving `root`, if one is running.
+///
+/// Writes go through it when it is, so they land in its buffer rather than
+/// racing its flushes.
+fn daemon_client(root: &Path) -> Option<Client> {
+    if !is_daemon_running(root) {
+        return None;
+    }
+    Client::connect(root, false).ok()
+}
+
 fn setup_logging() -> Result<()> {
     let log_dir = dirs::data_local_dir()
         .unwrap_or_else(|| PathBuf::from("."))
@@ -53,6 +68,73 @@ fn format_status(status: &Status) -> ColoredString {
     }
 }
 
+fn format_change(change: &Change) -> String {
+    match change {
+        Change::ItemCreated { item } => format!("{} {} {}", "created".green(), item.id.cyan(), item.title),
+        Change::ItemUpdated { item } => format!(
+            "{} {} {} {}",
+            "updated".yellow(),
+            item.id.cyan(),
+            format_status(&item.status),
+            item.title
+        ),
+        Change::ItemClosed { item } => format!("{} {} {}", "closed".blue(), item.id.cyan(), item.title),
+        Change::EdgeAdded { edge } => format!(
+            "{} {} -{:?}-> {}",
+            "linked".green(),
+            edge.from_id.cyan(),
+            edge.kind,
+            edge.to_id.cyan()
+        ),
+        Change::EdgeRemoved { edge } => format!(
+            "{} {} -{:?}-> {}",
+            "unlinked".red(),
+            edge.from_id.cyan(),
+            edge.kind,
+            edge.to_id.cyan()
+        ),
+        Change::EventRecorded { event } => format!(
+            "{} {} {}",
+            "event".magenta(),
+            event.kind,
+            event.source_task.as_deref().unwrap_or("-").cyan()
+        ),
+    }
+}
+
+fn print_blockers(blockers: &[Blocker], depth: usize) {
+    for blocker in blockers {
+        let relation = match blocker.kind {
+            EdgeKind::ParentChild => "has open child",
+            _ => "blocked by",
+        };
+        let assignee = blocker
+            .item
+            .assignee
+            .as_ref()
+            .map(|a| format!(" @{}", a))
+            .unwrap_or_default();
+
+        let repeated = if blocker.repeated {
+            format!(" {}", "(see above)".dimmed())
+        } else {
+            String::new()
+        };
+
+        println!(
+            "{}{} {} [{}{}] {}{}",
+            "  ".repeat(depth + 1),
+            relation.dimmed(),
+            blocker.item.id.cyan(),
+            format_status(&blocker.item.status),
+            assignee,
+            blocker.item.title,
+            repeated
+        );
+        print_blockers(&blocker.blocked_by, depth + 1);
+    }
+}
+
 fn run(cli: Cli) -> Result<()> {
     let store_dir = get_store_dir(&cli);
 
@@ -68,30 +150,31 @@ fn run(cli: Cli) -> Result<()> {
             labels,
             description,
         } => {
-            let mut store = Store::open(&store_dir).context("Failed to open store")?;
+            let mut graph = connect_or_open(&store_dir)?;
             let label_refs: Vec<&str> = labels
                 .as_ref()
                 .map(|l| l.iter().map(|s| s.as_str()).collect())
                 .unwrap_or_default();
 
-            let item = store
+            let item = graph
                 .create(&title, priority, &label_refs, description.as_deref())
                 .context("Failed to create item")?;
 
             println!("{} Created: {} {}", "✓".green(), item.id.cyan(), item.title);
         }
 
-        Command::List { status } => {
+        Command::List { status, query } => {
             let store = Store::open(&store_dir).context("Failed to open store")?;
-            let status_filter = status.as_ref().and_then(|s| match s.as_str() {
-                "open" => Some(Status::Open),
-                "in_progress" => Some(Status::InProgress),
-                "blocked" => Some(Status::Blocked),
-                "closed" => Some(Status::Closed),
-                _ => None,
-            });
-
-            let items = store.list(status_filter).context("Failed to list items")?;
+            let items = match query {
+                Some(query) => {
+                    // Point at the offending character under the query
+                    let mut filter = parse_filter(&query)
+                        .map_err(|e| eyre::eyre!("{}\n  {}\n  {}^", e, query, " ".repeat(e.position)))?;
+                    filter.status = status;
+                    store.query_with_filter(&filter).context("Failed to list items")?
+                }
+                None => store.list(status).context("Failed to list items")?,
+            };
 
             if items.is_empty() {
                 println!("{}", "No items found".dimmed());
@@ -145,11 +228,23 @@ fn run(cli: Cli) -> Result<()> {
             }
         }
 
-        Command::Close { id, reason } => {
-            let mut store = Store::open(&store_dir).context("Failed to open store")?;
-            let item = store.close(&id, reason.as_deref()).context("Failed to close item")?;
+        Command::Close { id, reason, tree } => {
+            let items = if tree {
+                match daemon_client(&store_dir) {
+                    Some(mut client) => client.close_tree(&id, reason.as_deref()),
+                    None => Store::open(&store_dir)
+                        .context("Failed to open store")?
+                        .close_tree(&id, reason.as_deref()),
+                }
+                .context("Failed to close tree")?
+            } else {
+                let mut graph = connect_or_open(&store_dir)?;
+                vec![graph.close(&id, reason.as_deref()).context("Failed to close item")?]
+            };
 
-            println!("{} Closed: {} {}", "✓".green(), item.id.cyan(), item.title);
+            for item in items {
+                println!("{} Closed: {} {}", "✓".green(), item.id.cyan(), item.title);
+            }
         }
 
         Command::Get { id } => {
@@ -165,6 +260,12 @@ fn run(cli: Cli) -> Result<()> {
                     if !item.labels.is_empty() {
                         println!("{}: {}", "Labels".bold(), item.labels.join(", "));
                     }
+                    if let Some(assignee) = &item.assignee {
+                        println!("{}: {}", "Assignee".bold(), assignee);
+                    }
+                    if let Some(expires) = &item.lease_expires_at {
+                        println!("{}: {}", "Lease Expires".bold(), expires);
+                    }
                     if let Some(desc) = &item.description {
                         println!("{}: {}", "Description".bold(), desc);
                     }
@@ -184,18 +285,173 @@ fn run(cli: Cli) -> Result<()> {
             }
         }
 
-        Command::Start { id } => {
-            let mut store = Store::open(&store_dir).context("Failed to open store")?;
+        Command::Search {
+            text,
+            status,
+            labels,
+            limit,
+        } => {
+            let store = Store::open(&store_dir).context("Failed to open store")?;
+            let mut query = store.query().limit(limit);
+            if let Some(status) = status {
+                query = query.status(status);
+            }
+            if let Some(labels) = labels {
+                query = query.labels(labels);
+            }
+
+            let hits = query.search(&text.join(" ")).context("Failed to search")?;
+
+            if hits.is_empty() {
+                println!("{}", "No matches".dimmed());
+            } else {
+                for hit in hits {
+                    println!(
+                        "{} {} P{} {}",
+                        format_status(&hit.item.status),
+                        hit.item.id.cyan(),
+                        hit.item.priority,
+                        hit.item.title
+                    );
+                    println!("    {}", hit.snippet.dimmed());
+                }
+            }
+        }
+
+        Command::Graph {
+            format,
+            root,
+            status,
+            labels,
+        } => {
+            let store = Store::open(&store_dir).context("Failed to open store")?;
+            let selection = match root {
+                Some(root) => Selection::Subtree(root),
+                None => {
+                    let mut filter = Filter::new();
+                    if let Some(status) = status {
+                        filter = filter.status(status);
+                    }
+                    if let Some(labels) = labels {
+                        filter = filter.labels(labels);
+                    }
+                    Selection::Filter(Box::new(filter))
+                }
+            };
+
+            print!(
+                "{}",
+                store
+                    .export_graph(&selection, format.into())
+                    .context("Failed to export graph")?
+            );
+        }
+
+        Command::Plan { id } => {
+            let store = Store::open(&store_dir).context("Failed to open store")?;
+            let waves = store.waves(&id).context("Failed to plan item")?;
+
+            if waves.is_empty() {
+                println!("{}", "Nothing left to do".dimmed());
+            } else {
+                let critical: Vec<String> = store
+                    .critical_path(&id)
+                    .context("Failed to find critical path")?
+                    .items
+                    .into_iter()
+                    .map(|i| i.id)
+                    .collect();
+
+                for (index, wave) in waves.iter().enumerate() {
+                    println!("{}", format!("Wave {}", index + 1).bold());
+                    for item in wave {
+                        let marker = if critical.contains(&item.id) {
+                            "*".red()
+                        } else {
+                            " ".normal()
+                        };
+                        println!(
+                            " {} {} [P{}] {} {}",
+                            marker,
+                            item.id.cyan(),
+                            item.priority,
+                            format_status(&item.status),
+                            item.title
+                        );
+                    }
+                }
+                println!("{}", "* on the critical path".dimmed());
+            }
+        }
+
+        Command::Why { id } => {
+            let store = Store::open(&store_dir).context("Failed to open store")?;
             let item = store
+                .get(&id)
+                .context("Failed to get item")?
+                .ok_or_else(|| eyre::eyre!("Item not found: {}", id))?;
+            let blockers = store.explain_blocked(&id).context("Failed to explain item")?;
+
+            if !blockers.is_empty() {
+                println!("{} {} is waiting on:", item.id.cyan(), item.title);
+                print_blockers(&blockers, 0);
+            } else if item.status == Status::Open {
+                println!("{} {} is ready", "✓".green(), item.id.cyan());
+            } else {
+                println!(
+                    "{} has nothing blocking it but is {}",
+                    item.id.cyan(),
+                    format_status(&item.status)
+                );
+            }
+        }
+
+        Command::Start { id } => {
+            let mut graph = connect_or_open(&store_dir)?;
+            let item = graph
                 .set_status(&id, Status::InProgress)
                 .context("Failed to start item")?;
 
             println!("{} Started: {} {}", "→".blue(), item.id.cyan(), item.title);
         }
 
+        Command::Next {
+            agent,
+            labels,
+            max_priority,
+            ttl,
+        } => {
+            let labels = labels.unwrap_or_default();
+            let label_refs: Vec<&str> = labels.iter().map(|s| s.as_str()).collect();
+            let lease = lease_ttl(ttl)?;
+            let item = match daemon_client(&store_dir) {
+                Some(mut client) => {
+                    client.claim_next(&agent, &label_refs, max_priority, std::time::Duration::from_secs(ttl))
+                }
+                None => Store::open(&store_dir).context("Failed to open store")?.claim_next(
+                    &agent,
+                    &label_refs,
+                    max_priority,
+                    lease,
+                ),
+            }
+            .context("Failed to claim next item")?;
+
+            match item {
+                Some(item) => println!(
+                    "{} Claimed: {} P{} {}",
+                    "→".blue(),
+                    item.id.cyan(),
+                    item.priority,
+                    item.title
+                ),
+                None => println!("{}", "No ready items".dimmed()),
+            }
+        }
+
         Command::Block { blocked_id, blocker_id } => {
-            let mut store = Store::open(&store_dir).context("Failed to open store")?;
-            store
+            let mut graph = connect_or_open(&store_dir)?;
+            graph
                 .add_edge(&blocked_id, &blocker_id, EdgeKind::Blocks)
                 .context("Failed to add blocking edge")?;
 
@@ -208,8 +464,8 @@ fn run(cli: Cli) -> Result<()> {
         }
 
         Command::Child { parent_id, child_id } => {
-            let mut store = Store::open(&store_dir).context("Failed to open store")?;
-            store
+            let mut graph = connect_or_open(&store_dir)?;
+            graph
                 .add_edge(&child_id, &parent_id, EdgeKind::ParentChild)
                 .context("Failed to add parent-child relationship")?;
 
@@ -221,6 +477,27 @@ fn run(cli: Cli) -> Result<()> {
             );
         }
 
+        Command::Policy {
+            auto_block,
+            on_children_closed,
+        } => {
+            let mut store = Store::open(&store_dir).context("Failed to open store")?;
+            let mut policy = store.policy().context("Failed to read policy")?;
+
+            // Without flags this only shows the policy
+            if auto_block.is_some() || on_children_closed.is_some() {
+                if let Some(auto_block) = auto_block {
+                    policy.auto_block = auto_block;
+                }
+                if let Some(action) = on_children_closed {
+                    policy.on_children_closed = action.into();
+                }
+                store.set_policy(&policy).context("Failed to update policy")?;
+            }
+
+            println!("{}", serde_json::to_string_pretty(&policy)?);
+        }
+
         Command::Daemon => {
             println!("{} Starting daemon for {}", "→".blue(), store_dir.display());
 
@@ -243,12 +520,28 @@ fn run(cli: Cli) -> Result<()> {
             println!("{} Daemon stopped", "✓".green());
         }
 
+        Command::Watch { labels, ids, kinds } => {
+            let filter = ChangeFilter {
+                labels,
+                item_ids: ids,
+                event_kinds: kinds,
+            };
+            let client = Client::connect(&store_dir, true).context("Failed to connect to daemon")?;
+
+            for change in client.subscribe(filter).context("Failed to subscribe")? {
+                match change {
+                    Ok(change) => println!("{}", format_change(&change)),
+                    Err(e) => eprintln!("{} {}", "✗".red(), e),
+                }
+            }
+        }
+
         Command::DaemonStatus => {
             if is_daemon_running(&store_dir) {
                 println!("{} Daemon is running", "✓".green());
 
                 // Try to ping
-                if let Ok(mut client) = Client::connect(&store_dir, false)
+                if let Ok(client) = Client::connect(&store_dir, false)
                     && client.ping().is_ok()
                 {
                     println!("  {} Responding to requests", "✓".green());
@@ -331,6 +624,11 @@ fn run(cli: Cli) -> Result<()> {
                 }
             }
         }
+
+        Command::MergeDriver { base, ours, theirs } => {
+            let result = merge_files(&base, &ours, &theirs).context("Failed to merge JSONL files")?;
+            info!("Merged {} record(s) of kind {:?}", result.records, result.kind);
+        }
     }
 
     Ok(())
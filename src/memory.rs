@@ -0,0 +1,406 @@
+//! In-memory task graph.
+//!
+//! Holds items, edges and events in plain collections with the same
+//! semantics as [`Store`](crate::Store), but nothing is persisted. Useful for
+//! tests and short-lived planning where a `.engram` directory is overkill.
+
+use crate::id::{generate_event_id, generate_id};
+use crate::store::StoreError;
+use crate::taskgraph::TaskGraph;
+use crate::types::{Edge, EdgeKind, Event, EventFilter, Filter, Item, SortDirection, Status};
+use chrono::Utc;
+use eyre::Result;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// A task graph held entirely in memory.
+#[derive(Debug, Default)]
+pub struct MemoryGraph {
+    items: HashMap<String, Item>,
+    edges: Vec<Edge>,
+    events: Vec<Event>,
+}
+
+impl MemoryGraph {
+    /// Create an empty graph.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get an item or fail with `ItemNotFound`.
+    fn existing(&self, id: &str) -> Result<Item> {
+        self.items
+            .get(id)
+            .cloned()
+            .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(id.to_string())))
+    }
+
+    /// Items sorted the way the store returns them.
+    fn sorted(items: impl Iterator<Item = Item>) -> Vec<Item> {
+        let mut items: Vec<Item> = items.collect();
+        items.sort_by(|a, b| (a.priority, a.created_at, &a.id).cmp(&(b.priority, b.created_at, &b.id)));
+        items
+    }
+
+    /// Whether `id` has an open `kind` edge towards another unclosed item.
+    fn has_open_edge(&self, id: &str, kind: EdgeKind, outgoing: bool) -> bool {
+        self.edges.iter().filter(|e| e.kind == kind).any(|e| {
+            let (this, other) = if outgoing {
+                (&e.from_id, &e.to_id)
+            } else {
+                (&e.to_id, &e.from_id)
+            };
+            this == id && self.items.get(other).is_some_and(|i| i.status != Status::Closed)
+        })
+    }
+
+    /// Check if adding an edge would create a cycle of items waiting on each
+    /// other, as `Store::add_edge` does.
+    fn would_create_cycle(&self, from_id: &str, to_id: &str, kind: EdgeKind) -> bool {
+        let (waiter, holder) = kind.waiter_and_holder(from_id, to_id);
+        let mut visited = HashSet::new();
+        let mut stack = vec![holder.to_string()];
+
+        while let Some(node) = stack.pop() {
+            if node == waiter {
+                return true;
+            }
+            if visited.insert(node.clone()) {
+                for edge in self.edges.iter().filter(|e| e.kind.is_blocking()) {
+                    let (edge_waiter, edge_holder) = edge.kind.waiter_and_holder(&edge.from_id, &edge.to_id);
+                    if edge_waiter == node {
+                        stack.push(edge_holder.to_string());
+                    }
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Persist an updated item after validating it.
+    fn put(&mut self, item: Item) -> Result<Item> {
+        item.validate().map_err(|e| eyre::eyre!(StoreError::Validation(e)))?;
+        self.items.insert(item.id.clone(), item.clone());
+        Ok(item)
+    }
+}
+
+impl TaskGraph for MemoryGraph {
+    fn create(&mut self, title: &str, priority: u8, labels: &[&str], description: Option<&str>) -> Result<Item> {
+        let now = Utc::now();
+        self.put(Item {
+            id: generate_id(title, now),
+            title: title.to_string(),
+            description: description.map(String::from),
+            status: Status::Open,
+            priority,
+            labels: labels.iter().map(|s| s.to_string()).collect(),
+            created_at: now,
+            updated_at: now,
+            closed_at: None,
+            close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
+        })
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Item>> {
+        Ok(self.items.get(id).cloned())
+    }
+
+    fn update(
+        &mut self,
+        id: &str,
+        title: Option<&str>,
+        description: Option<Option<&str>>,
+        priority: Option<u8>,
+        labels: Option<&[&str]>,
+    ) -> Result<Item> {
+        let existing = self.existing(id)?;
+        self.put(Item {
+            title: title.map(String::from).unwrap_or(existing.title),
+            description: match description {
+                Some(d) => d.map(String::from),
+                None => existing.description,
+            },
+            priority: priority.unwrap_or(existing.priority),
+            labels: labels
+                .map(|l| l.iter().map(|s| s.to_string()).collect())
+                .unwrap_or(existing.labels),
+            updated_at: Utc::now(),
+            ..existing
+        })
+    }
+
+    fn set_status(&mut self, id: &str, status: Status) -> Result<Item> {
+        let existing = self.existing(id)?;
+        if !existing.status.can_transition_to(&status) {
+            return Err(eyre::eyre!(StoreError::InvalidStatusTransition {
+                from: existing.status,
+                to: status
+            }));
+        }
+
+        let (assignee, lease_expires_at) = match status {
+            Status::InProgress => (existing.assignee.clone(), existing.lease_expires_at),
+            Status::Open => (None, None),
+            _ => (existing.assignee.clone(), None),
+        };
+
+        self.put(Item {
+            status,
+            updated_at: Utc::now(),
+            assignee,
+            lease_expires_at,
+            ..existing
+        })
+    }
+
+    fn close(&mut self, id: &str, reason: Option<&str>) -> Result<Item> {
+        let existing = self.existing(id)?;
+        if !existing.status.can_transition_to(&Status::Closed) {
+            return Err(eyre::eyre!(StoreError::InvalidStatusTransition {
+                from: existing.status,
+                to: Status::Closed
+            }));
+        }
+
+        let now = Utc::now();
+        self.put(Item {
+            status: Status::Closed,
+            updated_at: now,
+            closed_at: Some(now),
+            close_reason: reason.map(String::from),
+            lease_expires_at: None,
+            ..existing
+        })
+    }
+
+    fn list(&self, status_filter: Option<Status>) -> Result<Vec<Item>> {
+        let items = self
+            .items
+            .values()
+            .filter(|i| status_filter.is_none_or(|s| i.status == s));
+        Ok(Self::sorted(items.cloned()))
+    }
+
+    fn ready(&self) -> Result<Vec<Item>> {
+        let items = self.items.values().filter(|i| {
+            i.status == Status::Open
+                && !self.has_open_edge(&i.id, EdgeKind::Blocks, true)
+                && !self.has_open_edge(&i.id, EdgeKind::ParentChild, false)
+        });
+        Ok(Self::sorted(items.cloned()))
+    }
+
+    fn blocked(&self) -> Result<Vec<Item>> {
+        let items = self
+            .items
+            .values()
+            .filter(|i| i.status != Status::Closed && self.has_open_edge(&i.id, EdgeKind::Blocks, true));
+        Ok(Self::sorted(items.cloned()))
+    }
+
+    fn add_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<Edge> {
+        if from_id == to_id {
+            return Err(eyre::eyre!(StoreError::SelfReferentialEdge));
+        }
+        self.existing(from_id)?;
+        self.existing(to_id)?;
+
+        let edge = Edge {
+            from_id: from_id.to_string(),
+            to_id: to_id.to_string(),
+            kind,
+            created_at: Utc::now(),
+            deleted: false,
+        };
+
+        if self
+            .edges
+            .iter()
+            .any(|e| e.from_id == from_id && e.to_id == to_id && e.kind == kind)
+        {
+            return Ok(edge);
+        }
+
+        if kind.is_blocking() && self.would_create_cycle(from_id, to_id, kind) {
+            return Err(eyre::eyre!(StoreError::CycleDetected));
+        }
+
+        self.edges.push(edge.clone());
+        Ok(edge)
+    }
+
+    fn remove_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<()> {
+        self.edges
+            .retain(|e| !(e.from_id == from_id && e.to_id == to_id && e.kind == kind));
+        Ok(())
+    }
+
+    fn query_items(&self, filter: &Filter) -> Result<Vec<Item>> {
+        // Substring filters are case-insensitive; a missing field never matches
+        let contains = |text: Option<&str>, sub: &Option<String>| {
+            sub.as_ref()
+                .is_none_or(|sub| text.is_some_and(|t| t.to_lowercase().contains(&sub.to_lowercase())))
+        };
+        // Empty label and status lists don't filter, as in the store
+        let any_of = |list: &Option<Vec<String>>, i: &Item| {
+            list.as_ref()
+                .is_none_or(|labels| labels.is_empty() || labels.iter().any(|l| i.labels.contains(l)))
+        };
+        let closed_in = |i: &Item, bound: Option<_>, after: bool| {
+            bound.is_none_or(|b| i.closed_at.is_some_and(|c| if after { c >= b } else { c < b }))
+        };
+
+        let items = self.items.values().filter(|i| {
+            filter.status.is_none_or(|s| i.status == s)
+                && filter
+                    .statuses
+                    .as_ref()
+                    .is_none_or(|s| s.is_empty() || s.contains(&i.status))
+                && any_of(&filter.labels, i)
+                && filter.all_labels.iter().flatten().all(|l| i.labels.contains(l))
+                && !filter.without_labels.iter().flatten().any(|l| i.labels.contains(l))
+                && filter.min_priority.is_none_or(|p| i.priority >= p)
+                && filter.max_priority.is_none_or(|p| i.priority <= p)
+                && contains(Some(&i.title), &filter.title_contains)
+                && contains(i.description.as_deref(), &filter.description_contains)
+                && contains(i.close_reason.as_deref(), &filter.close_reason_contains)
+                && filter.created_after.is_none_or(|t| i.created_at >= t)
+                && filter.created_before.is_none_or(|t| i.created_at < t)
+                && filter.updated_after.is_none_or(|t| i.updated_at >= t)
+                && filter.updated_before.is_none_or(|t| i.updated_at < t)
+                && closed_in(i, filter.closed_after, true)
+                && closed_in(i, filter.closed_before, false)
+                && filter.predicate.as_ref().is_none_or(|p| p.matches(i))
+        });
+
+        let mut items = Self::sorted(items.cloned());
+        // Stable, so ties keep the default order
+        items.sort_by(|a, b| {
+            filter.order_by.iter().fold(Ordering::Equal, |ord, order| {
+                ord.then_with(|| match order.direction {
+                    SortDirection::Asc => order.column.compare(a, b),
+                    SortDirection::Desc => order.column.compare(b, a),
+                })
+            })
+        });
+
+        Ok(items
+            .into_iter()
+            .skip(filter.offset.unwrap_or(0))
+            .take(filter.limit.unwrap_or(usize::MAX))
+            .collect())
+    }
+
+    fn count_items(&self, filter: &Filter) -> Result<usize> {
+        let unbounded = Filter {
+            limit: None,
+            offset: None,
+            ..filter.clone()
+        };
+        Ok(self.query_items(&unbounded)?.len())
+    }
+
+    fn record_event(
+        &mut self,
+        kind: &str,
+        source_task: Option<&str>,
+        target_task: Option<&str>,
+        payload: serde_json::Value,
+    ) -> Result<Event> {
+        let now = Utc::now();
+        let event = Event {
+            id: generate_event_id(kind, now),
+            kind: kind.to_string(),
+            source_task: source_task.map(String::from),
+            target_task: target_task.map(String::from),
+            payload,
+            timestamp: now,
+        };
+        self.events.push(event.clone());
+        Ok(event)
+    }
+
+    fn get_event(&self, id: &str) -> Result<Option<Event>> {
+        Ok(self.events.iter().find(|e| e.id == id).cloned())
+    }
+
+    fn query_events(&self, filter: EventFilter) -> Result<Vec<Event>> {
+        let mut events: Vec<Event> = self
+            .events
+            .iter()
+            .filter(|e| {
+                filter
+                    .kinds
+                    .as_ref()
+                    .is_none_or(|kinds| kinds.is_empty() || kinds.contains(&e.kind))
+                    && filter
+                        .source_task
+                        .as_ref()
+                        .is_none_or(|s| e.source_task.as_ref() == Some(s))
+                    && filter
+                        .target_task
+                        .as_ref()
+                        .is_none_or(|t| e.target_task.as_ref() == Some(t))
+                    && filter.since.is_none_or(|since| e.timestamp >= since)
+            })
+            .cloned()
+            .collect();
+
+        // Most recent first
+        events.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+        events.truncate(filter.limit.unwrap_or(usize::MAX));
+        Ok(events)
+    }
+
+    fn recent_events(&self, limit: usize) -> Result<Vec<Event>> {
+        self.query_events(EventFilter::new().limit(limit))
+    }
+
+    fn task_events(&self, task_id: &str, limit: usize) -> Result<Vec<Event>> {
+        let mut events: Vec<Event> = self
+            .events
+            .iter()
+            .filter(|e| e.source_task.as_deref() == Some(task_id) || e.target_task.as_deref() == Some(task_id))
+            .cloned()
+            .collect();
+
+        events.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+        events.truncate(limit);
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parent_waits_for_children() {
+        let mut graph = MemoryGraph::new();
+        let parent = graph.create("Parent", 1, &[], None).unwrap();
+        let child = graph.create("Child", 2, &[], None).unwrap();
+        graph.add_edge(&child.id, &parent.id, EdgeKind::ParentChild).unwrap();
+
+        let ready: Vec<String> = graph.ready().unwrap().into_iter().map(|i| i.id).collect();
+        assert_eq!(ready, vec![child.id.clone()]);
+
+        graph.close(&child.id, None).unwrap();
+        assert_eq!(graph.ready().unwrap()[0].id, parent.id);
+    }
+
+    #[test]
+    fn test_query_paginates_in_priority_order() {
+        let mut graph = MemoryGraph::new();
+        for priority in [3, 1, 2] {
+            graph.create(&format!("P{}", priority), priority, &[], None).unwrap();
+        }
+
+        let page = graph.query_items(&Filter::new().offset(1).limit(1)).unwrap();
+        assert_eq!(page[0].title, "P2");
+        assert_eq!(graph.count_items(&Filter::new().limit(1)).unwrap(), 3);
+    }
+}
@@ -0,0 +1,345 @@
+//! Three-way merge of engram JSONL files, for use as a git merge driver.
+//!
+//! The JSONL files are append-only logs replayed with last-write-wins
+//! semantics, so textual conflicts between branches are almost always
+//! spurious. This module merges them record by record instead:
+//!
+//! - Items keep every distinct revision, ordered so that the revision with the
+//!   latest `updated_at` is replayed last and wins.
+//! - Edges keep every distinct record, ordered by `created_at` so the latest
+//!   add or tombstone for each `(from_id, to_id, kind)` wins.
+//! - Events are unioned by `id`.
+//!
+//! Register the driver once per clone:
+//!
+//! ```text
+//! git config merge.engram.name "engram JSONL merge"
+//! git config merge.engram.driver "eg merge-driver %O %A %B"
+//! echo ".engram/*.jsonl merge=engram" >> .gitattributes
+//! ```
+
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result, bail};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// The kind of records held by a JSONL file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    /// Items from items.jsonl.
+    Items,
+    /// Edges from edges.jsonl.
+    Edges,
+    /// Events from events.jsonl.
+    Events,
+}
+
+impl RecordKind {
+    /// Detect the record kind from a parsed record.
+    fn detect(record: &Value) -> Option<Self> {
+        if record.get("from_id").is_some() {
+            Some(RecordKind::Edges)
+        } else if record.get("title").is_some() {
+            Some(RecordKind::Items)
+        } else if record.get("kind").is_some() && record.get("timestamp").is_some() {
+            Some(RecordKind::Events)
+        } else {
+            None
+        }
+    }
+
+    /// Field holding the timestamp used to order records.
+    fn timestamp_field(self) -> &'static str {
+        match self {
+            RecordKind::Items => "updated_at",
+            RecordKind::Edges => "created_at",
+            RecordKind::Events => "timestamp",
+        }
+    }
+}
+
+/// Result of a merge.
+#[derive(Debug)]
+pub struct MergeResult {
+    /// Detected record kind (None if all inputs were empty).
+    pub kind: Option<RecordKind>,
+    /// Number of records written.
+    pub records: usize,
+}
+
+/// A parsed line from one side of the merge.
+struct Record {
+    /// Original line text, written back unchanged.
+    line: String,
+    /// Canonical serialization used to compare records across sides.
+    canonical: String,
+    value: Value,
+}
+
+/// Merge three JSONL files as a git merge driver, writing the result to `ours`.
+pub fn merge_files(base: &Path, ours: &Path, theirs: &Path) -> Result<MergeResult> {
+    let read = |path: &Path| -> Result<String> {
+        if path.exists() {
+            fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+        } else {
+            Ok(String::new())
+        }
+    };
+
+    let (merged, result) = merge_jsonl(&read(base)?, &read(ours)?, &read(theirs)?)?;
+    fs::write(ours, merged).with_context(|| format!("Failed to write {}", ours.display()))?;
+
+    Ok(result)
+}
+
+/// Merge the contents of three versions of a JSONL file.
+///
+/// Records present in `base` but removed on either side are dropped; all other
+/// records from `ours` and `theirs` are kept and ordered for replay. Fails if a
+/// line is not valid JSON or the files mix record kinds.
+pub fn merge_jsonl(base: &str, ours: &str, theirs: &str) -> Result<(String, MergeResult)> {
+    let base = parse_records(base, "base")?;
+    let ours = parse_records(ours, "ours")?;
+    let theirs = parse_records(theirs, "theirs")?;
+
+    let mut kind = None;
+    for record in base.iter().chain(&ours).chain(&theirs) {
+        match (kind, RecordKind::detect(&record.value)) {
+            (_, None) => bail!("Unrecognized record: {}", record.line),
+            (None, detected) => kind = detected,
+            (Some(k), Some(detected)) if k != detected => {
+                bail!("Mixed record kinds: {:?} and {:?}", k, detected)
+            }
+            _ => {}
+        }
+    }
+
+    let Some(kind) = kind else {
+        return Ok((String::new(), MergeResult { kind: None, records: 0 }));
+    };
+
+    let in_base: HashSet<&str> = base.iter().map(|r| r.canonical.as_str()).collect();
+    let in_ours: HashSet<&str> = ours.iter().map(|r| r.canonical.as_str()).collect();
+    let in_theirs: HashSet<&str> = theirs.iter().map(|r| r.canonical.as_str()).collect();
+
+    // Keep records either side has, unless one side removed them since base
+    let keep = |canonical: &str| {
+        !(in_base.contains(canonical) && (!in_ours.contains(canonical) || !in_theirs.contains(canonical)))
+    };
+
+    // Rank ours above theirs so that on equal timestamps our record is replayed last
+    let mut seen = HashSet::new();
+    let mut merged: Vec<(Option<DateTime<Utc>>, u8, &Record)> = Vec::new();
+    for (rank, side) in [(1, &ours), (0, &theirs)] {
+        for record in side.iter() {
+            if keep(&record.canonical) && seen.insert(record.canonical.as_str()) {
+                merged.push((timestamp(&record.value, kind), rank, record));
+            }
+        }
+    }
+
+    merged.sort_by_key(|(ts, rank, _)| (*ts, *rank));
+
+    // Events are immutable: keep only the winning record per id
+    if kind == RecordKind::Events {
+        let mut winners: HashMap<&str, usize> = HashMap::new();
+        for (i, (_, _, record)) in merged.iter().enumerate() {
+            if let Some(id) = record.value.get("id").and_then(Value::as_str) {
+                winners.insert(id, i);
+            }
+        }
+        let keep: HashSet<usize> = winners.into_values().collect();
+        merged = merged
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| keep.contains(i))
+            .map(|(_, entry)| entry)
+            .collect();
+    }
+
+    let mut output = String::new();
+    for (_, _, record) in &merged {
+        output.push_str(&record.line);
+        output.push('\n');
+    }
+
+    Ok((
+        output,
+        MergeResult {
+            kind: Some(kind),
+            records: merged.len(),
+        },
+    ))
+}
+
+/// Parse non-empty lines into records.
+fn parse_records(content: &str, side: &str) -> Result<Vec<Record>> {
+    let mut records = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value =
+            serde_json::from_str(line).with_context(|| format!("Invalid JSON in {} at line {}", side, i + 1))?;
+        records.push(Record {
+            line: line.to_string(),
+            canonical: value.to_string(),
+            value,
+        });
+    }
+    Ok(records)
+}
+
+/// Extract the ordering timestamp of a record.
+fn timestamp(value: &Value, kind: RecordKind) -> Option<DateTime<Utc>> {
+    value
+        .get(kind.timestamp_field())
+        .and_then(Value::as_str)
+        .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Edge, EdgeKind, Event, Item, Status};
+    use chrono::Duration;
+
+    fn item_line(id: &str, title: &str, updated_at: DateTime<Utc>) -> String {
+        let item = Item {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: None,
+            status: Status::Open,
+            priority: 2,
+            labels: vec![],
+            created_at: updated_at - Duration::hours(1),
+            updated_at,
+            closed_at: None,
+            close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
+        };
+        serde_json::to_string(&item).unwrap()
+    }
+
+    fn edge_line(deleted: bool, created_at: DateTime<Utc>) -> String {
+        let edge = Edge {
+            from_id: "eg-a".to_string(),
+            to_id: "eg-b".to_string(),
+            kind: EdgeKind::Blocks,
+            created_at,
+            deleted,
+        };
+        serde_json::to_string(&edge).unwrap()
+    }
+
+    fn event_line(id: &str, timestamp: DateTime<Utc>) -> String {
+        let event = Event {
+            id: id.to_string(),
+            kind: "task_started".to_string(),
+            source_task: None,
+            target_task: None,
+            payload: Value::Null,
+            timestamp,
+        };
+        serde_json::to_string(&event).unwrap()
+    }
+
+    fn lines(records: &[&String]) -> String {
+        records.iter().map(|r| format!("{}\n", r)).collect()
+    }
+
+    #[test]
+    fn test_merge_items_latest_update_wins() {
+        let t0 = Utc::now();
+        let created = item_line("eg-1", "Original", t0);
+        let ours_edit = item_line("eg-1", "Ours", t0 + Duration::seconds(20));
+        let theirs_edit = item_line("eg-1", "Theirs", t0 + Duration::seconds(10));
+        let theirs_new = item_line("eg-2", "New on theirs", t0 + Duration::seconds(5));
+
+        let base = lines(&[&created]);
+        let ours = lines(&[&created, &ours_edit]);
+        let theirs = lines(&[&created, &theirs_new, &theirs_edit]);
+
+        let (merged, result) = merge_jsonl(&base, &ours, &theirs).unwrap();
+        assert_eq!(result.kind, Some(RecordKind::Items));
+        assert_eq!(result.records, 4);
+
+        let merged: Vec<&str> = merged.lines().collect();
+        assert_eq!(merged.last(), Some(&ours_edit.as_str()));
+        assert!(merged.contains(&theirs_new.as_str()));
+    }
+
+    #[test]
+    fn test_merge_edges_tombstone_wins_when_newer() {
+        let t0 = Utc::now();
+        let added = edge_line(false, t0);
+        let removed = edge_line(true, t0 + Duration::seconds(1));
+
+        let base = lines(&[&added]);
+        let ours = lines(&[&added]);
+        let theirs = lines(&[&added, &removed]);
+
+        let (merged, _) = merge_jsonl(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged.lines().last(), Some(removed.as_str()));
+    }
+
+    #[test]
+    fn test_merge_events_union_by_id() {
+        let t0 = Utc::now();
+        let shared = event_line("eg-evt-1", t0);
+        let ours_only = event_line("eg-evt-2", t0 + Duration::seconds(1));
+        let theirs_only = event_line("eg-evt-3", t0 + Duration::seconds(2));
+
+        let base = lines(&[&shared]);
+        let ours = lines(&[&shared, &ours_only]);
+        let theirs = lines(&[&shared, &theirs_only]);
+
+        let (merged, result) = merge_jsonl(&base, &ours, &theirs).unwrap();
+        assert_eq!(result.kind, Some(RecordKind::Events));
+        assert_eq!(merged, lines(&[&shared, &ours_only, &theirs_only]));
+    }
+
+    #[test]
+    fn test_merge_drops_records_removed_on_one_side() {
+        let t0 = Utc::now();
+        let kept = item_line("eg-1", "Kept", t0);
+        let removed = item_line("eg-2", "Removed", t0);
+
+        let base = lines(&[&kept, &removed]);
+        let ours = lines(&[&kept]);
+        let theirs = lines(&[&kept, &removed]);
+
+        let (merged, _) = merge_jsonl(&base, &ours, &theirs).unwrap();
+        assert_eq!(merged, lines(&[&kept]));
+    }
+
+    #[test]
+    fn test_merge_rejects_invalid_json() {
+        let result = merge_jsonl("", "{not json}\n", "");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_merge_files_writes_ours() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let t0 = Utc::now();
+        let a = item_line("eg-1", "A", t0);
+        let b = item_line("eg-2", "B", t0 + Duration::seconds(1));
+
+        let base = temp_dir.path().join("base");
+        let ours = temp_dir.path().join("ours");
+        let theirs = temp_dir.path().join("theirs");
+        fs::write(&base, "").unwrap();
+        fs::write(&ours, lines(&[&a])).unwrap();
+        fs::write(&theirs, lines(&[&b])).unwrap();
+
+        let result = merge_files(&base, &ours, &theirs).unwrap();
+        assert_eq!(result.records, 2);
+        assert_eq!(fs::read_to_string(&ours).unwrap(), lines(&[&a, &b]));
+    }
+}
@@ -0,0 +1,267 @@
+//! Scheduling the work that stands between an item and completion.
+//!
+//! The plan for a root item covers the root and everything that holds it up,
+//! transitively: unfinished blockers and unfinished children, exactly as
+//! `explain_blocked` reports them. Finished work is left out.
+
+use crate::store::{Store, StoreError};
+use crate::types::{Item, Status};
+use eyre::{Result, bail};
+use std::collections::{HashMap, HashSet};
+
+/// The longest chain of unfinished work ending at the root.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CriticalPath {
+    /// Items in execution order, ending with the root.
+    pub items: Vec<Item>,
+    /// Sum of the estimates along the path.
+    pub total: f64,
+}
+
+/// Extension trait for planning on Store.
+pub trait StorePlanExt {
+    /// Unfinished work for `root` grouped into waves.
+    ///
+    /// Every item in a wave depends only on items in earlier waves, so a wave
+    /// can run in parallel once the previous one is done. Items within a wave
+    /// are in priority order; the root is alone in the last wave.
+    fn waves(&self, root: &str) -> Result<Vec<Vec<Item>>>;
+
+    /// Unfinished work for `root` in a valid execution order.
+    fn topological_order(&self, root: &str) -> Result<Vec<Item>> {
+        Ok(self.waves(root)?.into_iter().flatten().collect())
+    }
+
+    /// The longest chain of unfinished work ending at `root`, counting each
+    /// item as one unit.
+    fn critical_path(&self, root: &str) -> Result<CriticalPath> {
+        self.critical_path_by(root, |_| 1.0)
+    }
+
+    /// The chain of unfinished work ending at `root` with the largest total
+    /// `estimate`.
+    fn critical_path_by(&self, root: &str, estimate: impl Fn(&Item) -> f64) -> Result<CriticalPath>;
+}
+
+/// The unfinished subgraph under a root.
+struct WorkGraph {
+    items: HashMap<String, Item>,
+    /// Item ID to the IDs of the items holding it up.
+    holders: HashMap<String, Vec<String>>,
+}
+
+impl WorkGraph {
+    /// Collect `root` and everything that transitively holds it up.
+    fn load(store: &Store, root: &str) -> Result<Self> {
+        let item = store
+            .get(root)?
+            .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(root.to_string())))?;
+
+        let mut graph = Self {
+            items: HashMap::new(),
+            holders: HashMap::new(),
+        };
+        if item.status == Status::Closed {
+            return Ok(graph);
+        }
+
+        let mut stack = vec![item];
+        while let Some(item) = stack.pop() {
+            if graph.items.contains_key(&item.id) {
+                continue;
+            }
+
+            let holders = store.storage().holders(&item.id)?;
+            graph
+                .holders
+                .insert(item.id.clone(), holders.iter().map(|(_, h)| h.id.clone()).collect());
+            graph.items.insert(item.id.clone(), item);
+            stack.extend(holders.into_iter().map(|(_, h)| h));
+        }
+
+        Ok(graph)
+    }
+
+    /// Group the items into waves with Kahn's algorithm.
+    fn waves(&self) -> Result<Vec<Vec<Item>>> {
+        let mut done: HashSet<&str> = HashSet::new();
+        let mut waves = Vec::new();
+
+        while done.len() < self.items.len() {
+            let mut wave: Vec<Item> = self
+                .items
+                .values()
+                .filter(|item| !done.contains(item.id.as_str()))
+                .filter(|item| self.holders[&item.id].iter().all(|h| done.contains(h.as_str())))
+                .cloned()
+                .collect();
+
+            // `add_edge` rejects cycles, but edges merged from another clone
+            // are not checked, so don't spin on one
+            if wave.is_empty() {
+                bail!(
+                    "blocking graph contains a cycle: {}",
+                    self.find_cycle(&done).join(" -> ")
+                );
+            }
+
+            wave.sort_by_key(|item| (item.priority, item.created_at));
+            done.extend(wave.iter().map(|item| self.items[&item.id].id.as_str()));
+            waves.push(wave);
+        }
+
+        Ok(waves)
+    }
+
+    /// A cycle among the items not yet `done`, as IDs each waiting on the
+    /// next, with the first repeated at the end.
+    ///
+    /// Only called when no remaining item is free, so following any remaining
+    /// holder from any remaining item must eventually loop.
+    fn find_cycle(&self, done: &HashSet<&str>) -> Vec<&str> {
+        let mut path: Vec<&str> = Vec::new();
+        let mut next = self.items.keys().map(String::as_str).find(|id| !done.contains(id));
+
+        while let Some(id) = next {
+            if let Some(start) = path.iter().position(|seen| *seen == id) {
+                let mut cycle = path.split_off(start);
+                cycle.push(id);
+                return cycle;
+            }
+            path.push(id);
+            next = self.holders[id].iter().map(String::as_str).find(|h| !done.contains(h));
+        }
+
+        path
+    }
+}
+
+impl StorePlanExt for Store {
+    fn waves(&self, root: &str) -> Result<Vec<Vec<Item>>> {
+        WorkGraph::load(self, root)?.waves()
+    }
+
+    fn critical_path_by(&self, root: &str, estimate: impl Fn(&Item) -> f64) -> Result<CriticalPath> {
+        let graph = WorkGraph::load(self, root)?;
+
+        // Longest chain ending at each item, filled in execution order
+        let mut best: HashMap<String, (f64, Option<String>)> = HashMap::new();
+        for item in graph.waves()?.into_iter().flatten() {
+            let previous = graph.holders[&item.id].iter().map(|h| (best[h].0, h)).fold(
+                None,
+                |acc: Option<(f64, &String)>, (total, id)| match acc {
+                    Some((best_total, _)) if best_total >= total => acc,
+                    _ => Some((total, id)),
+                },
+            );
+
+            let total = estimate(&item) + previous.map_or(0.0, |(total, _)| total);
+            best.insert(item.id.clone(), (total, previous.map(|(_, id)| id.clone())));
+        }
+
+        let Some(&(total, _)) = best.get(root) else {
+            return Ok(CriticalPath {
+                items: Vec::new(),
+                total: 0.0,
+            });
+        };
+
+        let mut items = Vec::new();
+        let mut cursor = Some(root.to_string());
+        while let Some(id) = cursor {
+            cursor = best[&id].1.clone();
+            items.push(graph.items[&id].clone());
+        }
+        items.reverse();
+
+        Ok(CriticalPath { items, total })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EdgeKind;
+    use tempfile::TempDir;
+
+    fn setup_test_store() -> (TempDir, Store) {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Store::init(temp_dir.path()).unwrap();
+        (temp_dir, store)
+    }
+
+    fn titles(items: &[Item]) -> Vec<&str> {
+        items.iter().map(|i| i.title.as_str()).collect()
+    }
+
+    #[test]
+    fn test_waves_and_critical_path() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let release = store.create("Release", 1, &[], None).unwrap();
+        let backend = store.create("Backend", 2, &[], None).unwrap();
+        let frontend = store.create("Frontend", 2, &[], None).unwrap();
+        let schema = store.create("Schema", 1, &[], None).unwrap();
+        let docs = store.create("Docs", 3, &[], None).unwrap();
+        let done = store.create("Done", 1, &[], None).unwrap();
+
+        store.add_edge(&release.id, &backend.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&release.id, &frontend.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&backend.id, &schema.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&docs.id, &release.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&frontend.id, &done.id, EdgeKind::Blocks).unwrap();
+        store.close(&done.id, None).unwrap();
+
+        let waves = store.waves(&release.id).unwrap();
+        let waves: Vec<Vec<&str>> = waves.iter().map(|w| titles(w)).collect();
+        assert_eq!(
+            waves,
+            vec![vec!["Schema", "Frontend", "Docs"], vec!["Backend"], vec!["Release"]]
+        );
+        assert_eq!(store.topological_order(&release.id).unwrap().len(), 5);
+
+        let path = store.critical_path(&release.id).unwrap();
+        assert_eq!(titles(&path.items), vec!["Schema", "Backend", "Release"]);
+        assert_eq!(path.total, 3.0);
+
+        // A long frontend task outweighs the two-step backend chain
+        let weighted = store
+            .critical_path_by(&release.id, |item| if item.id == frontend.id { 5.0 } else { 1.0 })
+            .unwrap();
+        assert_eq!(titles(&weighted.items), vec!["Frontend", "Release"]);
+        assert_eq!(weighted.total, 6.0);
+    }
+
+    #[test]
+    fn test_waves_name_a_merged_cycle() {
+        let (temp_dir, mut store) = setup_test_store();
+        let a = store.create("A", 2, &[], None).unwrap();
+        let b = store.create("B", 2, &[], None).unwrap();
+        store.add_edge(&a.id, &b.id, EdgeKind::Blocks).unwrap();
+
+        // The other half of the cycle arrives through a merge, unchecked
+        let mut edge = store.storage().get_blocking_edges_from(&a.id).unwrap().remove(0);
+        std::mem::swap(&mut edge.from_id, &mut edge.to_id);
+        let edges_path = temp_dir.path().join(".engram").join("edges.jsonl");
+        let mut edges = std::fs::read_to_string(&edges_path).unwrap();
+        edges.push_str(&format!("{}\n", serde_json::to_string(&edge).unwrap()));
+        std::fs::write(&edges_path, edges).unwrap();
+
+        let err = Store::open(temp_dir.path())
+            .unwrap()
+            .waves(&a.id)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(&a.id) && err.contains(&b.id), "{}", err);
+    }
+
+    #[test]
+    fn test_closed_root_has_no_plan() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let item = store.create("Finished", 2, &[], None).unwrap();
+        store.close(&item.id, None).unwrap();
+
+        assert!(store.waves(&item.id).unwrap().is_empty());
+        assert!(store.critical_path(&item.id).unwrap().items.is_empty());
+        assert!(store.waves("eg-missing").is_err());
+    }
+}
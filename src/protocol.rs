@@ -1,12 +1,64 @@
 //! IPC protocol types for daemon communication.
+//!
+//! Each line on the socket is one JSON [`Envelope`]: a `Request` or
+//! `Response` plus an optional `request_id`. The daemon echoes the ID on
+//! each response, so several requests can be pipelined on one connection.
+//! Clients open with `Hello` to agree on a protocol version.
 
-use crate::types::{Edge, EdgeKind, Item, Status};
+use crate::batch::{BatchCloseResult, BatchCreateResult, CreateSpec};
+use crate::compact::{CompactConfig, CompactResult};
+use crate::eventquery::{EventCounts, TimelineEntry};
+use crate::graph::{Blocker, Direction, Related};
+use crate::store::StoreError;
+use crate::transaction::{TxOp, TxResult};
+use crate::types::{
+    Change, ChangeFilter, Edge, EdgeKind, Event, EventFilter, Filter, Item, Page, SearchHit, Status, ValidationError,
+};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Protocol version spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest protocol version this build still accepts.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Optional features the daemon offers at `PROTOCOL_VERSION`.
+pub const CAPABILITIES: &[&str] = &[
+    "pipelining",
+    "typed_errors",
+    "subscribe",
+    "claims",
+    "events",
+    "batch",
+    "compact",
+    "traverse",
+    "search",
+    "query_language",
+    "pages",
+];
+
+/// A request or response on the wire, with the ID that pairs them.
+///
+/// The ID is named `request_id` so it cannot clash with item `id` fields.
+/// Lines without one are accepted, and answered without one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope<T> {
+    /// Caller-chosen request ID, echoed on the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<u64>,
+    /// The request or response itself.
+    #[serde(flatten)]
+    pub body: T,
+}
+
 /// Request sent from client to daemon.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Request {
+    /// Open a session, agreeing on a protocol version.
+    Hello { protocol_version: u32, client_name: String },
+
     /// Create a new item.
     Create {
         title: String,
@@ -16,19 +68,37 @@ pub enum Request {
     },
 
     /// Update an existing item.
+    ///
+    /// With `expected_version` set, fails with a `conflict` error unless the
+    /// item's `updated_at` still matches it (likewise for `SetStatus` and `Close`).
     Update {
         id: String,
         title: Option<String>,
         description: Option<Option<String>>,
         priority: Option<u8>,
         labels: Option<Vec<String>>,
+        #[serde(default)]
+        expected_version: Option<DateTime<Utc>>,
     },
 
     /// Set item status.
-    SetStatus { id: String, status: Status },
+    SetStatus {
+        id: String,
+        status: Status,
+        #[serde(default)]
+        expected_version: Option<DateTime<Utc>>,
+    },
 
     /// Close an item.
-    Close { id: String, reason: Option<String> },
+    Close {
+        id: String,
+        reason: Option<String>,
+        #[serde(default)]
+        expected_version: Option<DateTime<Utc>>,
+    },
+
+    /// Close an item and its open descendants.
+    CloseTree { id: String, reason: Option<String> },
 
     /// Add an edge between items.
     AddEdge {
@@ -44,6 +114,26 @@ pub enum Request {
         kind: EdgeKind,
     },
 
+    /// Claim an item for an agent with a lease of `ttl_secs`.
+    Claim { id: String, agent: String, ttl_secs: u64 },
+
+    /// Renew an agent's lease on an item.
+    Heartbeat { id: String, agent: String, ttl_secs: u64 },
+
+    /// Claim the top ready item matching the filters.
+    ///
+    /// `labels` matches items with any of the labels; `ttl_secs` defaults to
+    /// `DEFAULT_LEASE_TTL_SECS`.
+    ClaimNext {
+        agent: String,
+        #[serde(default)]
+        labels: Vec<String>,
+        #[serde(default)]
+        max_priority: Option<u8>,
+        #[serde(default)]
+        ttl_secs: Option<u64>,
+    },
+
     /// Get an item by ID.
     Get { id: String },
 
@@ -56,9 +146,127 @@ pub enum Request {
     /// Get blocked items (have open blockers).
     Blocked,
 
+    /// Return items with expired leases to Open.
+    ExpireLeases,
+
+    /// Query items matching a filter and, if given, a query string (see
+    /// `query::parse_query`).
+    Query {
+        #[serde(default)]
+        filter: Filter,
+        #[serde(default)]
+        q: Option<String>,
+    },
+
+    /// One page of items matching a filter, after a cursor from a previous
+    /// page (see `Query::execute_page`).
+    QueryPage {
+        #[serde(default)]
+        filter: Filter,
+        #[serde(default)]
+        cursor: Option<String>,
+    },
+
+    /// Count items matching a filter (ignores limit and offset).
+    Count { filter: Filter },
+
+    /// Full-text search titles and descriptions, narrowed by a filter.
+    Search {
+        text: String,
+        #[serde(default)]
+        filter: Filter,
+    },
+
+    /// Record a new event.
+    RecordEvent {
+        kind: String,
+        source_task: Option<String>,
+        target_task: Option<String>,
+        #[serde(default)]
+        payload: serde_json::Value,
+    },
+
+    /// Record an existing event as-is.
+    RecordEventRaw { event: Event },
+
+    /// Get an event by ID.
+    GetEvent { id: String },
+
+    /// Query events matching a filter.
+    QueryEvents { filter: EventFilter },
+
+    /// One page of events matching a filter, after a cursor from a previous
+    /// page (see `EventQuery::execute_page`).
+    QueryEventsPage {
+        #[serde(default)]
+        filter: EventFilter,
+        #[serde(default)]
+        cursor: Option<String>,
+    },
+
+    /// Get the most recent events.
+    RecentEvents { limit: usize },
+
+    /// Get events for a task (as source or target).
+    TaskEvents { task_id: String, limit: usize },
+
+    /// Count events by kind.
+    EventCounts,
+
+    /// Count events by kind for a task.
+    TaskEventCounts { task_id: String },
+
+    /// Get events grouped into hourly buckets.
+    EventTimeline { since: DateTime<Utc>, limit: usize },
+
+    /// List recorded event kinds.
+    EventKinds,
+
+    /// Create several items.
+    BatchCreate { specs: Vec<CreateSpec> },
+
+    /// Close several items.
+    BatchClose { ids: Vec<String>, reason: Option<String> },
+
+    /// Set the status of several items.
+    BatchSetStatus { ids: Vec<String>, status: Status },
+
+    /// Compact old closed items.
+    Compact { config: CompactConfig },
+
+    /// List items eligible for compaction.
+    CompactableItems { older_than_days: u32 },
+
+    /// Walk the graph from an item (see `StoreGraphExt::traverse`).
+    Traverse {
+        id: String,
+        direction: Direction,
+        #[serde(default)]
+        kinds: Vec<EdgeKind>,
+        #[serde(default)]
+        depth: Option<usize>,
+    },
+
+    /// Explain why an item is not ready.
+    ExplainBlocked { id: String },
+
+    /// Run several operations as one transaction.
+    ///
+    /// IDs of the form `"$N"` refer to the item created by op `N`.
+    Transaction { ops: Vec<TxOp> },
+
     /// Force flush pending writes to disk.
     Flush,
 
+    /// Turn this connection into a stream of `Change` responses.
+    ///
+    /// The daemon answers `Subscribed`, then sends a `Change` for every
+    /// matching write until the client disconnects.
+    Subscribe {
+        #[serde(default)]
+        filter: ChangeFilter,
+    },
+
     /// Shutdown the daemon.
     Shutdown,
 
@@ -70,6 +278,16 @@ pub enum Request {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum Response {
+    /// Handshake accepted.
+    ///
+    /// `protocol_version` is the version both sides will speak; `capabilities`
+    /// lists the optional features available at that version.
+    Welcome {
+        protocol_version: u32,
+        daemon_version: String,
+        capabilities: Vec<String>,
+    },
+
     /// Single item response.
     Item { item: Item },
 
@@ -79,6 +297,51 @@ pub enum Response {
     /// Single edge response.
     Edge { edge: Edge },
 
+    /// Count response.
+    Count { count: usize },
+
+    /// One page of items.
+    ItemPage { page: Page<Item> },
+
+    /// One page of events.
+    EventPage { page: Page<Event> },
+
+    /// Full-text search results, best match first.
+    SearchHits { hits: Vec<SearchHit> },
+
+    /// Single event response.
+    Event { event: Event },
+
+    /// Multiple events response.
+    Events { events: Vec<Event> },
+
+    /// Event counts by kind.
+    EventCounts { counts: EventCounts },
+
+    /// Event timeline buckets.
+    Timeline { entries: Vec<TimelineEntry> },
+
+    /// Recorded event kinds.
+    EventKinds { kinds: Vec<String> },
+
+    /// Result of a batch create.
+    BatchCreated { result: BatchCreateResult },
+
+    /// Result of a batch close.
+    BatchClosed { result: BatchCloseResult },
+
+    /// Result of a compaction.
+    Compacted { result: CompactResult },
+
+    /// Items reached by a traversal, nearest first.
+    Related { related: Vec<Related> },
+
+    /// What holds up an item, as a tree.
+    Blockers { blockers: Vec<Blocker> },
+
+    /// Results of a committed transaction, one per op.
+    Transaction { results: Vec<TxResult> },
+
     /// Item not found.
     NotFound { id: String },
 
@@ -88,15 +351,116 @@ pub enum Response {
     /// Pong response to ping.
     Pong,
 
+    /// Subscription accepted; `Change` responses follow.
+    Subscribed,
+
+    /// A change on a subscribed connection.
+    Change { change: Change },
+
     /// Error response.
-    Error { message: String },
+    ///
+    /// `kind` is set when the error is a `StoreError`, so clients can rebuild it.
+    Error {
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<ErrorKind>,
+    },
 }
 
 impl Response {
+    /// Answer a `Hello` from a client speaking `client_version`.
+    pub fn welcome(client_version: u32) -> Self {
+        if client_version < MIN_PROTOCOL_VERSION {
+            return Self::error(format!(
+                "unsupported protocol version {} (daemon accepts {} to {})",
+                client_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            ));
+        }
+
+        Self::Welcome {
+            protocol_version: client_version.min(PROTOCOL_VERSION),
+            daemon_version: env!("CARGO_PKG_VERSION").to_string(),
+            capabilities: CAPABILITIES.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
     /// Create an error response.
     pub fn error(message: impl Into<String>) -> Self {
         Self::Error {
             message: message.into(),
+            kind: None,
+        }
+    }
+
+    /// Create an error response from a failed operation, keeping its `StoreError` if any.
+    pub fn from_report(report: &eyre::Report) -> Self {
+        Self::Error {
+            message: report.to_string(),
+            kind: report.downcast_ref::<StoreError>().map(ErrorKind::from),
+        }
+    }
+}
+
+/// Machine-readable cause of an error response, mirroring `StoreError`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Item not found.
+    ItemNotFound { id: String },
+    /// Self-referential edge.
+    SelfReferentialEdge,
+    /// Adding this edge would create a cycle.
+    CycleDetected,
+    /// Invalid status transition.
+    InvalidStatusTransition { from: Status, to: Status },
+    /// Validation error.
+    Validation { error: ValidationError },
+    /// Item is in progress under another agent's unexpired claim.
+    AlreadyClaimed { id: String, assignee: Option<String> },
+    /// Agent does not hold the claim it tried to renew.
+    LeaseNotHeld { id: String, agent: String },
+    /// Item changed since the version the caller expected.
+    Conflict { current: Item },
+}
+
+impl From<&StoreError> for ErrorKind {
+    fn from(error: &StoreError) -> Self {
+        match error {
+            StoreError::ItemNotFound(id) => ErrorKind::ItemNotFound { id: id.clone() },
+            StoreError::SelfReferentialEdge => ErrorKind::SelfReferentialEdge,
+            StoreError::CycleDetected => ErrorKind::CycleDetected,
+            StoreError::InvalidStatusTransition { from, to } => {
+                ErrorKind::InvalidStatusTransition { from: *from, to: *to }
+            }
+            StoreError::Validation(error) => ErrorKind::Validation { error: error.clone() },
+            StoreError::AlreadyClaimed { id, assignee } => ErrorKind::AlreadyClaimed {
+                id: id.clone(),
+                assignee: assignee.clone(),
+            },
+            StoreError::LeaseNotHeld { id, agent } => ErrorKind::LeaseNotHeld {
+                id: id.clone(),
+                agent: agent.clone(),
+            },
+            StoreError::Conflict { current } => ErrorKind::Conflict {
+                current: current.as_ref().clone(),
+            },
+        }
+    }
+}
+
+impl From<ErrorKind> for StoreError {
+    fn from(kind: ErrorKind) -> Self {
+        match kind {
+            ErrorKind::ItemNotFound { id } => StoreError::ItemNotFound(id),
+            ErrorKind::SelfReferentialEdge => StoreError::SelfReferentialEdge,
+            ErrorKind::CycleDetected => StoreError::CycleDetected,
+            ErrorKind::InvalidStatusTransition { from, to } => StoreError::InvalidStatusTransition { from, to },
+            ErrorKind::Validation { error } => StoreError::Validation(error),
+            ErrorKind::AlreadyClaimed { id, assignee } => StoreError::AlreadyClaimed { id, assignee },
+            ErrorKind::LeaseNotHeld { id, agent } => StoreError::LeaseNotHeld { id, agent },
+            ErrorKind::Conflict { current } => StoreError::Conflict {
+                current: Box::new(current),
+            },
         }
     }
 }
@@ -125,6 +489,87 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_filter_request_defaults() {
+        let parsed: Request = serde_json::from_str(r#"{"type":"Query","filter":{"labels":["api"]}}"#).unwrap();
+
+        if let Request::Query { filter, .. } = parsed {
+            assert_eq!(filter.labels, Some(vec!["api".to_string()]));
+            assert!(filter.status.is_none());
+        } else {
+            panic!("Wrong request type");
+        }
+    }
+
+    #[test]
+    fn test_error_kind_round_trip() {
+        let report = eyre::eyre!(StoreError::InvalidStatusTransition {
+            from: Status::Blocked,
+            to: Status::Blocked,
+        });
+        let json = serde_json::to_string(&Response::from_report(&report)).unwrap();
+        assert!(json.contains(r#""code":"invalid_status_transition""#));
+
+        let Response::Error { kind: Some(kind), .. } = serde_json::from_str(&json).unwrap() else {
+            panic!("Expected typed error");
+        };
+        assert!(matches!(
+            StoreError::from(kind),
+            StoreError::InvalidStatusTransition {
+                from: Status::Blocked,
+                to: Status::Blocked
+            }
+        ));
+
+        let validation = Response::from_report(&eyre::eyre!(StoreError::Validation(ValidationError::InvalidLabel(
+            "a b".to_string()
+        ))));
+        let json = serde_json::to_string(&validation).unwrap();
+        let Response::Error { kind: Some(kind), .. } = serde_json::from_str(&json).unwrap() else {
+            panic!("Expected typed error");
+        };
+        assert_eq!(
+            kind,
+            ErrorKind::Validation {
+                error: ValidationError::InvalidLabel("a b".to_string())
+            }
+        );
+
+        // Errors that are not store errors carry only a message
+        let plain = Response::from_report(&eyre::eyre!("disk full"));
+        assert!(!serde_json::to_string(&plain).unwrap().contains("code"));
+    }
+
+    #[test]
+    fn test_envelope_carries_id() {
+        let envelope = Envelope {
+            request_id: Some(7),
+            body: Request::Get { id: "eg-1".to_string() },
+        };
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert_eq!(json, r#"{"request_id":7,"type":"Get","id":"eg-1"}"#);
+
+        let parsed: Envelope<Request> = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.request_id, Some(7));
+        assert!(matches!(parsed.body, Request::Get { id } if id == "eg-1"));
+
+        // Lines from clients that predate request IDs still parse
+        let bare: Envelope<Request> = serde_json::from_str(r#"{"type":"Ping"}"#).unwrap();
+        assert_eq!(bare.request_id, None);
+    }
+
+    #[test]
+    fn test_welcome_negotiates_version() {
+        assert!(matches!(
+            Response::welcome(PROTOCOL_VERSION + 1),
+            Response::Welcome { protocol_version, .. } if protocol_version == PROTOCOL_VERSION
+        ));
+        assert!(matches!(
+            Response::welcome(MIN_PROTOCOL_VERSION - 1),
+            Response::Error { .. }
+        ));
+    }
+
     #[test]
     fn test_response_serialization() {
         let resp = Response::error("test error");
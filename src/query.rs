@@ -1,8 +1,26 @@
 //! Query API with flexible filtering.
+//!
+//! Besides the builder, filters can be written in a compact query language,
+//! as used by `eg list --where` and `Request::Query`:
+//!
+//! ```text
+//! status:open label:backend -label:wip priority<=1 updated>7d "login"
+//! ```
+//!
+//! Conditions next to each other must all hold; `OR` and `NOT` (or a leading
+//! `-`) combine them, with parentheses for grouping. `AND` may be written
+//! explicitly and binds tighter than `OR`. Fields are `status`, `label`,
+//! `assignee` and `title`, compared with `:`, and `priority`, `created`,
+//! `updated` and `closed`, which also take `<`, `<=`, `>` and `>=`. Times are
+//! dates (`2024-01-31`), RFC 3339 timestamps or ages (`30m`, `12h`, `7d`,
+//! `2w`) counted back from now, so `updated>7d` means updated within the last
+//! week. Any other word, or a quoted phrase, must appear in the title or
+//! description.
 
 use crate::storage::Storage;
 use crate::store::Store;
-use crate::types::{Filter, Item, Status};
+use crate::types::{Comparison, Filter, Item, Page, Predicate, SearchHit, SortColumn, SortDirection, Status};
+use chrono::{DateTime, Days, Duration, NaiveDate, Utc};
 use eyre::Result;
 
 /// Query builder for fluent queries.
@@ -26,6 +44,12 @@ impl<'a> Query<'a> {
         self
     }
 
+    /// Filter by statuses (any match).
+    pub fn statuses(mut self, statuses: impl IntoIterator<Item = Status>) -> Self {
+        self.filter = self.filter.statuses(statuses);
+        self
+    }
+
     /// Filter by label.
     pub fn label(mut self, label: impl Into<String>) -> Self {
         self.filter = self.filter.label(label);
@@ -38,6 +62,18 @@ impl<'a> Query<'a> {
         self
     }
 
+    /// Filter by labels (all must match).
+    pub fn all_labels(mut self, labels: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.filter = self.filter.all_labels(labels);
+        self
+    }
+
+    /// Exclude items with any of these labels.
+    pub fn without_labels(mut self, labels: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.filter = self.filter.without_labels(labels);
+        self
+    }
+
     /// Filter by minimum priority.
     pub fn min_priority(mut self, priority: u8) -> Self {
         self.filter = self.filter.min_priority(priority);
@@ -56,6 +92,60 @@ impl<'a> Query<'a> {
         self
     }
 
+    /// Filter by description substring.
+    pub fn description_contains(mut self, substring: impl Into<String>) -> Self {
+        self.filter = self.filter.description_contains(substring);
+        self
+    }
+
+    /// Filter by close reason substring.
+    pub fn close_reason_contains(mut self, substring: impl Into<String>) -> Self {
+        self.filter = self.filter.close_reason_contains(substring);
+        self
+    }
+
+    /// Created at or after `time`.
+    pub fn created_after(mut self, time: DateTime<Utc>) -> Self {
+        self.filter = self.filter.created_after(time);
+        self
+    }
+
+    /// Created before `time`.
+    pub fn created_before(mut self, time: DateTime<Utc>) -> Self {
+        self.filter = self.filter.created_before(time);
+        self
+    }
+
+    /// Updated at or after `time`.
+    pub fn updated_after(mut self, time: DateTime<Utc>) -> Self {
+        self.filter = self.filter.updated_after(time);
+        self
+    }
+
+    /// Updated before `time`.
+    pub fn updated_before(mut self, time: DateTime<Utc>) -> Self {
+        self.filter = self.filter.updated_before(time);
+        self
+    }
+
+    /// Closed at or after `time`.
+    pub fn closed_after(mut self, time: DateTime<Utc>) -> Self {
+        self.filter = self.filter.closed_after(time);
+        self
+    }
+
+    /// Closed before `time`.
+    pub fn closed_before(mut self, time: DateTime<Utc>) -> Self {
+        self.filter = self.filter.closed_before(time);
+        self
+    }
+
+    /// Sort by `column` after any sort keys already added.
+    pub fn order_by(mut self, column: SortColumn, direction: SortDirection) -> Self {
+        self.filter = self.filter.order_by(column, direction);
+        self
+    }
+
     /// Limit results.
     pub fn limit(mut self, limit: usize) -> Self {
         self.filter = self.filter.limit(limit);
@@ -68,15 +158,39 @@ impl<'a> Query<'a> {
         self
     }
 
+    /// Require `predicate` to hold.
+    pub fn matching(mut self, predicate: Predicate) -> Self {
+        self.filter = self.filter.matching(predicate);
+        self
+    }
+
     /// Execute the query and return matching items.
     pub fn execute(self) -> Result<Vec<Item>> {
         self.storage.query_items(&self.filter)
     }
 
+    /// Execute the query one page at a time.
+    ///
+    /// Returns up to `limit` items (`DEFAULT_PAGE_SIZE` if unset) after
+    /// `cursor`, which is None for the first page and the previous page's
+    /// `next` after that. `offset` is ignored. Unlike offsets, cursors don't
+    /// skip or repeat items when others are created between pages.
+    pub fn execute_page(self, cursor: Option<&str>) -> Result<Page<Item>> {
+        self.storage.query_items_page(&self.filter, cursor)
+    }
+
     /// Count matching items without fetching them.
     pub fn count(self) -> Result<usize> {
         self.storage.count_items(&self.filter)
     }
+
+    /// Full-text search titles and descriptions for `text`, best match first.
+    ///
+    /// Every word must appear, after stemming. The other filters, limit and
+    /// offset apply as usual.
+    pub fn search(self, text: &str) -> Result<Vec<SearchHit>> {
+        self.storage.search_items(text, &self.filter)
+    }
 }
 
 /// Extension trait to add query method to Store.
@@ -98,6 +212,379 @@ impl StoreQueryExt for Store {
     }
 }
 
+/// A query string could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryParseError {
+    /// What is wrong.
+    pub message: String,
+    /// Character offset of the offending input, from 0.
+    pub position: usize,
+}
+
+impl std::fmt::Display for QueryParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at column {}", self.message, self.position + 1)
+    }
+}
+
+impl std::error::Error for QueryParseError {}
+
+/// Parse a query string into a filter holding the equivalent predicate.
+pub fn parse_filter(input: &str) -> std::result::Result<Filter, QueryParseError> {
+    let mut filter = Filter::new();
+    filter.predicate = parse_query(input)?;
+    Ok(filter)
+}
+
+/// Parse a query string into a predicate, or None if it is blank.
+pub fn parse_query(input: &str) -> std::result::Result<Option<Predicate>, QueryParseError> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+
+    let mut parser = Parser {
+        tokens,
+        next: 0,
+        end: input.chars().count(),
+        now: Utc::now(),
+    };
+    let predicate = parser.or()?;
+    match parser.tokens.get(parser.next) {
+        Some((_, pos)) => Err(error("unexpected ')'", *pos)),
+        None => Ok(Some(predicate)),
+    }
+}
+
+fn error(message: impl Into<String>, position: usize) -> QueryParseError {
+    QueryParseError {
+        message: message.into(),
+        position,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    /// A bare word or quoted phrase.
+    Text(String),
+    /// `field op value`, with the positions of the operator and the value.
+    Field {
+        name: String,
+        cmp: Comparison,
+        op_pos: usize,
+        value: String,
+        value_pos: usize,
+    },
+}
+
+/// Split `input` into tokens, each with its character offset.
+fn tokenize(input: &str) -> std::result::Result<Vec<(Token, usize)>, QueryParseError> {
+    let chars: Vec<char> = input.chars().collect();
+    let is_op = |c: char| matches!(c, ':' | '=' | '<' | '>');
+    let ends_word = |c: char| c.is_whitespace() || matches!(c, '(' | ')' | '"');
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        match chars[i] {
+            c if c.is_whitespace() => i += 1,
+            '(' => {
+                tokens.push((Token::LParen, start));
+                i += 1;
+            }
+            ')' => {
+                tokens.push((Token::RParen, start));
+                i += 1;
+            }
+            '"' => {
+                let phrase = quoted(&chars, &mut i)?;
+                tokens.push((Token::Text(phrase), start));
+            }
+            '-' if chars.get(i + 1).is_some_and(|&c| !ends_word(c)) => {
+                tokens.push((Token::Not, start));
+                i += 1;
+            }
+            _ => {
+                while i < chars.len() && !ends_word(chars[i]) && !is_op(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                if i == chars.len() || !is_op(chars[i]) {
+                    tokens.push((
+                        match word.as_str() {
+                            "AND" => Token::And,
+                            "OR" => Token::Or,
+                            "NOT" => Token::Not,
+                            _ => Token::Text(word),
+                        },
+                        start,
+                    ));
+                    continue;
+                }
+
+                if word.is_empty() {
+                    return Err(error(format!("expected a field name before '{}'", chars[i]), i));
+                }
+                let op_pos = i;
+                let cmp = match (chars[i], chars.get(i + 1)) {
+                    ('<', Some('=')) => Comparison::Le,
+                    ('>', Some('=')) => Comparison::Ge,
+                    ('<', _) => Comparison::Lt,
+                    ('>', _) => Comparison::Gt,
+                    _ => Comparison::Eq,
+                };
+                i += if matches!(cmp, Comparison::Le | Comparison::Ge) {
+                    2
+                } else {
+                    1
+                };
+
+                // Values may contain ':' and '-', as in timestamps and labels
+                let value_pos = i;
+                let value = if chars.get(i) == Some(&'"') {
+                    quoted(&chars, &mut i)?
+                } else {
+                    while i < chars.len() && !ends_word(chars[i]) {
+                        i += 1;
+                    }
+                    chars[value_pos..i].iter().collect()
+                };
+                if value.is_empty() {
+                    let op: String = chars[op_pos..value_pos].iter().collect();
+                    return Err(error(format!("expected a value after '{}{}'", word, op), value_pos));
+                }
+
+                tokens.push((
+                    Token::Field {
+                        name: word,
+                        cmp,
+                        op_pos,
+                        value,
+                        value_pos,
+                    },
+                    start,
+                ));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Read a quoted phrase starting at `chars[*i]`, leaving `i` after the closing
+/// quote. `\"` and `\\` are escapes.
+fn quoted(chars: &[char], i: &mut usize) -> std::result::Result<String, QueryParseError> {
+    let start = *i;
+    let mut phrase = String::new();
+    *i += 1;
+    while *i < chars.len() {
+        match chars[*i] {
+            '"' => {
+                *i += 1;
+                return Ok(phrase);
+            }
+            '\\' if matches!(chars.get(*i + 1), Some('"' | '\\')) => {
+                phrase.push(chars[*i + 1]);
+                *i += 2;
+            }
+            c => {
+                phrase.push(c);
+                *i += 1;
+            }
+        }
+    }
+    Err(error("unterminated quote", start))
+}
+
+/// Recursive descent over the tokens; `OR` binds loosest, then `AND`, then `NOT`.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    /// Character length of the input, for errors at the end.
+    end: usize,
+    /// Reference time for ages.
+    now: DateTime<Utc>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.next).map_or(self.end, |(_, pos)| *pos)
+    }
+
+    fn or(&mut self) -> std::result::Result<Predicate, QueryParseError> {
+        let mut any = vec![self.and()?];
+        while self.peek() == Some(&Token::Or) {
+            self.next += 1;
+            any.push(self.and()?);
+        }
+        Ok(if any.len() == 1 {
+            any.remove(0)
+        } else {
+            Predicate::Or(any)
+        })
+    }
+
+    fn and(&mut self) -> std::result::Result<Predicate, QueryParseError> {
+        let mut all = vec![self.unary()?];
+        loop {
+            match self.peek() {
+                None | Some(Token::RParen) | Some(Token::Or) => break,
+                Some(Token::And) => self.next += 1,
+                Some(_) => {}
+            }
+            all.push(self.unary()?);
+        }
+        Ok(if all.len() == 1 {
+            all.remove(0)
+        } else {
+            Predicate::And(all)
+        })
+    }
+
+    fn unary(&mut self) -> std::result::Result<Predicate, QueryParseError> {
+        if self.peek() == Some(&Token::Not) {
+            self.next += 1;
+            return Ok(Predicate::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> std::result::Result<Predicate, QueryParseError> {
+        let position = self.position();
+        let Some((token, _)) = self.tokens.get(self.next).cloned() else {
+            return Err(error("expected a condition", position));
+        };
+        self.next += 1;
+
+        match token {
+            Token::LParen => {
+                let inner = self.or()?;
+                if self.peek() != Some(&Token::RParen) {
+                    return Err(error("expected ')' to close '('", self.position()));
+                }
+                self.next += 1;
+                Ok(inner)
+            }
+            Token::Text(text) => Ok(Predicate::Text(text)),
+            Token::Field {
+                name,
+                cmp,
+                op_pos,
+                value,
+                value_pos,
+            } => self.field(&name, position, cmp, op_pos, &value, value_pos),
+            Token::RParen => Err(error("unexpected ')'", position)),
+            Token::And => Err(error("expected a condition before 'AND'", position)),
+            Token::Or => Err(error("expected a condition before 'OR'", position)),
+            Token::Not => unreachable!("handled by unary"),
+        }
+    }
+
+    fn field(
+        &self,
+        name: &str,
+        name_pos: usize,
+        cmp: Comparison,
+        op_pos: usize,
+        value: &str,
+        value_pos: usize,
+    ) -> std::result::Result<Predicate, QueryParseError> {
+        let equality_only = || {
+            if cmp == Comparison::Eq {
+                Ok(())
+            } else {
+                Err(error(format!("'{}' can only be compared with ':'", name), op_pos))
+            }
+        };
+
+        match name {
+            "status" => {
+                equality_only()?;
+                let status = value.parse::<Status>().map_err(|message| error(message, value_pos))?;
+                Ok(Predicate::Status(status))
+            }
+            "label" => equality_only().map(|_| Predicate::Label(value.to_string())),
+            "assignee" => equality_only().map(|_| Predicate::Assignee(value.to_string())),
+            "title" => equality_only().map(|_| Predicate::Title(value.to_string())),
+            "priority" => match value.parse::<u8>() {
+                Ok(priority) if priority <= 4 => Ok(Predicate::Priority { cmp, value: priority }),
+                _ => Err(error(format!("priority must be 0-4, not '{}'", value), value_pos)),
+            },
+            "created" | "updated" | "closed" => {
+                let time = |cmp, at| match name {
+                    "created" => Predicate::Created { cmp, at },
+                    "updated" => Predicate::Updated { cmp, at },
+                    _ => Predicate::Closed { cmp, at },
+                };
+
+                if let Ok(day) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                    // A date covers the whole day (UTC)
+                    let start = day.and_hms_opt(0, 0, 0).unwrap().and_utc();
+                    let end = start
+                        .checked_add_days(Days::new(1))
+                        .ok_or_else(|| error(format!("date '{}' is out of range", value), value_pos))?;
+                    return Ok(match cmp {
+                        Comparison::Eq => Predicate::And(vec![time(Comparison::Ge, start), time(Comparison::Lt, end)]),
+                        Comparison::Lt | Comparison::Ge => time(cmp, start),
+                        Comparison::Le => time(Comparison::Lt, end),
+                        Comparison::Gt => time(Comparison::Ge, end),
+                    });
+                }
+
+                let at = match DateTime::parse_from_rfc3339(value) {
+                    Ok(at) => at.with_timezone(&Utc),
+                    Err(_) => {
+                        let age = parse_age(value).ok_or_else(|| {
+                            error(
+                                format!(
+                                    "expected a date (2024-01-31), a timestamp or an age (30m, 12h, 7d, 2w), not '{}'",
+                                    value
+                                ),
+                                value_pos,
+                            )
+                        })?;
+                        self.now
+                            .checked_sub_signed(age)
+                            .ok_or_else(|| error(format!("age '{}' is out of range", value), value_pos))?
+                    }
+                };
+                if cmp == Comparison::Eq {
+                    return Err(error(
+                        format!("compare '{}' with <, <=, > or >= unless the value is a date", name),
+                        op_pos,
+                    ));
+                }
+                Ok(time(cmp, at))
+            }
+            _ => Err(error(format!("unknown field '{}'", name), name_pos)),
+        }
+    }
+}
+
+/// Parse an age such as `30m`, `12h`, `7d` or `2w`.
+fn parse_age(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        'm' => Duration::try_minutes(amount),
+        'h' => Duration::try_hours(amount),
+        'd' => Duration::try_days(amount),
+        'w' => Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,4 +671,247 @@ mod tests {
         let offset = store.query().offset(3).execute().unwrap();
         assert_eq!(offset.len(), 2);
     }
+
+    #[test]
+    fn test_search_ranks_and_highlights() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        store
+            .create("Fix login timeout", 1, &["auth"], Some("Sessions expire too early"))
+            .unwrap();
+        store
+            .create("Write docs", 2, &[], Some("Explain how login timeouts are configured"))
+            .unwrap();
+        store.create("Unrelated", 2, &["auth"], None).unwrap();
+
+        // Stemming matches "timeouts" and "timeout"; the title match ranks higher
+        let hits = store.query().search("login timeout").unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].item.title, "Fix login timeout");
+        assert!(hits[0].rank <= hits[1].rank);
+        assert!(hits[1].snippet.contains("**login**"));
+
+        let auth = store.query().label("auth").search("sessions").unwrap();
+        assert_eq!(auth.len(), 1);
+        assert_eq!(auth[0].item.labels, vec!["auth"]);
+
+        // Query syntax in user text is matched literally
+        assert!(store.query().search("\"login OR docs").unwrap().is_empty());
+        assert!(store.query().search("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_follows_updates_and_rebuild() {
+        let (temp_dir, mut store) = setup_test_store();
+
+        let item = store.create("Old title", 2, &[], None).unwrap();
+        store
+            .update(&item.id, Some("Renamed widget"), None, None, None)
+            .unwrap();
+        assert!(store.query().search("old").unwrap().is_empty());
+        assert_eq!(store.query().search("widget").unwrap().len(), 1);
+
+        drop(store);
+        let mut storage = Storage::open(temp_dir.path()).unwrap();
+        storage.rebuild_from_jsonl().unwrap();
+        assert_eq!(Query::new(&storage).search("widget").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_query_precedence() {
+        let parsed = parse_query("status:open label:backend -label:wip OR priority<=1").unwrap();
+        assert_eq!(
+            parsed,
+            Some(Predicate::Or(vec![
+                Predicate::And(vec![
+                    Predicate::Status(Status::Open),
+                    Predicate::Label("backend".to_string()),
+                    Predicate::Not(Box::new(Predicate::Label("wip".to_string()))),
+                ]),
+                Predicate::Priority {
+                    cmp: Comparison::Le,
+                    value: 1,
+                },
+            ]))
+        );
+
+        let grouped = parse_query(r#"NOT (title:"a b" OR x-y) AND assignee:agent-1"#).unwrap();
+        assert_eq!(
+            grouped,
+            Some(Predicate::And(vec![
+                Predicate::Not(Box::new(Predicate::Or(vec![
+                    Predicate::Title("a b".to_string()),
+                    Predicate::Text("x-y".to_string()),
+                ]))),
+                Predicate::Assignee("agent-1".to_string()),
+            ]))
+        );
+        assert_eq!(parse_query("   ").unwrap(), None);
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_input() {
+        let cases = [
+            ("status:opne", 7, "unknown status"),
+            ("label:a foo:b", 8, "unknown field 'foo'"),
+            ("label>a", 5, "can only be compared with ':'"),
+            ("priority<=", 10, "expected a value after 'priority<='"),
+            ("priority:9", 9, "priority must be 0-4"),
+            ("(label:a", 8, "expected ')'"),
+            ("label:a )", 8, "unexpected ')'"),
+            ("OR label:a", 0, "before 'OR'"),
+            ("say \"hi", 4, "unterminated quote"),
+            ("updated>soon", 8, "expected a date"),
+            ("updated:7d", 7, "unless the value is a date"),
+            ("created>9999999999w", 8, "out of range"),
+            ("created<+262142-12-31", 8, "out of range"),
+        ];
+        for (input, position, message) in cases {
+            let err = parse_query(input).unwrap_err();
+            assert_eq!(err.position, position, "{}: {}", input, err);
+            assert!(err.message.contains(message), "{}: {}", input, err);
+        }
+    }
+
+    #[test]
+    fn test_query_language_matches_items() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        let login = store
+            .create("Fix login", 1, &["backend"], Some("Session timeout"))
+            .unwrap();
+        store.create("Draft API", 1, &["backend", "wip"], None).unwrap();
+        let docs = store.create("Docs", 3, &["docs"], None).unwrap();
+        store.close(&docs.id, None).unwrap();
+
+        let today = Utc::now().format("%Y-%m-%d").to_string();
+        let cases = [
+            ("label:backend -label:wip", vec!["Fix login"]),
+            (r#"priority<=1 "timeout""#, vec!["Fix login"]),
+            ("status:closed OR label:wip", vec!["Draft API", "Docs"]),
+            ("updated>1h closed>1h", vec!["Docs"]),
+            ("NOT closed>1h", vec!["Fix login", "Draft API"]),
+            (&format!("created:{}", today), vec!["Fix login", "Draft API", "Docs"]),
+            ("created<7d", vec![]),
+        ];
+        for (q, expected) in cases {
+            let filter = parse_filter(q).unwrap();
+            let items = store.query_with_filter(&filter).unwrap();
+            let titles: Vec<&str> = items.iter().map(|i| i.title.as_str()).collect();
+            assert_eq!(titles, expected, "{}", q);
+
+            // The in-memory evaluation agrees with the SQL
+            let predicate = filter.predicate.unwrap();
+            let all = store.list(None).unwrap();
+            assert_eq!(
+                all.iter().filter(|i| predicate.matches(i)).count(),
+                expected.len(),
+                "{}",
+                q
+            );
+        }
+
+        let combined = store
+            .query()
+            .status(Status::Open)
+            .matching(Predicate::Text("login".to_string()));
+        assert_eq!(combined.execute().unwrap()[0].id, login.id);
+    }
+
+    #[test]
+    fn test_rich_filters_and_ordering() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        let api = store
+            .create("API", 1, &["backend", "api"], Some("REST endpoints"))
+            .unwrap();
+        let wip = store.create("Draft", 2, &["backend", "wip"], None).unwrap();
+        let docs = store.create("Docs", 3, &["docs"], None).unwrap();
+        store.set_status(&wip.id, Status::InProgress).unwrap();
+        store.close(&docs.id, Some("Superseded by wiki")).unwrap();
+
+        let titles = |query: Query| -> Vec<String> { query.execute().unwrap().into_iter().map(|i| i.title).collect() };
+
+        assert_eq!(titles(store.query().all_labels(["backend", "api"])), vec!["API"]);
+        assert_eq!(titles(store.query().without_labels(["wip", "docs"])), vec!["API"]);
+        assert_eq!(
+            titles(store.query().statuses([Status::Open, Status::Closed])),
+            vec!["API", "Docs"]
+        );
+        assert_eq!(titles(store.query().description_contains("rest")), vec!["API"]);
+        assert_eq!(titles(store.query().close_reason_contains("WIKI")), vec!["Docs"]);
+
+        let now = Utc::now();
+        assert_eq!(store.query().created_after(api.created_at).count().unwrap(), 3);
+        assert_eq!(store.query().created_before(api.created_at).count().unwrap(), 0);
+        assert_eq!(store.query().closed_after(now - Duration::hours(1)).count().unwrap(), 1);
+        assert_eq!(
+            store.query().closed_before(now + Duration::hours(1)).count().unwrap(),
+            1
+        );
+        assert_eq!(
+            store.query().updated_before(now - Duration::hours(1)).count().unwrap(),
+            0
+        );
+
+        assert_eq!(
+            titles(store.query().order_by(SortColumn::Priority, SortDirection::Desc)),
+            vec!["Docs", "Draft", "API"]
+        );
+        assert_eq!(
+            titles(store.query().order_by(SortColumn::Status, SortDirection::Asc)),
+            vec!["API", "Draft", "Docs"]
+        );
+        assert_eq!(
+            titles(
+                store
+                    .query()
+                    .order_by(SortColumn::ClosedAt, SortDirection::Desc)
+                    .order_by(SortColumn::Title, SortDirection::Desc)
+            ),
+            vec!["Docs", "Draft", "API"]
+        );
+    }
+
+    #[test]
+    fn test_execute_page_is_stable_under_inserts() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        for i in 0..5 {
+            store.create(&format!("Task {}", i), 2, &[], None).unwrap();
+        }
+        fn by_title(store: &Store) -> Query<'_> {
+            store.query().order_by(SortColumn::Title, SortDirection::Desc).limit(2)
+        }
+
+        let first = by_title(&store).execute_page(None).unwrap();
+        let token = first.next.clone().unwrap();
+        assert_eq!(first.results[0].title, "Task 4");
+
+        // One new item sorts before the cursor and one after it
+        store.create("Task 9", 2, &[], None).unwrap();
+        store.create("Task 0a", 2, &[], None).unwrap();
+
+        let mut titles: Vec<String> = first.results.into_iter().map(|i| i.title).collect();
+        let mut cursor = Some(token.clone());
+        while let Some(token) = cursor {
+            let page = by_title(&store).execute_page(Some(&token)).unwrap();
+            titles.extend(page.results.into_iter().map(|i| i.title));
+            cursor = page.next;
+        }
+        assert_eq!(
+            titles,
+            vec!["Task 4", "Task 3", "Task 2", "Task 1", "Task 0a", "Task 0"]
+        );
+
+        // A cursor only fits the order it came from
+        assert!(store.query().limit(2).execute_page(Some(&token)).is_err());
+        assert!(by_title(&store).execute_page(Some("not-a-cursor")).is_err());
+        assert!(store.query().execute_page(None).unwrap().next.is_none());
+
+        // Oversized limits are capped rather than overflowing
+        let page = store.query().limit(usize::MAX).execute_page(None).unwrap();
+        assert_eq!(page.results.len(), 7);
+        assert!(page.next.is_none());
+    }
 }
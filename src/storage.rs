@@ -1,12 +1,21 @@
 //! Storage layer for Engram: JSONL files + SQLite cache.
 
-use crate::types::{Edge, EdgeKind, Event, EventFilter, Item, Status};
+use crate::types::{
+    Change, Edge, EdgeKind, Event, EventFilter, Item, Page, Policy, Predicate, SearchHit, SortDirection, Status,
+};
 use eyre::{Context, Result};
 use rusqlite::{Connection, OptionalExtension, params};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Storage directory name.
 const ENGRAM_DIR: &str = ".engram";
@@ -23,10 +32,162 @@ const EVENTS_FILE: &str = "events.jsonl";
 /// SQLite database file.
 const DB_FILE: &str = "engram.db";
 
+/// JSON file holding the store's `Policy`.
+const POLICY_FILE: &str = "policy.json";
+
+/// `meta` key set while the cache holds buffered records not yet in JSONL.
+const UNFLUSHED_KEY: &str = "unflushed";
+
+/// File a handle holds a shared `flock` on while it has buffered records, so
+/// other processes can tell live unflushed records from a crashed writer's.
+const BUFFER_LOCK_FILE: &str = "buffer.lock";
+
+/// Page size for paginated queries whose filter sets no limit.
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+
+/// Largest page a paginated query returns; bigger limits are cut to this.
+pub const MAX_PAGE_SIZE: usize = 10_000;
+
+/// SQL condition on an edge `e` and the item `blocker` at its `to_id`: a
+/// `Blocks` edge that still holds up `e.from_id`.
+///
+/// `ready`, `blocked` and `holders` all share this (and `OPEN_CHILD`), so they
+/// never disagree about what blocks an item.
+const OPEN_BLOCKER: &str = "e.kind = 'blocks' AND blocker.status IN ('open', 'in_progress', 'blocked')";
+
+/// SQL condition on an edge `e` and the item `child` at its `from_id`: a
+/// `ParentChild` edge that still holds up the parent `e.to_id`.
+const OPEN_CHILD: &str = "e.kind = 'parent_child' AND child.status IN ('open', 'in_progress', 'blocked')";
+
+/// How long SQLite waits on a database locked by another process.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A step that upgrades the SQLite cache to `version`.
+#[derive(Clone)]
+struct CacheMigration {
+    /// Schema version after this migration runs.
+    version: u32,
+    /// What the migration changes.
+    description: &'static str,
+    /// SQL to apply.
+    sql: &'static str,
+    /// Whether existing rows must be repopulated from JSONL afterwards.
+    rebuild: bool,
+}
+
+/// Cache migrations, in order. Version 1 is the base schema created by
+/// `init_schema`, which must not change; the first entry here is version 2.
+const CACHE_MIGRATIONS: &[CacheMigration] = &[
+    CacheMigration {
+        version: 2,
+        description: "add item assignee and lease expiry",
+        sql: r#"
+        ALTER TABLE items ADD COLUMN assignee TEXT;
+        ALTER TABLE items ADD COLUMN lease_expires_at TEXT;
+        CREATE INDEX IF NOT EXISTS idx_items_assignee ON items(assignee);
+    "#,
+        rebuild: true,
+    },
+    CacheMigration {
+        version: 3,
+        description: "add full-text index over item titles and descriptions",
+        sql: r#"
+        CREATE VIRTUAL TABLE IF NOT EXISTS items_fts USING fts5(
+            title, description, tokenize = 'porter unicode61'
+        );
+    "#,
+        rebuild: true,
+    },
+];
+
+/// Current SQLite cache schema version.
+pub const SCHEMA_VERSION: u32 = 1 + CACHE_MIGRATIONS.len() as u32;
+
+/// A step that upgrades a JSONL record to `version`.
+struct RecordMigration {
+    /// Record version after this migration runs.
+    version: u32,
+    /// Rewrite a record from the given log in place.
+    apply: fn(LogFile, &mut serde_json::Map<String, serde_json::Value>),
+}
+
+/// Record migrations, in order. Records without a `"v"` field are version 0.
+const RECORD_MIGRATIONS: &[RecordMigration] = &[
+    // v1: records carry a format version; no field changes
+    RecordMigration {
+        version: 1,
+        apply: |_, _| {},
+    },
+];
+
+/// Current JSONL record format version, written as `"v"` on every record.
+pub const RECORD_VERSION: u32 = RECORD_MIGRATIONS.len() as u32;
+
 /// Storage handle for reading/writing engram data.
 pub struct Storage {
-    root: PathBuf,
+    /// Store root, or None for an in-memory cache with no JSONL files.
+    root: Option<PathBuf>,
     db: Connection,
+    /// Write lock state shared with outstanding guards.
+    lock: Arc<Mutex<LockState>>,
+    /// JSONL file lengths as of the last sync, to skip refreshes when nothing changed.
+    seen_lengths: Cell<[u64; 3]>,
+    /// Whether appends are buffered until `flush` instead of written immediately.
+    buffered: bool,
+    /// Buffered lines awaiting `flush`, in append order.
+    pending: Vec<(LogFile, String)>,
+    /// Shared lock on `BUFFER_LOCK_FILE`, held while `pending` is non-empty.
+    buffer_lock: Option<File>,
+    /// Changes appended or replayed since the last `take_changes`, if tracking is on.
+    changes: RefCell<Option<Vec<Change>>>,
+    /// The open transaction, if any.
+    txn: Option<Transaction>,
+}
+
+/// State saved when a transaction begins, to commit or roll back to.
+struct Transaction {
+    /// Length of `pending` before the transaction's first append.
+    pending_start: usize,
+    /// Length of `changes` before the transaction's first append.
+    changes_start: usize,
+    /// Buffering mode to restore afterwards.
+    buffered: bool,
+    /// Write lock held for the whole transaction.
+    _lock: WriteLock,
+}
+
+/// Reentrant state of the advisory write lock.
+#[derive(Default)]
+struct LockState {
+    /// Number of live guards on this handle.
+    depth: usize,
+    /// The locked .engram directory, open while `depth > 0`.
+    dir: Option<File>,
+}
+
+/// Advisory lock on the .engram directory, held for the duration of a write.
+///
+/// Guards nest: the lock is released when the last guard from this handle is
+/// dropped.
+pub(crate) struct WriteLock {
+    /// Shared lock state (None for in-memory storage).
+    state: Option<Arc<Mutex<LockState>>>,
+}
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        let Some(state) = &self.state else {
+            return;
+        };
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        state.depth -= 1;
+        if state.depth == 0
+            && let Some(dir) = state.dir.take()
+        {
+            // SAFETY: the descriptor is owned by `dir` and still open.
+            unsafe { libc::flock(dir.as_raw_fd(), libc::LOCK_UN) };
+        }
+    }
 }
 
 impl Storage {
@@ -52,15 +213,18 @@ impl Storage {
 
         // Create SQLite database
         let db_path = engram_dir.join(DB_FILE);
-        let db = Connection::open(&db_path).context("Failed to open SQLite database")?;
-
-        let mut storage = Self {
-            root: root.to_path_buf(),
-            db,
-        };
+        let storage = Self::with_connection(Some(root.to_path_buf()), Connection::open(&db_path))?;
 
+        let _lock = storage.acquire_lock()?;
         storage.init_schema()?;
-        storage.rebuild_from_jsonl()?;
+        storage.upgrade_records()?;
+
+        // Rebuilding would drop a live writer's buffered records
+        if storage.buffers_live()? {
+            storage.sync_with_jsonl()?;
+        } else {
+            storage.rebuild()?;
+        }
 
         Ok(storage)
     }
@@ -73,21 +237,148 @@ impl Storage {
         }
 
         let db_path = engram_dir.join(DB_FILE);
-        let db = Connection::open(&db_path).context("Failed to open SQLite database")?;
+        let storage = Self::with_connection(Some(root.to_path_buf()), Connection::open(&db_path))?;
+
+        let _lock = storage.acquire_lock()?;
+        storage.init_schema()?;
+        storage.upgrade_records()?;
+
+        // Replay appended records, or rebuild if the files were rewritten
+        storage.sync_with_jsonl()?;
+
+        Ok(storage)
+    }
+
+    /// Create an in-memory storage with an empty cache and no JSONL files.
+    ///
+    /// Appends only update the cache; nothing is persisted.
+    pub(crate) fn in_memory() -> Result<Self> {
+        let storage = Self::with_connection(None, Connection::open_in_memory())?;
+        storage.init_schema()?;
+        Ok(storage)
+    }
+
+    /// Wrap a freshly opened SQLite connection.
+    fn with_connection(root: Option<PathBuf>, db: rusqlite::Result<Connection>) -> Result<Self> {
+        let db = db.context("Failed to open SQLite database")?;
+        db.busy_timeout(BUSY_TIMEOUT)
+            .context("Failed to set SQLite busy timeout")?;
 
-        let mut storage = Self {
-            root: root.to_path_buf(),
+        Ok(Self {
+            root,
             db,
+            lock: Arc::default(),
+            seen_lengths: Cell::new([0; 3]),
+            buffered: false,
+            pending: Vec::new(),
+            buffer_lock: None,
+            changes: RefCell::new(None),
+            txn: None,
+        })
+    }
+
+    /// Take the write lock without refreshing the cache.
+    ///
+    /// Uses `flock` on the .engram directory, so it coordinates every process
+    /// (CLI, daemon, library users) working on the same store. Blocks until
+    /// the lock is available.
+    fn acquire_lock(&self) -> Result<WriteLock> {
+        let Some(root) = &self.root else {
+            return Ok(WriteLock { state: None });
         };
+        let mut state = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        if state.depth == 0 {
+            let dir = File::open(root.join(ENGRAM_DIR)).context("Failed to open .engram directory for locking")?;
+            loop {
+                // SAFETY: the descriptor is owned by `dir` and still open.
+                if unsafe { libc::flock(dir.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                    break;
+                }
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err).context("Failed to lock .engram directory");
+                }
+            }
+            state.dir = Some(dir);
+        }
 
-        storage.init_schema()?;
+        state.depth += 1;
+        Ok(WriteLock {
+            state: Some(Arc::clone(&self.lock)),
+        })
+    }
+
+    /// Whether this handle currently holds the write lock.
+    fn lock_held(&self) -> bool {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner()).depth > 0
+    }
 
-        // Check consistency and rebuild if needed
-        if storage.needs_rebuild()? {
-            storage.rebuild_from_jsonl()?;
+    /// Take the write lock and catch up with records other processes appended.
+    ///
+    /// Callers that validate against the cache before appending should hold
+    /// this for the whole read-validate-write sequence.
+    pub(crate) fn lock(&self) -> Result<WriteLock> {
+        let nested = self.lock_held();
+        let lock = self.acquire_lock()?;
+        if !nested && self.root.is_some() {
+            self.catch_up()?;
         }
+        Ok(lock)
+    }
 
-        Ok(storage)
+    /// Replay records appended to the JSONL files by other processes.
+    ///
+    /// Cheap when nothing changed: only the file lengths are checked. This
+    /// does not re-verify prefixes; a file that shrank triggers a full
+    /// rebuild, and other rewrites are detected on the next open.
+    pub fn refresh(&self) -> Result<()> {
+        if self.root.is_none() || self.lock_held() {
+            return Ok(());
+        }
+        if self.log_lengths()? == self.seen_lengths.get() {
+            return Ok(());
+        }
+
+        let _lock = self.acquire_lock()?;
+        self.catch_up()
+    }
+
+    /// Current length of each JSONL file, in `LogFile::ALL` order.
+    fn log_lengths(&self) -> Result<[u64; 3]> {
+        let mut lengths = [0; 3];
+        for log in LogFile::ALL {
+            if let Some(path) = self.log_path(log) {
+                lengths[log.index()] = file_len(&path)?;
+            }
+        }
+        Ok(lengths)
+    }
+
+    /// Replay whatever lies past the recorded cursors. Call with the lock held.
+    fn catch_up(&self) -> Result<()> {
+        let lengths = self.log_lengths()?;
+        let mut cursors = Vec::with_capacity(LogFile::ALL.len());
+
+        for log in LogFile::ALL {
+            let Some(cursor) = self.read_cursor(log)? else {
+                return self.rebuild();
+            };
+            if lengths[log.index()] < cursor.offset {
+                log::info!("{} shrank, rebuilding cache", log.file_name());
+                return self.rebuild();
+            }
+            cursors.push(cursor);
+        }
+
+        self.track_external_appends(lengths)?;
+
+        if cursors.iter().zip(lengths).all(|(cursor, len)| cursor.offset == len) {
+            self.seen_lengths.set(lengths);
+            return Ok(());
+        }
+
+        self.replay_from(&cursors)
     }
 
     /// Initialize SQLite schema.
@@ -145,57 +436,145 @@ impl Storage {
             )
             .context("Failed to initialize schema")?;
 
-        Ok(())
+        self.migrate_cache(CACHE_MIGRATIONS)
     }
 
-    /// Check if SQLite needs to be rebuilt from JSONL.
-    fn needs_rebuild(&self) -> Result<bool> {
-        let items_path = self.root.join(ENGRAM_DIR).join(ITEMS_FILE);
-        let edges_path = self.root.join(ENGRAM_DIR).join(EDGES_FILE);
-        let events_path = self.root.join(ENGRAM_DIR).join(EVENTS_FILE);
+    /// Bring the cache schema up to the latest version in `migrations`.
+    ///
+    /// Caches created before versioning have no recorded version and are
+    /// treated as the base schema. If any applied migration needs existing rows
+    /// repopulated, the replay cursors are dropped so the next sync rebuilds.
+    fn migrate_cache(&self, migrations: &[CacheMigration]) -> Result<()> {
+        let target = 1 + migrations.len() as u32;
+        let current: u32 = self
+            .db
+            .query_row("SELECT value FROM meta WHERE key = 'schema_version'", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .optional()?
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1);
+
+        if current > target {
+            eyre::bail!(
+                "engram.db has schema version {} but this engram supports up to {}; \
+                 upgrade engram or delete the cache to rebuild it",
+                current,
+                target
+            );
+        }
 
-        let items_lines = count_lines(&items_path)?;
-        let edges_lines = count_lines(&edges_path)?;
-        let events_lines = count_lines(&events_path)?;
+        let mut needs_rebuild = false;
+        for migration in migrations.iter().filter(|m| m.version > current) {
+            log::info!(
+                "Migrating cache to schema version {}: {}",
+                migration.version,
+                migration.description
+            );
+            let tx = self.db.unchecked_transaction()?;
+            tx.execute_batch(migration.sql)
+                .with_context(|| format!("Failed to apply cache migration {}", migration.version))?;
+            tx.execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?)",
+                params![migration.version.to_string()],
+            )?;
+            tx.commit()?;
+            needs_rebuild |= migration.rebuild;
+        }
 
-        let stored_items: i64 = self
-            .db
-            .query_row(
-                "SELECT COALESCE((SELECT value FROM meta WHERE key = 'jsonl_items_lines'), '0')",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
+        self.db.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?)",
+            params![target.to_string()],
+        )?;
 
-        let stored_edges: i64 = self
-            .db
-            .query_row(
-                "SELECT COALESCE((SELECT value FROM meta WHERE key = 'jsonl_edges_lines'), '0')",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
+        if needs_rebuild {
+            for log in LogFile::ALL {
+                self.db.execute(
+                    "DELETE FROM meta WHERE key IN (?, ?)",
+                    params![
+                        format!("{}_offset", log.meta_prefix()),
+                        format!("{}_hash", log.meta_prefix())
+                    ],
+                )?;
+            }
+        }
+
+        Ok(())
+    }
 
-        let stored_events: i64 = self
+    /// Rewrite JSONL files whose records predate `RECORD_VERSION`.
+    ///
+    /// Runs once per cache: after a successful pass the version is recorded in
+    /// `meta`. Records that arrive later from older writers (e.g. via git
+    /// merge) are still upgraded in memory on replay.
+    fn upgrade_records(&self) -> Result<()> {
+        let done: Option<String> = self
             .db
-            .query_row(
-                "SELECT COALESCE((SELECT value FROM meta WHERE key = 'jsonl_events_lines'), '0')",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
+            .query_row("SELECT value FROM meta WHERE key = 'record_version'", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        if done == Some(RECORD_VERSION.to_string()) {
+            return Ok(());
+        }
+
+        for log in LogFile::ALL {
+            if let Some(path) = self.log_path(log) {
+                rewrite_log(&path, log)?;
+            }
+        }
+
+        self.db.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('record_version', ?)",
+            params![RECORD_VERSION.to_string()],
+        )?;
+
+        Ok(())
+    }
+
+    /// Bring the SQLite cache up to date with the JSONL files.
+    ///
+    /// If every file still starts with the prefix recorded in `meta`, only the
+    /// bytes appended since then are replayed. Otherwise (missing metadata, a
+    /// truncated file, or a rewritten prefix after a git checkout or rebase)
+    /// the cache is rebuilt from scratch.
+    fn sync_with_jsonl(&self) -> Result<()> {
+        // A writer died with buffered records the JSONL files never received
+        if self.meta(UNFLUSHED_KEY)?.is_some() && !self.buffers_live()? {
+            log::info!("Cache has unflushed records, rebuilding");
+            return self.rebuild();
+        }
+
+        let mut cursors = Vec::with_capacity(LogFile::ALL.len());
+
+        for log in LogFile::ALL {
+            let cursor = match self.read_cursor(log)? {
+                Some(cursor) => cursor,
+                None => return self.rebuild(),
+            };
+
+            let Some(path) = self.log_path(log) else {
+                return Ok(());
+            };
+            if !verify_prefix(&path, &cursor)? {
+                log::info!("{} prefix changed, rebuilding cache", log.file_name());
+                return self.rebuild();
+            }
+
+            cursors.push(cursor);
+        }
 
-        Ok(items_lines as i64 != stored_items
-            || edges_lines as i64 != stored_edges
-            || events_lines as i64 != stored_events)
+        self.replay_from(&cursors)
     }
 
     /// Rebuild SQLite cache from JSONL files.
     pub fn rebuild_from_jsonl(&mut self) -> Result<()> {
-        let items_path = self.root.join(ENGRAM_DIR).join(ITEMS_FILE);
-        let edges_path = self.root.join(ENGRAM_DIR).join(EDGES_FILE);
-        let events_path = self.root.join(ENGRAM_DIR).join(EVENTS_FILE);
+        let _lock = self.acquire_lock()?;
+        self.rebuild()
+    }
 
+    /// Clear the cache and replay every JSONL file from the start.
+    fn rebuild(&self) -> Result<()> {
         // Clear existing data
         self.db
             .execute_batch(
@@ -203,128 +582,77 @@ impl Storage {
                 DELETE FROM labels;
                 DELETE FROM edges;
                 DELETE FROM items;
+                DELETE FROM items_fts;
                 DELETE FROM events;
             "#,
             )
             .context("Failed to clear tables")?;
+        self.db
+            .execute("DELETE FROM meta WHERE key = ?", params![UNFLUSHED_KEY])?;
 
-        // Read items (last occurrence wins)
-        let mut items: HashMap<String, Item> = HashMap::new();
-        let mut items_line_count = 0;
-
-        if items_path.exists() {
-            let file = File::open(&items_path).context("Failed to open items.jsonl")?;
-            let reader = BufReader::new(file);
-
-            for line in reader.lines() {
-                items_line_count += 1;
-                let line = match line {
-                    Ok(l) => l,
-                    Err(e) => {
-                        log::warn!("Failed to read line {}: {}", items_line_count, e);
-                        continue;
-                    }
-                };
+        let start = vec![LogCursor::default(); LogFile::ALL.len()];
+        self.replay_from(&start)
+    }
 
-                if line.trim().is_empty() {
-                    continue;
-                }
+    /// Replay JSONL records after the given cursors into SQLite.
+    ///
+    /// `cursors` is indexed in `LogFile::ALL` order. Within the replayed range
+    /// the last occurrence of each record wins, matching append semantics.
+    fn replay_from(&self, cursors: &[LogCursor]) -> Result<()> {
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .context("Failed to start replay transaction")?;
 
-                match serde_json::from_str::<Item>(&line) {
-                    Ok(item) => {
-                        items.insert(item.id.clone(), item);
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to parse item at line {}: {}", items_line_count, e);
-                    }
-                }
+        let items_path = self.log_path(LogFile::Items);
+        let edges_path = self.log_path(LogFile::Edges);
+        let events_path = self.log_path(LogFile::Events);
+
+        // Read items (last occurrence wins)
+        let mut items: HashMap<String, Item> = HashMap::new();
+        let items_cursor = scan_log(items_path.as_deref(), &cursors[0], |line, offset| {
+            if let Some(item) = replay_record::<Item>(LogFile::Items, line, offset)? {
+                items.insert(item.id.clone(), item);
             }
-        }
+            Ok(())
+        })?;
 
         // Insert items into SQLite
         for item in items.values() {
             self.insert_item_to_db(item)?;
         }
 
-        // Read edges (handle tombstones)
-        let mut edges: HashMap<(String, String, String), Option<Edge>> = HashMap::new();
-        let mut edges_line_count = 0;
-
-        if edges_path.exists() {
-            let file = File::open(&edges_path).context("Failed to open edges.jsonl")?;
-            let reader = BufReader::new(file);
-
-            for line in reader.lines() {
-                edges_line_count += 1;
-                let line = match line {
-                    Ok(l) => l,
-                    Err(e) => {
-                        log::warn!("Failed to read edge line {}: {}", edges_line_count, e);
-                        continue;
-                    }
-                };
-
-                if line.trim().is_empty() {
-                    continue;
-                }
-
-                match serde_json::from_str::<Edge>(&line) {
-                    Ok(edge) => {
-                        let key = (
-                            edge.from_id.clone(),
-                            edge.to_id.clone(),
-                            format!("{:?}", edge.kind).to_lowercase(),
-                        );
-                        if edge.deleted {
-                            edges.insert(key, None);
-                        } else {
-                            edges.insert(key, Some(edge));
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to parse edge at line {}: {}", edges_line_count, e);
-                    }
-                }
+        // Read edges (last occurrence per key wins, including tombstones)
+        let mut edges: HashMap<(String, String, String), Edge> = HashMap::new();
+        let edges_cursor = scan_log(edges_path.as_deref(), &cursors[1], |line, offset| {
+            if let Some(edge) = replay_record::<Edge>(LogFile::Edges, line, offset)? {
+                let key = (
+                    edge.from_id.clone(),
+                    edge.to_id.clone(),
+                    format!("{:?}", edge.kind).to_lowercase(),
+                );
+                edges.insert(key, edge);
+            }
+            Ok(())
+        })?;
+
+        // Apply edges, removing tombstoned ones
+        for edge in edges.values() {
+            if edge.deleted {
+                self.delete_edge_from_db(edge)?;
+            } else {
+                self.insert_edge_to_db(edge)?;
             }
-        }
-
-        // Insert non-deleted edges into SQLite
-        for edge in edges.values().flatten() {
-            self.insert_edge_to_db(edge)?;
         }
 
         // Read events (last occurrence wins based on id)
         let mut events: HashMap<String, Event> = HashMap::new();
-        let mut events_line_count = 0;
-
-        if events_path.exists() {
-            let file = File::open(&events_path).context("Failed to open events.jsonl")?;
-            let reader = BufReader::new(file);
-
-            for line in reader.lines() {
-                events_line_count += 1;
-                let line = match line {
-                    Ok(l) => l,
-                    Err(e) => {
-                        log::warn!("Failed to read event line {}: {}", events_line_count, e);
-                        continue;
-                    }
-                };
-
-                if line.trim().is_empty() {
-                    continue;
-                }
-
-                match serde_json::from_str::<Event>(&line) {
-                    Ok(event) => {
-                        events.insert(event.id.clone(), event);
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to parse event at line {}: {}", events_line_count, e);
-                    }
-                }
+        let events_cursor = scan_log(events_path.as_deref(), &cursors[2], |line, offset| {
+            if let Some(event) = replay_record::<Event>(LogFile::Events, line, offset)? {
+                events.insert(event.id.clone(), event);
             }
-        }
+            Ok(())
+        })?;
 
         // Insert events into SQLite
         for event in events.values() {
@@ -332,84 +660,495 @@ impl Storage {
         }
 
         // Update metadata
-        self.db.execute(
-            "INSERT OR REPLACE INTO meta (key, value) VALUES ('jsonl_items_lines', ?)",
-            params![items_line_count.to_string()],
+        self.write_cursor(LogFile::Items, &items_cursor)?;
+        self.write_cursor(LogFile::Edges, &edges_cursor)?;
+        self.write_cursor(LogFile::Events, &events_cursor)?;
+
+        tx.commit().context("Failed to commit replay")?;
+
+        Ok(())
+    }
+
+    /// Path of a JSONL log file (None for in-memory storage).
+    fn log_path(&self, log: LogFile) -> Option<PathBuf> {
+        self.root
+            .as_ref()
+            .map(|root| root.join(ENGRAM_DIR).join(log.file_name()))
+    }
+
+    /// Read every item record in append order, including superseded versions.
+    pub fn item_log(&self) -> Result<Vec<Item>> {
+        let mut items = Vec::new();
+        scan_log(
+            self.log_path(LogFile::Items).as_deref(),
+            &LogCursor::default(),
+            |line, offset| {
+                items.extend(replay_record::<Item>(LogFile::Items, line, offset)?);
+                Ok(())
+            },
+        )?;
+        Ok(items)
+    }
+
+    /// Read every edge record in append order, including tombstones.
+    pub fn edge_log(&self) -> Result<Vec<Edge>> {
+        let mut edges = Vec::new();
+        scan_log(
+            self.log_path(LogFile::Edges).as_deref(),
+            &LogCursor::default(),
+            |line, offset| {
+                edges.extend(replay_record::<Edge>(LogFile::Edges, line, offset)?);
+                Ok(())
+            },
         )?;
+        Ok(edges)
+    }
+
+    /// Build an in-memory cache of the graph as it stood at `as_of`.
+    ///
+    /// Item versions updated after `as_of` and edge records created after it
+    /// are ignored; otherwise the usual last-write-wins replay applies.
+    pub fn snapshot_at(&self, as_of: chrono::DateTime<chrono::Utc>) -> Result<Storage> {
+        let snapshot = Storage::in_memory()?;
+
+        let mut items: HashMap<String, Item> = HashMap::new();
+        for item in self.item_log()? {
+            if item.updated_at <= as_of {
+                items.insert(item.id.clone(), item);
+            }
+        }
+        for item in items.values() {
+            snapshot.insert_item_to_db(item)?;
+        }
+
+        for edge in self.edge_log()? {
+            if edge.created_at > as_of {
+                continue;
+            }
+            if edge.deleted {
+                snapshot.delete_edge_from_db(&edge)?;
+            } else {
+                snapshot.insert_edge_to_db(&edge)?;
+            }
+        }
+
+        Ok(snapshot)
+    }
+
+    /// Read the replay cursor for a log file from `meta`.
+    fn read_cursor(&self, log: LogFile) -> Result<Option<LogCursor>> {
+        let offset: Option<String> = self
+            .db
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?",
+                params![format!("{}_offset", log.meta_prefix())],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let hash: Option<String> = self
+            .db
+            .query_row(
+                "SELECT value FROM meta WHERE key = ?",
+                params![format!("{}_hash", log.meta_prefix())],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        match (offset.and_then(|o| o.parse::<u64>().ok()), hash) {
+            (Some(offset), Some(hash)) => Ok(Some(LogCursor { offset, hash })),
+            _ => Ok(None),
+        }
+    }
+
+    /// Persist the replay cursor for a log file to `meta`.
+    fn write_cursor(&self, log: LogFile, cursor: &LogCursor) -> Result<()> {
+        let mut seen = self.seen_lengths.get();
+        seen[log.index()] = cursor.offset;
+        self.seen_lengths.set(seen);
+
         self.db.execute(
-            "INSERT OR REPLACE INTO meta (key, value) VALUES ('jsonl_edges_lines', ?)",
-            params![edges_line_count.to_string()],
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)",
+            params![format!("{}_offset", log.meta_prefix()), cursor.offset.to_string()],
         )?;
         self.db.execute(
-            "INSERT OR REPLACE INTO meta (key, value) VALUES ('jsonl_events_lines', ?)",
-            params![events_line_count.to_string()],
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)",
+            params![format!("{}_hash", log.meta_prefix()), cursor.hash],
         )?;
-
         Ok(())
     }
 
-    /// Insert an item into SQLite.
-    fn insert_item_to_db(&self, item: &Item) -> Result<()> {
-        let status_str = match item.status {
-            Status::Open => "open",
-            Status::InProgress => "in_progress",
-            Status::Blocked => "blocked",
-            Status::Closed => "closed",
+    /// Append a serialized record to a JSONL file and advance its cursor.
+    fn append_line(&mut self, log: LogFile, json: &str) -> Result<()> {
+        let Some(path) = self.log_path(log) else {
+            return Ok(());
         };
+        let _lock = self.lock()?;
+        let line = format!("{}\n", json);
 
-        self.db.execute(
-            r#"
-            INSERT OR REPLACE INTO items (id, title, description, status, priority, created_at, updated_at, closed_at, close_reason)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-            "#,
-            params![
-                item.id,
-                item.title,
-                item.description,
-                status_str,
-                item.priority,
-                item.created_at.to_rfc3339(),
-                item.updated_at.to_rfc3339(),
-                item.closed_at.map(|dt| dt.to_rfc3339()),
-                item.close_reason,
-            ],
-        )?;
+        if !self.buffered {
+            let lines = [line];
+            write_lines(&path, log, &lines)?;
+            return self.advance_cursor(log, &lines);
+        }
 
-        // Delete existing labels and insert new ones
-        self.db
-            .execute("DELETE FROM labels WHERE item_id = ?", params![item.id])?;
-        for label in &item.labels {
-            self.db.execute(
-                "INSERT INTO labels (item_id, label) VALUES (?, ?)",
-                params![item.id, label],
-            )?;
+        if self.buffer_lock.is_none() {
+            self.buffer_lock = Some(
+                self.lock_buffer_file(libc::LOCK_SH)?
+                    .ok_or_else(|| eyre::eyre!("Failed to lock {}", BUFFER_LOCK_FILE))?,
+            );
         }
+        // Set on every append: another handle's flush may have cleared it
+        self.db.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?, '1')",
+            params![UNFLUSHED_KEY],
+        )?;
+        self.pending.push((log, line));
 
         Ok(())
     }
 
-    /// Insert an edge into SQLite.
-    fn insert_edge_to_db(&self, edge: &Edge) -> Result<()> {
-        let kind_str = match edge.kind {
-            EdgeKind::Blocks => "blocks",
-            EdgeKind::ParentChild => "parent_child",
-            EdgeKind::Related => "related",
+    /// Lock `BUFFER_LOCK_FILE` with `operation` without blocking. Returns
+    /// None if a conflicting lock is held.
+    fn lock_buffer_file(&self, operation: libc::c_int) -> Result<Option<File>> {
+        let Some(root) = &self.root else {
+            return Ok(None);
         };
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(root.join(ENGRAM_DIR).join(BUFFER_LOCK_FILE))
+            .with_context(|| format!("Failed to open {}", BUFFER_LOCK_FILE))?;
+        loop {
+            // SAFETY: the descriptor is owned by `file` and still open.
+            if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == 0 {
+                return Ok(Some(file));
+            }
+            let err = std::io::Error::last_os_error();
+            match err.kind() {
+                std::io::ErrorKind::Interrupted => continue,
+                std::io::ErrorKind::WouldBlock => return Ok(None),
+                _ => return Err(err).with_context(|| format!("Failed to lock {}", BUFFER_LOCK_FILE)),
+            }
+        }
+    }
 
-        self.db.execute(
-            r#"
-            INSERT OR REPLACE INTO edges (from_id, to_id, kind, created_at)
-            VALUES (?, ?, ?, ?)
-            "#,
-            params![edge.from_id, edge.to_id, kind_str, edge.created_at.to_rfc3339(),],
-        )?;
+    /// Whether any handle, in this process or another, has buffered records
+    /// not yet flushed.
+    fn buffers_live(&self) -> Result<bool> {
+        Ok(self.root.is_some() && self.lock_buffer_file(libc::LOCK_EX)?.is_none())
+    }
 
+    /// Buffer appends in memory until `flush` instead of writing each one.
+    ///
+    /// The cache is updated immediately, so reads see buffered records. The
+    /// write lock is only held for each append and for the flush, so other
+    /// processes keep writing in between; records they append to the same
+    /// items are replayed under the flushed ones. Turning buffering off
+    /// flushes.
+    pub(crate) fn set_buffered(&mut self, buffered: bool) -> Result<()> {
+        if !buffered {
+            self.flush()?;
+        }
+        self.buffered = buffered;
         Ok(())
     }
 
-    /// Insert an event into SQLite.
-    fn insert_event_to_db(&self, event: &Event) -> Result<()> {
-        self.db.execute(
+    /// Write buffered appends to the JSONL files.
+    ///
+    /// Each file gets a single write and fsync. Does nothing when nothing is
+    /// buffered, or while a transaction is open (it flushes on commit). If a
+    /// write fails, every file is cut back to its length before the flush and
+    /// all records stay buffered for the next one.
+    pub fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() || self.txn.is_some() {
+            return Ok(());
+        }
+
+        let _lock = self.lock()?;
+        self.write_pending()?;
+        self.clear_pending()?;
+        self.replay_own_writes()
+    }
+
+    /// Drop the pending records once written, and the unflushed marker with
+    /// them unless another handle still buffers.
+    fn clear_pending(&mut self) -> Result<()> {
+        self.pending.clear();
+        self.buffer_lock = None;
+        if !self.buffers_live()? {
+            self.db
+                .execute("DELETE FROM meta WHERE key = ?", params![UNFLUSHED_KEY])?;
+        }
+        Ok(())
+    }
+
+    /// Replay the lines this handle just wrote past the cursors. Call with the
+    /// lock held.
+    ///
+    /// This leaves the cache agreeing with the files when another process
+    /// wrote the same records while ours were buffered.
+    fn replay_own_writes(&self) -> Result<()> {
+        let cursors = LogFile::ALL
+            .into_iter()
+            .map(|log| Ok(self.read_cursor(log)?.unwrap_or_default()))
+            .collect::<Result<Vec<_>>>()?;
+        self.replay_from(&cursors)
+    }
+
+    /// Append every pending line to its file, all or none. Call with the lock
+    /// held.
+    fn write_pending(&self) -> Result<()> {
+        let mut written: Vec<(PathBuf, LogFile, u64)> = Vec::new();
+        for log in LogFile::ALL {
+            let Some(path) = self.log_path(log) else {
+                continue;
+            };
+            let lines: Vec<String> = self
+                .pending
+                .iter()
+                .filter(|(l, _)| *l == log)
+                .map(|(_, line)| line.clone())
+                .collect();
+            if lines.is_empty() {
+                continue;
+            }
+
+            let len = file_len(&path)?;
+            if let Err(e) = write_lines(&path, log, &lines) {
+                for (path, log, len) in written {
+                    if let Err(e) = truncate_log(&path, log, len) {
+                        log::error!("{}", e);
+                    }
+                }
+                return Err(e);
+            }
+            written.push((path, log, len));
+        }
+        Ok(())
+    }
+
+    /// Whether a transaction is open.
+    pub(crate) fn in_transaction(&self) -> bool {
+        self.txn.is_some()
+    }
+
+    /// Start a transaction.
+    ///
+    /// Appends are buffered and the cache changes are held in a SQLite
+    /// savepoint until `commit_transaction` or `rollback_transaction`, so
+    /// reads inside the transaction see its own writes. The write lock is held
+    /// throughout.
+    pub(crate) fn begin_transaction(&mut self) -> Result<()> {
+        let lock = self.lock()?;
+        self.db.execute_batch("SAVEPOINT engram_txn")?;
+        self.txn = Some(Transaction {
+            pending_start: self.pending.len(),
+            changes_start: self.changes.borrow().as_ref().map_or(0, Vec::len),
+            buffered: self.buffered,
+            _lock: lock,
+        });
+        self.buffered = true;
+        Ok(())
+    }
+
+    /// Keep the transaction's records.
+    ///
+    /// Unless buffering was already on, they are written straight away with a
+    /// single append per file, before the savepoint is released: if any write
+    /// fails, the files are cut back and the whole transaction rolled back.
+    pub(crate) fn commit_transaction(&mut self) -> Result<()> {
+        let Some(txn) = self.txn.as_ref() else {
+            return Ok(());
+        };
+        if txn.buffered {
+            self.txn = None;
+            self.buffered = true;
+            return self.db.execute_batch("RELEASE engram_txn").map_err(Into::into);
+        }
+
+        if let Err(e) = self.write_pending() {
+            if let Err(e) = self.rollback_transaction() {
+                log::error!("Failed to roll back transaction: {}", e);
+            }
+            return Err(e);
+        }
+
+        // Keep the transaction's lock until the cache has caught up
+        let _txn = self.txn.take();
+        self.buffered = false;
+        self.clear_pending()?;
+        self.db.execute_batch("RELEASE engram_txn")?;
+        self.replay_own_writes()
+    }
+
+    /// Discard the transaction's records and cache changes.
+    pub(crate) fn rollback_transaction(&mut self) -> Result<()> {
+        let Some(txn) = self.txn.take() else {
+            return Ok(());
+        };
+        self.buffered = txn.buffered;
+        self.pending.truncate(txn.pending_start);
+        if self.pending.is_empty() {
+            self.buffer_lock = None;
+        }
+        if let Some(changes) = self.changes.get_mut() {
+            changes.truncate(txn.changes_start);
+        }
+        self.db.execute_batch("ROLLBACK TO engram_txn; RELEASE engram_txn")?;
+        Ok(())
+    }
+
+    /// Advance a log's cursor past lines just written to it.
+    fn advance_cursor(&self, log: LogFile, lines: &[String]) -> Result<()> {
+        let mut cursor = self.read_cursor(log)?.unwrap_or_default();
+        for line in lines {
+            cursor.advance(line.as_bytes());
+        }
+        self.write_cursor(log, &cursor)
+    }
+
+    /// Read a value from `meta`.
+    fn meta(&self, key: &str) -> Result<Option<String>> {
+        Ok(self
+            .db
+            .query_row("SELECT value FROM meta WHERE key = ?", params![key], |row| row.get(0))
+            .optional()?)
+    }
+
+    /// Read the store's policy (the default if none has been set).
+    pub fn policy(&self) -> Result<Policy> {
+        let Some(path) = self.policy_path() else {
+            return Ok(Policy::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).context("Failed to parse store policy"),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Policy::default()),
+            Err(e) => Err(e).context("Failed to read store policy"),
+        }
+    }
+
+    /// Persist the store's policy.
+    ///
+    /// The policy lives in `.engram/policy.json` next to the JSONL files, so
+    /// it survives cache rebuilds and is shared through git. In-memory storage
+    /// has nowhere to keep it and always uses the default.
+    pub fn set_policy(&mut self, policy: &Policy) -> Result<()> {
+        let Some(path) = self.policy_path() else {
+            return Ok(());
+        };
+        let _lock = self.lock()?;
+
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp_path).context("Failed to create policy file")?;
+        writeln!(file, "{}", serde_json::to_string_pretty(policy)?).context("Failed to write policy file")?;
+        file.sync_all().context("Failed to sync policy file")?;
+        fs::rename(&tmp_path, &path).context("Failed to replace policy file")?;
+
+        Ok(())
+    }
+
+    /// Path of the policy file (None for in-memory storage).
+    fn policy_path(&self) -> Option<PathBuf> {
+        self.root.as_ref().map(|root| root.join(ENGRAM_DIR).join(POLICY_FILE))
+    }
+
+    /// Insert an item into SQLite.
+    fn insert_item_to_db(&self, item: &Item) -> Result<()> {
+        let status_str = match item.status {
+            Status::Open => "open",
+            Status::InProgress => "in_progress",
+            Status::Blocked => "blocked",
+            Status::Closed => "closed",
+        };
+
+        // REPLACE gives the row a new rowid, so drop the old index entry first
+        let old_rowid: Option<i64> = self
+            .db
+            .query_row("SELECT rowid FROM items WHERE id = ?", params![item.id], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        if let Some(rowid) = old_rowid {
+            self.db
+                .execute("DELETE FROM items_fts WHERE rowid = ?", params![rowid])?;
+        }
+
+        self.db.execute(
+            r#"
+            INSERT OR REPLACE INTO items (id, title, description, status, priority, created_at, updated_at, closed_at, close_reason, assignee, lease_expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+            params![
+                item.id,
+                item.title,
+                item.description,
+                status_str,
+                item.priority,
+                item.created_at.to_rfc3339(),
+                item.updated_at.to_rfc3339(),
+                item.closed_at.map(|dt| dt.to_rfc3339()),
+                item.close_reason,
+                item.assignee,
+                item.lease_expires_at.map(|dt| dt.to_rfc3339()),
+            ],
+        )?;
+        self.db.execute(
+            "INSERT INTO items_fts (rowid, title, description) VALUES (?, ?, ?)",
+            params![self.db.last_insert_rowid(), item.title, item.description],
+        )?;
+
+        // Delete existing labels and insert new ones
+        self.db
+            .execute("DELETE FROM labels WHERE item_id = ?", params![item.id])?;
+        for label in &item.labels {
+            self.db.execute(
+                "INSERT INTO labels (item_id, label) VALUES (?, ?)",
+                params![item.id, label],
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Insert an edge into SQLite.
+    fn insert_edge_to_db(&self, edge: &Edge) -> Result<()> {
+        let kind_str = match edge.kind {
+            EdgeKind::Blocks => "blocks",
+            EdgeKind::ParentChild => "parent_child",
+            EdgeKind::Related => "related",
+        };
+
+        self.db.execute(
+            r#"
+            INSERT OR REPLACE INTO edges (from_id, to_id, kind, created_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+            params![edge.from_id, edge.to_id, kind_str, edge.created_at.to_rfc3339(),],
+        )?;
+
+        Ok(())
+    }
+
+    /// Remove an edge from SQLite.
+    fn delete_edge_from_db(&self, edge: &Edge) -> Result<()> {
+        let kind_str = match edge.kind {
+            EdgeKind::Blocks => "blocks",
+            EdgeKind::ParentChild => "parent_child",
+            EdgeKind::Related => "related",
+        };
+
+        self.db.execute(
+            "DELETE FROM edges WHERE from_id = ? AND to_id = ? AND kind = ?",
+            params![edge.from_id, edge.to_id, kind_str],
+        )?;
+
+        Ok(())
+    }
+
+    /// Insert an event into SQLite.
+    fn insert_event_to_db(&self, event: &Event) -> Result<()> {
+        self.db.execute(
             r#"
             INSERT OR REPLACE INTO events (id, kind, source_task, target_task, payload, timestamp)
             VALUES (?, ?, ?, ?, ?, ?)
@@ -429,94 +1168,122 @@ impl Storage {
 
     /// Append an item to the JSONL file.
     pub fn append_item(&mut self, item: &Item) -> Result<()> {
-        let items_path = self.root.join(ENGRAM_DIR).join(ITEMS_FILE);
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&items_path)
-            .context("Failed to open items.jsonl for append")?;
-
-        let json = serde_json::to_string(item).context("Failed to serialize item")?;
-        writeln!(file, "{}", json).context("Failed to write to items.jsonl")?;
-        file.sync_all().context("Failed to sync items.jsonl")?;
+        let json = encode_record(item).context("Failed to serialize item")?;
+        self.append_line(LogFile::Items, &json)?;
+
+        if self.changes.borrow().is_some() {
+            let change = match self.get_item(&item.id)? {
+                None => Change::ItemCreated { item: item.clone() },
+                Some(previous) if item.status == Status::Closed && previous.status != Status::Closed => {
+                    Change::ItemClosed { item: item.clone() }
+                }
+                Some(_) => Change::ItemUpdated { item: item.clone() },
+            };
+            self.push_change(change);
+        }
 
         // Update SQLite cache
         self.insert_item_to_db(item)?;
 
-        // Update line count
-        self.db.execute(
-            "UPDATE meta SET value = CAST(CAST(value AS INTEGER) + 1 AS TEXT) WHERE key = 'jsonl_items_lines'",
-            [],
-        )?;
-
         Ok(())
     }
 
     /// Append an edge to the JSONL file.
     pub fn append_edge(&mut self, edge: &Edge) -> Result<()> {
-        let edges_path = self.root.join(ENGRAM_DIR).join(EDGES_FILE);
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&edges_path)
-            .context("Failed to open edges.jsonl for append")?;
-
-        let json = serde_json::to_string(edge).context("Failed to serialize edge")?;
-        writeln!(file, "{}", json).context("Failed to write to edges.jsonl")?;
-        file.sync_all().context("Failed to sync edges.jsonl")?;
+        let json = encode_record(edge).context("Failed to serialize edge")?;
+        self.append_line(LogFile::Edges, &json)?;
 
         // Update SQLite cache (only if not deleted)
         if !edge.deleted {
             self.insert_edge_to_db(edge)?;
+            self.push_change(Change::EdgeAdded { edge: edge.clone() });
         } else {
-            // Remove from SQLite
-            let kind_str = match edge.kind {
-                EdgeKind::Blocks => "blocks",
-                EdgeKind::ParentChild => "parent_child",
-                EdgeKind::Related => "related",
-            };
-            self.db.execute(
-                "DELETE FROM edges WHERE from_id = ? AND to_id = ? AND kind = ?",
-                params![edge.from_id, edge.to_id, kind_str],
-            )?;
+            self.delete_edge_from_db(edge)?;
+            self.push_change(Change::EdgeRemoved { edge: edge.clone() });
         }
 
-        // Update line count
-        self.db.execute(
-            "UPDATE meta SET value = CAST(CAST(value AS INTEGER) + 1 AS TEXT) WHERE key = 'jsonl_edges_lines'",
-            [],
-        )?;
-
         Ok(())
     }
 
     /// Append an event to the JSONL file.
     pub fn append_event(&mut self, event: &Event) -> Result<()> {
-        let events_path = self.root.join(ENGRAM_DIR).join(EVENTS_FILE);
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&events_path)
-            .context("Failed to open events.jsonl for append")?;
-
-        let json = serde_json::to_string(event).context("Failed to serialize event")?;
-        writeln!(file, "{}", json).context("Failed to write to events.jsonl")?;
-        file.sync_all().context("Failed to sync events.jsonl")?;
+        let json = encode_record(event).context("Failed to serialize event")?;
+        self.append_line(LogFile::Events, &json)?;
 
         // Update SQLite cache
         self.insert_event_to_db(event)?;
+        self.push_change(Change::EventRecorded { event: event.clone() });
 
-        // Update line count
-        self.db.execute(
-            "UPDATE meta SET value = CAST(CAST(value AS INTEGER) + 1 AS TEXT) WHERE key = 'jsonl_events_lines'",
-            [],
-        )?;
+        Ok(())
+    }
+
+    /// Start collecting a `Change` for every append, to be drained with `take_changes`.
+    ///
+    /// Records other processes append are collected too, when a refresh or
+    /// the write lock catches this handle up with them.
+    pub(crate) fn track_changes(&mut self) {
+        self.changes.get_mut().get_or_insert_with(Vec::new);
+    }
+
+    /// Drain the changes collected since the last call.
+    pub(crate) fn take_changes(&mut self) -> Vec<Change> {
+        self.changes.get_mut().as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    /// Record a change if tracking is on.
+    fn push_change(&self, change: Change) {
+        if let Some(changes) = self.changes.borrow_mut().as_mut() {
+            changes.push(change);
+        }
+    }
+
+    /// Record a change for each record other processes appended since this
+    /// handle last looked at the logs, if tracking is on.
+    ///
+    /// `lengths` are the current log lengths. The other process has already
+    /// put its records in the shared cache, so they are read from the logs
+    /// between `seen_lengths` and the end, not from the replay.
+    fn track_external_appends(&self, lengths: [u64; 3]) -> Result<()> {
+        if self.changes.borrow().is_none() {
+            return Ok(());
+        }
+
+        let seen = self.seen_lengths.get();
+        for log in LogFile::ALL {
+            if seen[log.index()] >= lengths[log.index()] {
+                continue;
+            }
+            let from = LogCursor {
+                offset: seen[log.index()],
+                hash: String::new(),
+            };
+            scan_log(self.log_path(log).as_deref(), &from, |line, offset| {
+                let change = match log {
+                    LogFile::Items => replay_record::<Item>(log, line, offset)?.map(external_item_change),
+                    LogFile::Edges => replay_record::<Edge>(log, line, offset)?.map(|edge| {
+                        if edge.deleted {
+                            Change::EdgeRemoved { edge }
+                        } else {
+                            Change::EdgeAdded { edge }
+                        }
+                    }),
+                    LogFile::Events => {
+                        replay_record::<Event>(log, line, offset)?.map(|event| Change::EventRecorded { event })
+                    }
+                };
+                if let Some(change) = change {
+                    self.push_change(change);
+                }
+                Ok(())
+            })?;
+        }
 
         Ok(())
     }
 
     /// Get an event by ID.
     pub fn get_event(&self, id: &str) -> Result<Option<Event>> {
+        self.refresh()?;
         let mut stmt = self.db.prepare(
             r#"
             SELECT id, kind, source_task, target_task, payload, timestamp
@@ -531,6 +1298,8 @@ impl Storage {
 
     /// Query events with filters.
     pub fn query_events(&self, filter: &EventFilter) -> Result<Vec<Event>> {
+        self.refresh()?;
+        let (conditions, params) = event_conditions(filter);
         let mut sql = String::from(
             r#"
             SELECT id, kind, source_task, target_task, payload, timestamp
@@ -538,38 +1307,6 @@ impl Storage {
             "#,
         );
 
-        let mut conditions = Vec::new();
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-
-        // Kind filter
-        if let Some(ref kinds) = filter.kinds
-            && !kinds.is_empty()
-        {
-            let placeholders: Vec<_> = kinds.iter().map(|_| "?").collect();
-            conditions.push(format!("kind IN ({})", placeholders.join(", ")));
-            for kind in kinds {
-                params.push(Box::new(kind.clone()));
-            }
-        }
-
-        // Source task filter
-        if let Some(ref source) = filter.source_task {
-            conditions.push("source_task = ?".to_string());
-            params.push(Box::new(source.clone()));
-        }
-
-        // Target task filter
-        if let Some(ref target) = filter.target_task {
-            conditions.push("target_task = ?".to_string());
-            params.push(Box::new(target.clone()));
-        }
-
-        // Since filter
-        if let Some(since) = filter.since {
-            conditions.push("timestamp >= ?".to_string());
-            params.push(Box::new(since.to_rfc3339()));
-        }
-
         // Build WHERE clause
         if !conditions.is_empty() {
             sql.push_str("WHERE ");
@@ -577,7 +1314,7 @@ impl Storage {
         }
 
         // Order by timestamp descending (most recent first)
-        sql.push_str(" ORDER BY timestamp DESC");
+        sql.push_str(&order_clause(&event_keys()));
 
         // Limit
         if let Some(limit) = filter.limit {
@@ -594,6 +1331,61 @@ impl Storage {
         Ok(events)
     }
 
+    /// One page of `query_events`, most recent first, starting after
+    /// `cursor`.
+    ///
+    /// The page holds up to `filter.limit` events (`DEFAULT_PAGE_SIZE` if
+    /// unset, at most `MAX_PAGE_SIZE`). Events recorded between calls are newer than any page already
+    /// returned, so they never shift later pages.
+    pub fn query_events_page(&self, filter: &EventFilter, cursor: Option<&str>) -> Result<Page<Event>> {
+        self.refresh()?;
+        let keys = event_keys();
+        let (mut conditions, mut params) = event_conditions(filter);
+        if let Some(token) = cursor {
+            let after = PageCursor::decode(token, &keys)?;
+            conditions.push(after_condition(&keys, &after, &mut params));
+        }
+
+        let mut sql = String::from(
+            r#"
+            SELECT id, kind, source_task, target_task, payload, timestamp
+            FROM events
+            "#,
+        );
+        if !conditions.is_empty() {
+            sql.push_str("WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+
+        // One extra row tells whether another page follows
+        let limit = page_size(filter.limit);
+        sql.push_str(&order_clause(&keys));
+        sql.push_str(&format!(" LIMIT {}", limit + 1));
+
+        let mut stmt = self.db.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let mut rows: Vec<(Event, Vec<rusqlite::types::Value>)> = stmt
+            .query_map(param_refs.as_slice(), |row| {
+                // The sort keys are timestamp and id, already selected
+                let values = vec![row.get(5)?, row.get(0)?];
+                Ok((Self::row_to_event(row)?, values))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let next = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|(_, values)| PageCursor::encode(&keys, values))
+        } else {
+            None
+        };
+
+        Ok(Page {
+            results: rows.into_iter().map(|(event, _)| event).collect(),
+            next,
+        })
+    }
+
     /// Get recent events.
     pub fn recent_events(&self, limit: usize) -> Result<Vec<Event>> {
         self.query_events(&EventFilter::new().limit(limit))
@@ -601,6 +1393,7 @@ impl Storage {
 
     /// Get events for a specific task (as source or target).
     pub fn task_events(&self, task_id: &str, limit: usize) -> Result<Vec<Event>> {
+        self.refresh()?;
         let mut stmt = self.db.prepare(
             r#"
             SELECT id, kind, source_task, target_task, payload, timestamp
@@ -621,6 +1414,7 @@ impl Storage {
 
     /// Count all events in the store.
     pub fn count_all_events(&self) -> Result<usize> {
+        self.refresh()?;
         let count: i64 = self.db.query_row("SELECT COUNT(*) FROM events", [], |row| row.get(0))?;
         Ok(count as usize)
     }
@@ -646,50 +1440,16 @@ impl Storage {
 
     /// Get an item by ID.
     pub fn get_item(&self, id: &str) -> Result<Option<Item>> {
+        self.refresh()?;
         let mut stmt = self.db.prepare(
             r#"
-            SELECT id, title, description, status, priority, created_at, updated_at, closed_at, close_reason
+            SELECT id, title, description, status, priority, created_at, updated_at, closed_at, close_reason,
+                   assignee, lease_expires_at
             FROM items WHERE id = ?
             "#,
         )?;
 
-        let item = stmt
-            .query_row(params![id], |row| {
-                let status_str: String = row.get(3)?;
-                let status = match status_str.as_str() {
-                    "open" => Status::Open,
-                    "in_progress" => Status::InProgress,
-                    "blocked" => Status::Blocked,
-                    "closed" => Status::Closed,
-                    _ => Status::Open,
-                };
-
-                let created_at_str: String = row.get(5)?;
-                let updated_at_str: String = row.get(6)?;
-                let closed_at_str: Option<String> = row.get(7)?;
-
-                Ok(Item {
-                    id: row.get(0)?,
-                    title: row.get(1)?,
-                    description: row.get(2)?,
-                    status,
-                    priority: row.get(4)?,
-                    labels: vec![], // Will be filled below
-                    created_at: chrono::DateTime::parse_from_rfc3339(&created_at_str)
-                        .map(|dt| dt.with_timezone(&chrono::Utc))
-                        .unwrap_or_else(|_| chrono::Utc::now()),
-                    updated_at: chrono::DateTime::parse_from_rfc3339(&updated_at_str)
-                        .map(|dt| dt.with_timezone(&chrono::Utc))
-                        .unwrap_or_else(|_| chrono::Utc::now()),
-                    closed_at: closed_at_str.and_then(|s| {
-                        chrono::DateTime::parse_from_rfc3339(&s)
-                            .map(|dt| dt.with_timezone(&chrono::Utc))
-                            .ok()
-                    }),
-                    close_reason: row.get(8)?,
-                })
-            })
-            .optional()?;
+        let item = stmt.query_row(params![id], Self::row_to_item).optional()?;
 
         // Load labels if item exists
         if let Some(mut item) = item {
@@ -709,17 +1469,20 @@ impl Storage {
 
     /// List all items with optional status filter.
     pub fn list_items(&self, status_filter: Option<Status>) -> Result<Vec<Item>> {
+        self.refresh()?;
         let sql = match status_filter {
             Some(_) => {
                 r#"
-                SELECT id, title, description, status, priority, created_at, updated_at, closed_at, close_reason
+                SELECT id, title, description, status, priority, created_at, updated_at, closed_at, close_reason,
+                       assignee, lease_expires_at
                 FROM items WHERE status = ?
                 ORDER BY priority ASC, created_at ASC
                 "#
             }
             None => {
                 r#"
-                SELECT id, title, description, status, priority, created_at, updated_at, closed_at, close_reason
+                SELECT id, title, description, status, priority, created_at, updated_at, closed_at, close_reason,
+                       assignee, lease_expires_at
                 FROM items
                 ORDER BY priority ASC, created_at ASC
                 "#
@@ -758,28 +1521,30 @@ impl Storage {
 
     /// Get items that are ready to work on (open, not blocked).
     pub fn ready(&self) -> Result<Vec<Item>> {
-        let sql = r#"
-            SELECT i.id, i.title, i.description, i.status, i.priority, i.created_at, i.updated_at, i.closed_at, i.close_reason
+        self.refresh()?;
+        let sql = format!(
+            r#"
+            SELECT i.id, i.title, i.description, i.status, i.priority, i.created_at, i.updated_at, i.closed_at, i.close_reason,
+                   i.assignee, i.lease_expires_at
             FROM items i
             WHERE i.status = 'open'
             AND NOT EXISTS (
                 SELECT 1 FROM edges e
                 JOIN items blocker ON e.to_id = blocker.id
                 WHERE e.from_id = i.id
-                AND e.kind = 'blocks'
-                AND blocker.status IN ('open', 'in_progress', 'blocked')
+                AND {OPEN_BLOCKER}
             )
             AND NOT EXISTS (
                 SELECT 1 FROM edges e
                 JOIN items child ON e.from_id = child.id
                 WHERE e.to_id = i.id
-                AND e.kind = 'parent_child'
-                AND child.status IN ('open', 'in_progress', 'blocked')
+                AND {OPEN_CHILD}
             )
             ORDER BY i.priority ASC, i.created_at ASC
-        "#;
+            "#
+        );
 
-        let mut stmt = self.db.prepare(sql)?;
+        let mut stmt = self.db.prepare(&sql)?;
         let mut items: Vec<Item> = stmt.query_map([], Self::row_to_item)?.filter_map(|r| r.ok()).collect();
 
         // Load labels for each item
@@ -799,22 +1564,24 @@ impl Storage {
     /// Get items that are currently blocked.
     /// Returns items that have at least one open blocker.
     pub fn blocked(&self) -> Result<Vec<Item>> {
+        self.refresh()?;
         // Items that:
         // 1. Are open or in_progress (not closed)
         // 2. Have at least one blocking edge where the blocker is open/in_progress
-        let sql = r#"
+        let sql = format!(
+            r#"
             SELECT DISTINCT i.id, i.title, i.description, i.status, i.priority,
-                   i.created_at, i.updated_at, i.closed_at, i.close_reason
+                   i.created_at, i.updated_at, i.closed_at, i.close_reason, i.assignee, i.lease_expires_at
             FROM items i
             JOIN edges e ON e.from_id = i.id
             JOIN items blocker ON e.to_id = blocker.id
             WHERE i.status IN ('open', 'in_progress', 'blocked')
-            AND e.kind = 'blocks'
-            AND blocker.status IN ('open', 'in_progress', 'blocked')
+            AND {OPEN_BLOCKER}
             ORDER BY i.priority ASC, i.created_at ASC
-        "#;
+            "#
+        );
 
-        let mut stmt = self.db.prepare(sql)?;
+        let mut stmt = self.db.prepare(&sql)?;
         let mut items: Vec<Item> = stmt.query_map([], Self::row_to_item)?.filter_map(|r| r.ok()).collect();
 
         // Load labels for each item
@@ -833,6 +1600,7 @@ impl Storage {
 
     /// Check if an edge exists.
     pub fn edge_exists(&self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<bool> {
+        self.refresh()?;
         let kind_str = match kind {
             EdgeKind::Blocks => "blocks",
             EdgeKind::ParentChild => "parent_child",
@@ -848,27 +1616,237 @@ impl Storage {
         Ok(count > 0)
     }
 
-    /// Get blocking edges from an item.
-    pub fn get_blocking_edges_from(&self, from_id: &str) -> Result<Vec<Edge>> {
-        let mut stmt = self.db.prepare(
+    /// IDs of the unclosed items that `id` has a `Blocks` edge to.
+    pub fn open_blockers(&self, id: &str) -> Result<Vec<String>> {
+        self.refresh()?;
+        let mut stmt = self.db.prepare(&format!(
             r#"
-            SELECT from_id, to_id, kind, created_at
-            FROM edges
-            WHERE from_id = ? AND kind IN ('blocks', 'parent_child')
-            "#,
-        )?;
+            SELECT e.to_id
+            FROM edges e
+            JOIN items blocker ON e.to_id = blocker.id
+            WHERE e.from_id = ? AND {OPEN_BLOCKER}
+            ORDER BY e.to_id
+            "#
+        ))?;
 
-        let edges: Vec<Edge> = stmt
-            .query_map(params![from_id], |row| {
-                let kind_str: String = row.get(2)?;
-                let kind = match kind_str.as_str() {
-                    "blocks" => EdgeKind::Blocks,
-                    "parent_child" => EdgeKind::ParentChild,
-                    _ => EdgeKind::Related,
-                };
-                let created_at_str: String = row.get(3)?;
+        let ids = stmt
+            .query_map(params![id], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
 
-                Ok(Edge {
+        Ok(ids)
+    }
+
+    /// Items that keep `id` out of `ready()`: its unfinished blockers (via
+    /// `Blocks`) and its unfinished children (via `ParentChild`).
+    pub fn holders(&self, id: &str) -> Result<Vec<(EdgeKind, Item)>> {
+        self.refresh()?;
+        let sql = format!(
+            r#"
+            SELECT blocker.id, blocker.title, blocker.description, blocker.status, blocker.priority,
+                   blocker.created_at, blocker.updated_at, blocker.closed_at, blocker.close_reason,
+                   blocker.assignee, blocker.lease_expires_at, e.kind
+            FROM edges e
+            JOIN items blocker ON e.to_id = blocker.id
+            WHERE e.from_id = ?1 AND {OPEN_BLOCKER}
+            UNION ALL
+            SELECT child.id, child.title, child.description, child.status, child.priority,
+                   child.created_at, child.updated_at, child.closed_at, child.close_reason,
+                   child.assignee, child.lease_expires_at, e.kind
+            FROM edges e
+            JOIN items child ON e.from_id = child.id
+            WHERE e.to_id = ?1 AND {OPEN_CHILD}
+            ORDER BY 12 ASC, 5 ASC, 6 ASC
+            "#
+        );
+
+        let mut stmt = self.db.prepare(&sql)?;
+        let mut holders: Vec<(EdgeKind, Item)> = stmt
+            .query_map(params![id], |row| {
+                let kind: String = row.get(11)?;
+                let kind = if kind == "blocks" {
+                    EdgeKind::Blocks
+                } else {
+                    EdgeKind::ParentChild
+                };
+                Ok((kind, Self::row_to_item(row)?))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Load labels for each item
+        for (_, item) in &mut holders {
+            let mut label_stmt = self
+                .db
+                .prepare("SELECT label FROM labels WHERE item_id = ? ORDER BY label")?;
+            item.labels = label_stmt
+                .query_map(params![item.id], |row| row.get(0))?
+                .filter_map(|r| r.ok())
+                .collect();
+        }
+
+        Ok(holders)
+    }
+
+    /// IDs of the items with a `kind` edge pointing at `to_id`.
+    pub fn edge_sources(&self, to_id: &str, kind: EdgeKind) -> Result<Vec<String>> {
+        self.refresh()?;
+        let kind_str = match kind {
+            EdgeKind::Blocks => "blocks",
+            EdgeKind::ParentChild => "parent_child",
+            EdgeKind::Related => "related",
+        };
+
+        let mut stmt = self
+            .db
+            .prepare("SELECT from_id FROM edges WHERE to_id = ? AND kind = ? ORDER BY from_id")?;
+
+        let ids = stmt
+            .query_map(params![to_id, kind_str], |row| row.get(0))?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// Items reachable from `id` over edges of the given kinds (any kind if
+    /// empty), each with the length of the shortest path to it.
+    ///
+    /// Follows edges from `from_id` to `to_id`, or the other way round if
+    /// `incoming`. Walks at most `max_depth` steps, or the whole graph if None.
+    /// The start item itself is not included.
+    pub fn reachable(
+        &self,
+        id: &str,
+        kinds: &[EdgeKind],
+        max_depth: Option<usize>,
+        incoming: bool,
+    ) -> Result<Vec<(Item, usize)>> {
+        self.refresh()?;
+
+        // A shortest path never revisits an item, so the item count bounds the
+        // depth even when `related` edges form cycles
+        let max_depth = match max_depth {
+            Some(depth) => depth as i64,
+            None => self.db.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))?,
+        };
+        let (near, far) = if incoming {
+            ("to_id", "from_id")
+        } else {
+            ("from_id", "to_id")
+        };
+        let kind_clause = if kinds.is_empty() {
+            String::new()
+        } else {
+            let names: Vec<String> = kinds
+                .iter()
+                .map(|kind| match kind {
+                    EdgeKind::Blocks => "'blocks'".to_string(),
+                    EdgeKind::ParentChild => "'parent_child'".to_string(),
+                    EdgeKind::Related => "'related'".to_string(),
+                })
+                .collect();
+            format!("AND e.kind IN ({})", names.join(", "))
+        };
+
+        let sql = format!(
+            r#"
+            WITH RECURSIVE reach(id, depth) AS (
+                SELECT ?1, 0
+                UNION
+                SELECT e.{far}, r.depth + 1
+                FROM edges e
+                JOIN reach r ON e.{near} = r.id
+                WHERE r.depth < ?2 {kind_clause}
+            )
+            SELECT i.id, i.title, i.description, i.status, i.priority, i.created_at, i.updated_at, i.closed_at,
+                   i.close_reason, i.assignee, i.lease_expires_at, MIN(r.depth) AS depth
+            FROM reach r
+            JOIN items i ON i.id = r.id
+            WHERE r.id != ?1
+            GROUP BY i.id
+            ORDER BY depth ASC, i.priority ASC, i.created_at ASC
+            "#
+        );
+
+        let mut stmt = self.db.prepare(&sql)?;
+        let mut found: Vec<(Item, usize)> = stmt
+            .query_map(params![id, max_depth], |row| {
+                let depth: i64 = row.get(11)?;
+                Ok((Self::row_to_item(row)?, depth as usize))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Load labels for each item
+        for (item, _) in &mut found {
+            let mut label_stmt = self
+                .db
+                .prepare("SELECT label FROM labels WHERE item_id = ? ORDER BY label")?;
+            item.labels = label_stmt
+                .query_map(params![item.id], |row| row.get(0))?
+                .filter_map(|r| r.ok())
+                .collect();
+        }
+
+        Ok(found)
+    }
+
+    /// List all edges.
+    pub fn list_edges(&self) -> Result<Vec<Edge>> {
+        self.refresh()?;
+        let mut stmt = self
+            .db
+            .prepare("SELECT from_id, to_id, kind, created_at FROM edges ORDER BY created_at ASC")?;
+
+        let edges: Vec<Edge> = stmt
+            .query_map([], |row| {
+                let kind_str: String = row.get(2)?;
+                let kind = match kind_str.as_str() {
+                    "blocks" => EdgeKind::Blocks,
+                    "parent_child" => EdgeKind::ParentChild,
+                    _ => EdgeKind::Related,
+                };
+                let created_at_str: String = row.get(3)?;
+
+                Ok(Edge {
+                    from_id: row.get(0)?,
+                    to_id: row.get(1)?,
+                    kind,
+                    created_at: chrono::DateTime::parse_from_rfc3339(&created_at_str)
+                        .map(|dt| dt.with_timezone(&chrono::Utc))
+                        .unwrap_or_else(|_| chrono::Utc::now()),
+                    deleted: false,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        Ok(edges)
+    }
+
+    /// Get blocking edges from an item.
+    pub fn get_blocking_edges_from(&self, from_id: &str) -> Result<Vec<Edge>> {
+        self.refresh()?;
+        let mut stmt = self.db.prepare(
+            r#"
+            SELECT from_id, to_id, kind, created_at
+            FROM edges
+            WHERE from_id = ? AND kind IN ('blocks', 'parent_child')
+            "#,
+        )?;
+
+        let edges: Vec<Edge> = stmt
+            .query_map(params![from_id], |row| {
+                let kind_str: String = row.get(2)?;
+                let kind = match kind_str.as_str() {
+                    "blocks" => EdgeKind::Blocks,
+                    "parent_child" => EdgeKind::ParentChild,
+                    _ => EdgeKind::Related,
+                };
+                let created_at_str: String = row.get(3)?;
+
+                Ok(Edge {
                     from_id: row.get(0)?,
                     to_id: row.get(1)?,
                     kind,
@@ -886,231 +1864,965 @@ impl Storage {
 
     /// Query items with flexible filtering.
     pub fn query_items(&self, filter: &crate::types::Filter) -> Result<Vec<Item>> {
+        self.refresh()?;
+        let (conditions, params) = filter_conditions(filter);
         let mut sql = String::from(
             r#"
-            SELECT DISTINCT i.id, i.title, i.description, i.status, i.priority,
-                   i.created_at, i.updated_at, i.closed_at, i.close_reason
+            SELECT i.id, i.title, i.description, i.status, i.priority,
+                   i.created_at, i.updated_at, i.closed_at, i.close_reason, i.assignee, i.lease_expires_at
             FROM items i
             "#,
         );
 
-        let mut conditions = Vec::new();
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if !conditions.is_empty() {
+            sql.push_str("WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+
+        // Order by priority and created_at unless the filter says otherwise
+        sql.push_str(&order_clause(&sort_keys(filter, ITEM_ORDER)));
+        sql.push_str(&limit_clause(filter));
+
+        let mut stmt = self.db.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let mut items: Vec<Item> = stmt
+            .query_map(param_refs.as_slice(), Self::row_to_item)?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Load labels for each item
+        for item in &mut items {
+            let mut label_stmt = self
+                .db
+                .prepare("SELECT label FROM labels WHERE item_id = ? ORDER BY label")?;
+            item.labels = label_stmt
+                .query_map(params![item.id], |row| row.get(0))?
+                .filter_map(|r| r.ok())
+                .collect();
+        }
+
+        Ok(items)
+    }
+
+    /// One page of `query_items`, starting after `cursor`.
+    ///
+    /// The page holds up to `filter.limit` items (`DEFAULT_PAGE_SIZE` if
+    /// unset, at most `MAX_PAGE_SIZE`); `filter.offset` is ignored. Pass the returned `next` cursor
+    /// back with the same filter to continue. Paging by sort key rather than
+    /// offset means items created between calls never shift later pages.
+    pub fn query_items_page(&self, filter: &crate::types::Filter, cursor: Option<&str>) -> Result<Page<Item>> {
+        self.refresh()?;
+        let keys = sort_keys(filter, ITEM_ORDER);
+        let (mut conditions, mut params) = filter_conditions(filter);
+        if let Some(token) = cursor {
+            let after = PageCursor::decode(token, &keys)?;
+            conditions.push(after_condition(&keys, &after, &mut params));
+        }
+
+        let key_columns: Vec<&str> = keys.iter().map(|(sql, _)| sql.as_str()).collect();
+        let mut sql = format!(
+            r#"
+            SELECT i.id, i.title, i.description, i.status, i.priority,
+                   i.created_at, i.updated_at, i.closed_at, i.close_reason, i.assignee, i.lease_expires_at,
+                   {}
+            FROM items i
+            "#,
+            key_columns.join(", ")
+        );
+
+        if !conditions.is_empty() {
+            sql.push_str("WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+
+        // One extra row tells whether another page follows
+        let limit = page_size(filter.limit);
+        sql.push_str(&order_clause(&keys));
+        sql.push_str(&format!(" LIMIT {}", limit + 1));
+
+        let mut stmt = self.db.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let mut rows: Vec<(Item, Vec<rusqlite::types::Value>)> = stmt
+            .query_map(param_refs.as_slice(), |row| {
+                let values = (0..keys.len())
+                    .map(|k| row.get(11 + k))
+                    .collect::<rusqlite::Result<_>>()?;
+                Ok((Self::row_to_item(row)?, values))
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        let next = if rows.len() > limit {
+            rows.truncate(limit);
+            rows.last().map(|(_, values)| PageCursor::encode(&keys, values))
+        } else {
+            None
+        };
+        let mut items: Vec<Item> = rows.into_iter().map(|(item, _)| item).collect();
+
+        // Load labels for each item
+        for item in &mut items {
+            let mut label_stmt = self
+                .db
+                .prepare("SELECT label FROM labels WHERE item_id = ? ORDER BY label")?;
+            item.labels = label_stmt
+                .query_map(params![item.id], |row| row.get(0))?
+                .filter_map(|r| r.ok())
+                .collect();
+        }
+
+        Ok(Page { results: items, next })
+    }
+
+    /// Full-text search over titles and descriptions, best matches first.
+    ///
+    /// Every word in `text` must appear; words are matched after stemming, so
+    /// "blocking" finds "blocked". `filter` narrows the results as in
+    /// `query_items`; its sort keys, if any, take precedence over ranking.
+    /// Matches in the snippet are wrapped in `**`.
+    pub fn search_items(&self, text: &str, filter: &crate::types::Filter) -> Result<Vec<SearchHit>> {
+        self.refresh()?;
+        let (conditions, filter_params) = filter_conditions(filter);
+
+        // Quote each word so user text never parses as FTS5 query syntax
+        let terms: Vec<String> = text
+            .split_whitespace()
+            .map(|word| format!("\"{}\"", word.replace('"', "\"\"")))
+            .collect();
+        if terms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut sql = String::from(
+            r#"
+            SELECT i.id, i.title, i.description, i.status, i.priority,
+                   i.created_at, i.updated_at, i.closed_at, i.close_reason, i.assignee, i.lease_expires_at,
+                   bm25(items_fts) AS rank, snippet(items_fts, -1, '**', '**', '...', 12)
+            FROM items_fts
+            JOIN items i ON i.rowid = items_fts.rowid
+            WHERE items_fts MATCH ?
+            "#,
+        );
+        for condition in &conditions {
+            sql.push_str(" AND ");
+            sql.push_str(condition);
+        }
+        let ranked = [("rank", SortDirection::Asc), ITEM_ORDER[0], ITEM_ORDER[1]];
+        sql.push_str(&order_clause(&sort_keys(filter, &ranked)));
+        sql.push_str(&limit_clause(filter));
+
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(terms.join(" "))];
+        params.extend(filter_params);
+
+        let mut stmt = self.db.prepare(&sql)?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let mut hits: Vec<SearchHit> = stmt
+            .query_map(param_refs.as_slice(), |row| {
+                Ok(SearchHit {
+                    item: Self::row_to_item(row)?,
+                    rank: row.get(11)?,
+                    snippet: row.get(12)?,
+                })
+            })?
+            .filter_map(|r| r.ok())
+            .collect();
+
+        // Load labels for each item
+        for hit in &mut hits {
+            let mut label_stmt = self
+                .db
+                .prepare("SELECT label FROM labels WHERE item_id = ? ORDER BY label")?;
+            hit.item.labels = label_stmt
+                .query_map(params![hit.item.id], |row| row.get(0))?
+                .filter_map(|r| r.ok())
+                .collect();
+        }
+
+        Ok(hits)
+    }
+
+    /// Run SQLite VACUUM to reclaim space.
+    pub fn vacuum(&self) -> Result<()> {
+        self.db.execute("VACUUM", [])?;
+        Ok(())
+    }
+
+    /// Count all items in the store.
+    pub fn count_all_items(&self) -> Result<usize> {
+        self.refresh()?;
+        let count: i64 = self.db.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Count all edges in the store.
+    pub fn count_all_edges(&self) -> Result<usize> {
+        self.refresh()?;
+        let count: i64 = self.db.query_row("SELECT COUNT(*) FROM edges", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    /// Count items matching the filter.
+    pub fn count_items(&self, filter: &crate::types::Filter) -> Result<usize> {
+        self.refresh()?;
+        let (conditions, params) = filter_conditions(filter);
+        let mut sql = String::from("SELECT COUNT(*) FROM items i ");
+
+        if !conditions.is_empty() {
+            sql.push_str("WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let count: i64 = self.db.query_row(&sql, param_refs.as_slice(), |row| row.get(0))?;
+
+        Ok(count as usize)
+    }
+
+    /// Convert a database row to an Item.
+    fn row_to_item(row: &rusqlite::Row) -> rusqlite::Result<Item> {
+        let status_str: String = row.get(3)?;
+        let status = match status_str.as_str() {
+            "open" => Status::Open,
+            "in_progress" => Status::InProgress,
+            "blocked" => Status::Blocked,
+            "closed" => Status::Closed,
+            _ => Status::Open,
+        };
+
+        let created_at_str: String = row.get(5)?;
+        let updated_at_str: String = row.get(6)?;
+        let closed_at_str: Option<String> = row.get(7)?;
+
+        Ok(Item {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            description: row.get(2)?,
+            status,
+            priority: row.get(4)?,
+            labels: vec![],
+            created_at: chrono::DateTime::parse_from_rfc3339(&created_at_str)
+                .map(|dt| dt.with_timezone(&chrono::Utc))
+                .unwrap_or_else(|_| chrono::Utc::now()),
+            updated_at: chrono::DateTime::parse_from_rfc3339(&updated_at_str)
+                .map(|dt| dt.with_timezone(&chrono::Utc))
+                .unwrap_or_else(|_| chrono::Utc::now()),
+            closed_at: closed_at_str.and_then(|s| {
+                chrono::DateTime::parse_from_rfc3339(&s)
+                    .map(|dt| dt.with_timezone(&chrono::Utc))
+                    .ok()
+            }),
+            close_reason: row.get(8)?,
+            assignee: row.get(9)?,
+            lease_expires_at: row.get::<_, Option<String>>(10)?.and_then(|s| {
+                chrono::DateTime::parse_from_rfc3339(&s)
+                    .map(|dt| dt.with_timezone(&chrono::Utc))
+                    .ok()
+            }),
+        })
+    }
+}
+
+impl Drop for Storage {
+    fn drop(&mut self) {
+        // A transaction still open here was never committed
+        if let Err(e) = self.rollback_transaction() {
+            log::error!("Failed to roll back open transaction: {}", e);
+        }
+        if let Err(e) = self.flush() {
+            log::error!("Failed to flush buffered records: {}", e);
+        }
+    }
+}
+
+/// A JSONL log file mirrored into the SQLite cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFile {
+    Items,
+    Edges,
+    Events,
+}
+
+impl LogFile {
+    /// All log files, in replay order.
+    const ALL: [LogFile; 3] = [LogFile::Items, LogFile::Edges, LogFile::Events];
+
+    /// File name within the .engram directory.
+    fn file_name(self) -> &'static str {
+        match self {
+            LogFile::Items => ITEMS_FILE,
+            LogFile::Edges => EDGES_FILE,
+            LogFile::Events => EVENTS_FILE,
+        }
+    }
+
+    /// Prefix for this file's keys in the `meta` table.
+    fn meta_prefix(self) -> &'static str {
+        match self {
+            LogFile::Items => "jsonl_items",
+            LogFile::Edges => "jsonl_edges",
+            LogFile::Events => "jsonl_events",
+        }
+    }
+
+    /// Position in `LogFile::ALL`.
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How much of a JSONL file has been applied to the cache.
+///
+/// `hash` chains SHA-256 over every complete line before `offset`, so the
+/// prefix can be verified without storing a copy of it and extended one
+/// line at a time on append.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct LogCursor {
+    /// Byte offset just past the last applied line.
+    offset: u64,
+    /// Chained hash of all lines before `offset` (empty for an empty prefix).
+    hash: String,
+}
+
+impl LogCursor {
+    /// Advance past one complete line (including its trailing newline).
+    fn advance(&mut self, line: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(self.hash.as_bytes());
+        hasher.update(line);
+        self.hash = format!("{:x}", hasher.finalize());
+        self.offset += line.len() as u64;
+    }
+}
+
+/// Append complete lines to a JSONL file with one write and one fsync.
+///
+/// On failure the file is cut back to its previous length, so a torn or
+/// unsynced write never leaves lines behind to be appended again.
+fn write_lines(path: &Path, log: LogFile, lines: &[String]) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {} for append", log.file_name()))?;
+    let len = file
+        .metadata()
+        .with_context(|| format!("Failed to stat {}", log.file_name()))?
+        .len();
+
+    let result = file
+        .write_all(lines.concat().as_bytes())
+        .with_context(|| format!("Failed to write to {}", log.file_name()))
+        .and_then(|()| {
+            file.sync_all()
+                .with_context(|| format!("Failed to sync {}", log.file_name()))
+        });
+    if result.is_err()
+        && let Err(e) = truncate_log(path, log, len)
+    {
+        log::error!("{}", e);
+    }
+    result
+}
+
+/// Cut a JSONL file back to `len` bytes and sync it.
+fn truncate_log(path: &Path, log: LogFile, len: u64) -> Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {} to undo a write", log.file_name()))?;
+    file.set_len(len)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("Failed to undo a write to {}", log.file_name()))
+}
+
+/// Length of a file in bytes (0 if it does not exist).
+fn file_len(path: &Path) -> Result<u64> {
+    match fs::metadata(path) {
+        Ok(meta) => Ok(meta.len()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e).with_context(|| format!("Failed to stat {}", path.display())),
+    }
+}
+
+/// SQL conditions on items `i` for the criteria in `filter` (other than
+/// limit and offset), with their parameters.
+fn filter_conditions(filter: &crate::types::Filter) -> (Vec<String>, Vec<Box<dyn rusqlite::ToSql>>) {
+    let mut conditions = Vec::new();
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    // Status filter
+    if let Some(ref status) = filter.status {
+        let status_str = match status {
+            Status::Open => "open",
+            Status::InProgress => "in_progress",
+            Status::Blocked => "blocked",
+            Status::Closed => "closed",
+        };
+        conditions.push("i.status = ?".to_string());
+        params.push(Box::new(status_str.to_string()));
+    }
 
-        // Join labels table if filtering by label
-        if filter.labels.is_some() {
-            sql.push_str("JOIN labels l ON i.id = l.item_id ");
+    // Status set (any match)
+    if let Some(statuses) = filter.statuses.as_ref().filter(|s| !s.is_empty()) {
+        let placeholders: Vec<_> = statuses.iter().map(|_| "?").collect();
+        conditions.push(format!("i.status IN ({})", placeholders.join(", ")));
+        for status in statuses {
+            let status_str = match status {
+                Status::Open => "open",
+                Status::InProgress => "in_progress",
+                Status::Blocked => "blocked",
+                Status::Closed => "closed",
+            };
+            params.push(Box::new(status_str.to_string()));
+        }
+    }
+
+    // Label filter (any match)
+    if let Some(labels) = filter.labels.as_ref().filter(|l| !l.is_empty()) {
+        let placeholders: Vec<_> = labels.iter().map(|_| "?").collect();
+        conditions.push(format!(
+            "EXISTS (SELECT 1 FROM labels l WHERE l.item_id = i.id AND l.label IN ({}))",
+            placeholders.join(", ")
+        ));
+        for label in labels {
+            params.push(Box::new(label.clone()));
+        }
+    }
+
+    // Label filter (all match)
+    for label in filter.all_labels.iter().flatten() {
+        conditions.push("EXISTS (SELECT 1 FROM labels l WHERE l.item_id = i.id AND l.label = ?)".to_string());
+        params.push(Box::new(label.clone()));
+    }
+
+    // Label filter (none match)
+    if let Some(labels) = filter.without_labels.as_ref().filter(|l| !l.is_empty()) {
+        let placeholders: Vec<_> = labels.iter().map(|_| "?").collect();
+        conditions.push(format!(
+            "NOT EXISTS (SELECT 1 FROM labels l WHERE l.item_id = i.id AND l.label IN ({}))",
+            placeholders.join(", ")
+        ));
+        for label in labels {
+            params.push(Box::new(label.clone()));
         }
+    }
+
+    // Priority filters
+    if let Some(min_p) = filter.min_priority {
+        conditions.push("i.priority >= ?".to_string());
+        params.push(Box::new(min_p as i64));
+    }
+    if let Some(max_p) = filter.max_priority {
+        conditions.push("i.priority <= ?".to_string());
+        params.push(Box::new(max_p as i64));
+    }
+
+    // Title filter
+    if let Some(ref title_sub) = filter.title_contains {
+        conditions.push("LOWER(i.title) LIKE ?".to_string());
+        params.push(Box::new(format!("%{}%", title_sub.to_lowercase())));
+    }
+
+    // Description and close reason filters
+    if let Some(ref sub) = filter.description_contains {
+        conditions.push("LOWER(i.description) LIKE ?".to_string());
+        params.push(Box::new(format!("%{}%", sub.to_lowercase())));
+    }
+    if let Some(ref sub) = filter.close_reason_contains {
+        conditions.push("LOWER(i.close_reason) LIKE ?".to_string());
+        params.push(Box::new(format!("%{}%", sub.to_lowercase())));
+    }
 
-        // Status filter
-        if let Some(ref status) = filter.status {
+    // Time ranges; NULL closed_at never matches
+    let ranges = [
+        ("i.created_at >= ?", filter.created_after),
+        ("i.created_at < ?", filter.created_before),
+        ("i.updated_at >= ?", filter.updated_after),
+        ("i.updated_at < ?", filter.updated_before),
+        ("i.closed_at >= ?", filter.closed_after),
+        ("i.closed_at < ?", filter.closed_before),
+    ];
+    for (condition, time) in ranges {
+        if let Some(time) = time {
+            conditions.push(condition.to_string());
+            params.push(Box::new(time.to_rfc3339()));
+        }
+    }
+
+    if let Some(ref predicate) = filter.predicate {
+        conditions.push(predicate_sql(predicate, &mut params));
+    }
+
+    (conditions, params)
+}
+
+/// SQL condition on items `i` for `predicate`, pushing its parameters.
+fn predicate_sql(predicate: &Predicate, params: &mut Vec<Box<dyn rusqlite::ToSql>>) -> String {
+    let mut join = |all: &[Predicate], op: &str, empty: &str| {
+        if all.is_empty() {
+            return empty.to_string();
+        }
+        let parts: Vec<String> = all.iter().map(|p| predicate_sql(p, params)).collect();
+        format!("({})", parts.join(op))
+    };
+
+    match predicate {
+        Predicate::And(all) => join(all, " AND ", "1"),
+        Predicate::Or(any) => join(any, " OR ", "0"),
+        Predicate::Not(inner) => format!("NOT ({})", predicate_sql(inner, params)),
+        Predicate::Status(status) => {
             let status_str = match status {
                 Status::Open => "open",
                 Status::InProgress => "in_progress",
                 Status::Blocked => "blocked",
                 Status::Closed => "closed",
             };
-            conditions.push("i.status = ?".to_string());
             params.push(Box::new(status_str.to_string()));
+            "i.status = ?".to_string()
+        }
+        Predicate::Label(label) => {
+            params.push(Box::new(label.clone()));
+            "EXISTS (SELECT 1 FROM labels l WHERE l.item_id = i.id AND l.label = ?)".to_string()
+        }
+        Predicate::Assignee(agent) => {
+            params.push(Box::new(agent.clone()));
+            "i.assignee IS ?".to_string()
+        }
+        Predicate::Title(text) => {
+            params.push(Box::new(format!("%{}%", text.to_lowercase())));
+            "LOWER(i.title) LIKE ?".to_string()
+        }
+        Predicate::Text(text) => {
+            let pattern = format!("%{}%", text.to_lowercase());
+            params.push(Box::new(pattern.clone()));
+            params.push(Box::new(pattern));
+            "(LOWER(i.title) LIKE ? OR LOWER(COALESCE(i.description, '')) LIKE ?)".to_string()
+        }
+        Predicate::Priority { cmp, value } => {
+            params.push(Box::new(*value as i64));
+            format!("i.priority {} ?", cmp.sql())
+        }
+        Predicate::Created { cmp, at } => {
+            params.push(Box::new(at.to_rfc3339()));
+            format!("i.created_at {} ?", cmp.sql())
+        }
+        Predicate::Updated { cmp, at } => {
+            params.push(Box::new(at.to_rfc3339()));
+            format!("i.updated_at {} ?", cmp.sql())
+        }
+        Predicate::Closed { cmp, at } => {
+            // Without the NULL check, NOT would drop open items too
+            params.push(Box::new(at.to_rfc3339()));
+            format!("(i.closed_at IS NOT NULL AND i.closed_at {} ?)", cmp.sql())
+        }
+    }
+}
+
+/// SQL conditions on `events` for `filter` (other than limit), with their
+/// parameters.
+fn event_conditions(filter: &EventFilter) -> (Vec<String>, Vec<Box<dyn rusqlite::ToSql>>) {
+    let mut conditions = Vec::new();
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    // Kind filter
+    if let Some(ref kinds) = filter.kinds
+        && !kinds.is_empty()
+    {
+        let placeholders: Vec<_> = kinds.iter().map(|_| "?").collect();
+        conditions.push(format!("kind IN ({})", placeholders.join(", ")));
+        for kind in kinds {
+            params.push(Box::new(kind.clone()));
+        }
+    }
+
+    // Source task filter
+    if let Some(ref source) = filter.source_task {
+        conditions.push("source_task = ?".to_string());
+        params.push(Box::new(source.clone()));
+    }
+
+    // Target task filter
+    if let Some(ref target) = filter.target_task {
+        conditions.push("target_task = ?".to_string());
+        params.push(Box::new(target.clone()));
+    }
+
+    // Since filter
+    if let Some(since) = filter.since {
+        conditions.push("timestamp >= ?".to_string());
+        params.push(Box::new(since.to_rfc3339()));
+    }
+
+    (conditions, params)
+}
+
+/// A sort key: an SQL expression and its direction.
+type SortKey = (String, SortDirection);
+
+/// Default item order: priority, then creation time.
+const ITEM_ORDER: &[(&str, SortDirection)] =
+    &[("i.priority", SortDirection::Asc), ("i.created_at", SortDirection::Asc)];
+
+/// Event order: most recent first, with the ID breaking ties.
+fn event_keys() -> Vec<SortKey> {
+    vec![
+        ("timestamp".to_string(), SortDirection::Desc),
+        ("id".to_string(), SortDirection::Desc),
+    ]
+}
+
+/// Sort keys for `filter`: its explicit keys, then `default`, then the item
+/// ID so that no two rows tie.
+fn sort_keys(filter: &crate::types::Filter, default: &[(&str, SortDirection)]) -> Vec<SortKey> {
+    filter
+        .order_by
+        .iter()
+        .map(|order| (order.column.sql().to_string(), order.direction))
+        .chain(default.iter().map(|(sql, direction)| (sql.to_string(), *direction)))
+        .chain([("i.id".to_string(), SortDirection::Asc)])
+        .collect()
+}
+
+/// `ORDER BY` clause for `keys`.
+fn order_clause(keys: &[SortKey]) -> String {
+    let keys: Vec<String> = keys
+        .iter()
+        .map(|(sql, direction)| match direction {
+            SortDirection::Asc => format!("{} ASC", sql),
+            SortDirection::Desc => format!("{} DESC", sql),
+        })
+        .collect();
+    format!(" ORDER BY {}", keys.join(", "))
+}
+
+/// Condition selecting the rows that sort strictly after a row whose sort
+/// key values are `after`, pushing its parameters.
+fn after_condition(
+    keys: &[SortKey],
+    after: &[serde_json::Value],
+    params: &mut Vec<Box<dyn rusqlite::ToSql>>,
+) -> String {
+    // (k1 > v1) OR (k1 = v1 AND k2 > v2) OR ..., flipped for descending keys
+    let mut alternatives = Vec::new();
+    for (j, (sql, direction)) in keys.iter().enumerate() {
+        let mut parts = Vec::new();
+        for (k, (prefix, _)) in keys[..j].iter().enumerate() {
+            parts.push(format!("{} = ?", prefix));
+            params.push(sql_value(&after[k]));
         }
+        let op = match direction {
+            SortDirection::Asc => ">",
+            SortDirection::Desc => "<",
+        };
+        parts.push(format!("{} {} ?", sql, op));
+        params.push(sql_value(&after[j]));
+        alternatives.push(format!("({})", parts.join(" AND ")));
+    }
+    format!("({})", alternatives.join(" OR "))
+}
+
+/// SQL parameter for a sort key value read back from a cursor.
+fn sql_value(value: &serde_json::Value) -> Box<dyn rusqlite::ToSql> {
+    match value {
+        serde_json::Value::Null => Box::new(rusqlite::types::Null),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Box::new(i),
+            None => Box::new(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => Box::new(s.clone()),
+        other => Box::new(other.to_string()),
+    }
+}
+
+/// The position after the last row of a page, as handed out in `Page::next`.
+///
+/// Encoded as hex JSON so callers treat it as opaque.
+#[derive(Serialize, Deserialize)]
+struct PageCursor {
+    /// Fingerprint of the sort keys, so a cursor can't be used with a
+    /// different order.
+    order: String,
+    /// Sort key values of the last row.
+    after: Vec<serde_json::Value>,
+}
 
-        // Label filter
-        if let Some(ref labels) = filter.labels
-            && !labels.is_empty()
-        {
-            let placeholders: Vec<_> = labels.iter().map(|_| "?").collect();
-            conditions.push(format!("l.label IN ({})", placeholders.join(", ")));
-            for label in labels {
-                params.push(Box::new(label.clone()));
-            }
-        }
+impl PageCursor {
+    fn fingerprint(keys: &[SortKey]) -> String {
+        let hash = Sha256::digest(order_clause(keys).as_bytes());
+        hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
 
-        // Priority filters
-        if let Some(min_p) = filter.min_priority {
-            conditions.push("i.priority >= ?".to_string());
-            params.push(Box::new(min_p as i64));
-        }
-        if let Some(max_p) = filter.max_priority {
-            conditions.push("i.priority <= ?".to_string());
-            params.push(Box::new(max_p as i64));
-        }
+    /// Cursor token for the row with sort key values `values`.
+    fn encode(keys: &[SortKey], values: &[rusqlite::types::Value]) -> String {
+        use rusqlite::types::Value;
+        let after = values
+            .iter()
+            .map(|value| match value {
+                Value::Null => serde_json::Value::Null,
+                Value::Integer(i) => serde_json::json!(i),
+                Value::Real(f) => serde_json::json!(f),
+                Value::Text(s) => serde_json::json!(s),
+                Value::Blob(b) => serde_json::json!(String::from_utf8_lossy(b)),
+            })
+            .collect();
+        let cursor = PageCursor {
+            order: Self::fingerprint(keys),
+            after,
+        };
+        let json = serde_json::to_vec(&cursor).expect("cursor serializes");
+        json.iter().map(|b| format!("{:02x}", b)).collect()
+    }
 
-        // Title filter
-        if let Some(ref title_sub) = filter.title_contains {
-            conditions.push("LOWER(i.title) LIKE ?".to_string());
-            params.push(Box::new(format!("%{}%", title_sub.to_lowercase())));
-        }
+    /// Sort key values from a token produced by `encode` for the same keys.
+    fn decode(token: &str, keys: &[SortKey]) -> Result<Vec<serde_json::Value>> {
+        let bytes: Option<Vec<u8>> = (0..token.len())
+            .step_by(2)
+            .map(|i| token.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+            .collect();
+        let cursor: PageCursor = bytes
+            .and_then(|b| serde_json::from_slice(&b).ok())
+            .ok_or_else(|| eyre::eyre!("Invalid page cursor"))?;
 
-        // Build WHERE clause
-        if !conditions.is_empty() {
-            sql.push_str("WHERE ");
-            sql.push_str(&conditions.join(" AND "));
+        if cursor.order != Self::fingerprint(keys) || cursor.after.len() != keys.len() {
+            eyre::bail!("Page cursor belongs to a query with a different sort order");
         }
+        Ok(cursor.after)
+    }
+}
 
-        // Order by priority and created_at
-        sql.push_str(" ORDER BY i.priority ASC, i.created_at ASC");
+/// Rows per page for a paginated query with the given limit.
+fn page_size(limit: Option<usize>) -> usize {
+    limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+}
 
-        // Limit and offset (SQLite requires LIMIT before OFFSET)
-        match (filter.limit, filter.offset) {
-            (Some(limit), Some(offset)) => {
-                sql.push_str(&format!(" LIMIT {} OFFSET {}", limit, offset));
-            }
-            (Some(limit), None) => {
-                sql.push_str(&format!(" LIMIT {}", limit));
-            }
-            (None, Some(offset)) => {
-                // SQLite needs LIMIT for OFFSET to work, use -1 for unlimited
-                sql.push_str(&format!(" LIMIT -1 OFFSET {}", offset));
-            }
-            (None, None) => {}
+/// `LIMIT`/`OFFSET` clause for `filter` (empty if neither is set).
+fn limit_clause(filter: &crate::types::Filter) -> String {
+    // SQLite requires LIMIT before OFFSET; -1 means unlimited
+    match (filter.limit, filter.offset) {
+        (Some(limit), Some(offset)) => format!(" LIMIT {} OFFSET {}", limit, offset),
+        (Some(limit), None) => format!(" LIMIT {}", limit),
+        (None, Some(offset)) => format!(" LIMIT -1 OFFSET {}", offset),
+        (None, None) => String::new(),
+    }
+}
+
+/// Read complete lines of `path` after `from`, calling `apply` with each
+/// non-empty line and its byte offset. Returns the cursor positioned after the
+/// last complete line; a trailing partial line is left for the next replay.
+/// A missing path or file reads as empty. Stops at the first error `apply`
+/// returns.
+fn scan_log(
+    path: Option<&Path>,
+    from: &LogCursor,
+    mut apply: impl FnMut(&str, u64) -> Result<()>,
+) -> Result<LogCursor> {
+    let mut cursor = from.clone();
+    let Some(path) = path.filter(|p| p.exists()) else {
+        return Ok(cursor);
+    };
+
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    file.seek(SeekFrom::Start(cursor.offset))
+        .with_context(|| format!("Failed to seek in {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        let n = reader
+            .read_until(b'\n', &mut buf)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 || buf.last() != Some(&b'\n') {
+            break;
         }
 
-        let mut stmt = self.db.prepare(&sql)?;
-        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-        let mut items: Vec<Item> = stmt
-            .query_map(param_refs.as_slice(), Self::row_to_item)?
-            .filter_map(|r| r.ok())
-            .collect();
+        let offset = cursor.offset;
+        cursor.advance(&buf);
 
-        // Load labels for each item
-        for item in &mut items {
-            let mut label_stmt = self
-                .db
-                .prepare("SELECT label FROM labels WHERE item_id = ? ORDER BY label")?;
-            item.labels = label_stmt
-                .query_map(params![item.id], |row| row.get(0))?
-                .filter_map(|r| r.ok())
-                .collect();
+        match std::str::from_utf8(&buf) {
+            Ok(line) if !line.trim().is_empty() => apply(line.trim_end(), offset)?,
+            Ok(_) => {}
+            Err(e) => log::warn!("Invalid UTF-8 in {} at byte {}: {}", path.display(), offset, e),
         }
-
-        Ok(items)
     }
 
-    /// Run SQLite VACUUM to reclaim space.
-    pub fn vacuum(&self) -> Result<()> {
-        self.db.execute("VACUUM", [])?;
-        Ok(())
-    }
+    Ok(cursor)
+}
 
-    /// Count all items in the store.
-    pub fn count_all_items(&self) -> Result<usize> {
-        let count: i64 = self.db.query_row("SELECT COUNT(*) FROM items", [], |row| row.get(0))?;
-        Ok(count as usize)
+/// Check that a file still begins with the prefix described by `cursor`.
+fn verify_prefix(path: &Path, cursor: &LogCursor) -> Result<bool> {
+    let len = if path.exists() {
+        fs::metadata(path)
+            .with_context(|| format!("Failed to stat {}", path.display()))?
+            .len()
+    } else {
+        0
+    };
+    if len < cursor.offset {
+        return Ok(false);
     }
 
-    /// Count all edges in the store.
-    pub fn count_all_edges(&self) -> Result<usize> {
-        let count: i64 = self.db.query_row("SELECT COUNT(*) FROM edges", [], |row| row.get(0))?;
-        Ok(count as usize)
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file.take(cursor.offset));
+    let mut check = LogCursor::default();
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        let n = reader
+            .read_until(b'\n', &mut buf)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        check.advance(&buf);
     }
 
-    /// Count items matching the filter.
-    pub fn count_items(&self, filter: &crate::types::Filter) -> Result<usize> {
-        let mut sql = String::from("SELECT COUNT(DISTINCT i.id) FROM items i ");
+    Ok(check == *cursor)
+}
 
-        let mut conditions = Vec::new();
-        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+/// Leading fields of a JSONL record, used to check its format version.
+#[derive(Deserialize)]
+struct RecordHeader {
+    #[serde(default)]
+    v: u32,
+}
 
-        // Join labels table if filtering by label
-        if filter.labels.is_some() {
-            sql.push_str("JOIN labels l ON i.id = l.item_id ");
-        }
+/// A record serialized with its format version.
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    v: u32,
+    #[serde(flatten)]
+    record: &'a T,
+}
 
-        // Status filter
-        if let Some(ref status) = filter.status {
-            let status_str = match status {
-                Status::Open => "open",
-                Status::InProgress => "in_progress",
-                Status::Blocked => "blocked",
-                Status::Closed => "closed",
-            };
-            conditions.push("i.status = ?".to_string());
-            params.push(Box::new(status_str.to_string()));
-        }
+/// Serialize a record for appending to JSONL, tagged with `RECORD_VERSION`.
+fn encode_record<T: Serialize>(record: &T) -> serde_json::Result<String> {
+    serde_json::to_string(&Versioned {
+        v: RECORD_VERSION,
+        record,
+    })
+}
 
-        // Label filter
-        if let Some(labels) = filter.labels.as_ref().filter(|l| !l.is_empty()) {
-            let placeholders: Vec<_> = labels.iter().map(|_| "?").collect();
-            conditions.push(format!("l.label IN ({})", placeholders.join(", ")));
-            for label in labels {
-                params.push(Box::new(label.clone()));
-            }
-        }
+/// Parse a JSONL record, upgrading it first if it predates `RECORD_VERSION`.
+fn parse_record<T: DeserializeOwned>(log: LogFile, line: &str) -> Result<T> {
+    let header: RecordHeader = serde_json::from_str(line)?;
+    if header.v == RECORD_VERSION {
+        return Ok(serde_json::from_str(line)?);
+    }
+    if header.v > RECORD_VERSION {
+        eyre::bail!(
+            "record version {} is newer than supported version {}",
+            header.v,
+            RECORD_VERSION
+        );
+    }
 
-        // Priority filters
-        if let Some(min_p) = filter.min_priority {
-            conditions.push("i.priority >= ?".to_string());
-            params.push(Box::new(min_p as i64));
-        }
-        if let Some(max_p) = filter.max_priority {
-            conditions.push("i.priority <= ?".to_string());
-            params.push(Box::new(max_p as i64));
+    let mut value: serde_json::Value = serde_json::from_str(line)?;
+    if let serde_json::Value::Object(map) = &mut value {
+        for migration in RECORD_MIGRATIONS.iter().filter(|m| m.version > header.v) {
+            (migration.apply)(log, map);
+            map.insert("v".to_string(), migration.version.into());
         }
+    }
+    Ok(serde_json::from_value(value)?)
+}
 
-        // Title filter
-        if let Some(ref title_sub) = filter.title_contains {
-            conditions.push("LOWER(i.title) LIKE ?".to_string());
-            params.push(Box::new(format!("%{}%", title_sub.to_lowercase())));
-        }
+/// Change for an item record another process appended.
+///
+/// Its previous version is no longer in the cache, so the kind is read off the
+/// record: creating and closing an item set `updated_at` to the same instant
+/// as `created_at` and `closed_at`.
+fn external_item_change(item: Item) -> Change {
+    if item.status == Status::Closed && item.closed_at == Some(item.updated_at) {
+        Change::ItemClosed { item }
+    } else if item.created_at == item.updated_at {
+        Change::ItemCreated { item }
+    } else {
+        Change::ItemUpdated { item }
+    }
+}
 
-        // Build WHERE clause
-        if !conditions.is_empty() {
-            sql.push_str("WHERE ");
-            sql.push_str(&conditions.join(" AND "));
-        }
+/// Parse a record during replay.
+///
+/// A record from a newer engram is an error, as in `rewrite_log`: skipping it
+/// would hide it from this binary and let the next rewrite or compaction drop
+/// it. Malformed lines are logged and skipped (None).
+fn replay_record<T: DeserializeOwned>(log: LogFile, line: &str, offset: u64) -> Result<Option<T>> {
+    if let Ok(header) = serde_json::from_str::<RecordHeader>(line)
+        && header.v > RECORD_VERSION
+    {
+        eyre::bail!(
+            "{} contains records of version {} but this engram supports up to {}; upgrade engram",
+            log.file_name(),
+            header.v,
+            RECORD_VERSION
+        );
+    }
 
-        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
-        let count: i64 = self.db.query_row(&sql, param_refs.as_slice(), |row| row.get(0))?;
+    match parse_record(log, line) {
+        Ok(record) => Ok(Some(record)),
+        Err(e) => {
+            log::warn!("Failed to parse {} record at byte {}: {}", log.file_name(), offset, e);
+            Ok(None)
+        }
+    }
+}
 
-        Ok(count as usize)
+/// Rewrite a JSONL file so every record is at `RECORD_VERSION`.
+///
+/// The file is only replaced if some record changed. Unparseable lines are
+/// kept as-is for replay to report. Fails on records from a newer engram.
+fn rewrite_log(path: &Path, log: LogFile) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
     }
 
-    /// Convert a database row to an Item.
-    fn row_to_item(row: &rusqlite::Row) -> rusqlite::Result<Item> {
-        let status_str: String = row.get(3)?;
-        let status = match status_str.as_str() {
-            "open" => Status::Open,
-            "in_progress" => Status::InProgress,
-            "blocked" => Status::Blocked,
-            "closed" => Status::Closed,
-            _ => Status::Open,
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut output = String::with_capacity(content.len());
+    let mut changed = false;
+
+    for line in content.lines() {
+        let upgraded = match serde_json::from_str::<RecordHeader>(line) {
+            Ok(header) if header.v > RECORD_VERSION => {
+                eyre::bail!(
+                    "{} contains records of version {} but this engram supports up to {}; upgrade engram",
+                    log.file_name(),
+                    header.v,
+                    RECORD_VERSION
+                );
+            }
+            Ok(header) if header.v < RECORD_VERSION => match log {
+                LogFile::Items => parse_record::<Item>(log, line).and_then(|r| Ok(encode_record(&r)?)),
+                LogFile::Edges => parse_record::<Edge>(log, line).and_then(|r| Ok(encode_record(&r)?)),
+                LogFile::Events => parse_record::<Event>(log, line).and_then(|r| Ok(encode_record(&r)?)),
+            }
+            .ok(),
+            _ => None,
         };
 
-        let created_at_str: String = row.get(5)?;
-        let updated_at_str: String = row.get(6)?;
-        let closed_at_str: Option<String> = row.get(7)?;
-
-        Ok(Item {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            description: row.get(2)?,
-            status,
-            priority: row.get(4)?,
-            labels: vec![],
-            created_at: chrono::DateTime::parse_from_rfc3339(&created_at_str)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|_| chrono::Utc::now()),
-            updated_at: chrono::DateTime::parse_from_rfc3339(&updated_at_str)
-                .map(|dt| dt.with_timezone(&chrono::Utc))
-                .unwrap_or_else(|_| chrono::Utc::now()),
-            closed_at: closed_at_str.and_then(|s| {
-                chrono::DateTime::parse_from_rfc3339(&s)
-                    .map(|dt| dt.with_timezone(&chrono::Utc))
-                    .ok()
-            }),
-            close_reason: row.get(8)?,
-        })
+        match upgraded {
+            Some(upgraded) => {
+                output.push_str(&upgraded);
+                changed = true;
+            }
+            None => output.push_str(line),
+        }
+        output.push('\n');
     }
-}
 
-/// Count lines in a file.
-fn count_lines(path: &Path) -> Result<usize> {
-    if !path.exists() {
-        return Ok(0);
+    if !changed {
+        return Ok(());
     }
-    let file = File::open(path).context("Failed to open file for line count")?;
-    let reader = BufReader::new(file);
-    Ok(reader.lines().count())
+
+    log::info!("Upgrading {} to record version {}", log.file_name(), RECORD_VERSION);
+    let tmp_path = path.with_extension("jsonl.tmp");
+    let mut file = File::create(&tmp_path).with_context(|| format!("Failed to create {}", tmp_path.display()))?;
+    file.write_all(output.as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -1152,6 +2864,8 @@ mod tests {
             updated_at: now,
             closed_at: None,
             close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
         };
 
         storage.append_item(&item).unwrap();
@@ -1180,6 +2894,8 @@ mod tests {
                 updated_at: now,
                 closed_at: if i == 2 { Some(now) } else { None },
                 close_reason: None,
+                assignee: None,
+                lease_expires_at: None,
             };
             storage.append_item(&item).unwrap();
         }
@@ -1212,6 +2928,8 @@ mod tests {
             updated_at: now,
             closed_at: None,
             close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
         };
         storage.append_item(&blocker).unwrap();
 
@@ -1227,6 +2945,8 @@ mod tests {
             updated_at: now,
             closed_at: None,
             close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
         };
         storage.append_item(&blocked).unwrap();
 
@@ -1327,4 +3047,403 @@ mod tests {
 
         assert_eq!(storage.count_all_events().unwrap(), 3);
     }
+
+    fn make_item(id: &str, title: &str) -> Item {
+        let now = chrono::Utc::now();
+        Item {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: None,
+            status: Status::Open,
+            priority: 2,
+            labels: vec![],
+            created_at: now,
+            updated_at: now,
+            closed_at: None,
+            close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
+        }
+    }
+
+    /// Insert a row straight into SQLite; it survives replays but not rebuilds.
+    fn insert_sentinel(storage: &Storage) {
+        storage
+            .insert_item_to_db(&make_item("eg-sentinel01", "Sentinel"))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_open_replays_appended_tail() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+        insert_sentinel(&storage);
+        drop(storage);
+
+        // Another writer appends a record behind our back
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        let mut file = OpenOptions::new().append(true).open(&items_path).unwrap();
+        let json = serde_json::to_string(&make_item("eg-second0001", "Second")).unwrap();
+        writeln!(file, "{}", json).unwrap();
+        drop(file);
+
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert!(storage.get_item("eg-second0001").unwrap().is_some());
+        assert!(storage.get_item("eg-sentinel01").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_reads_refresh_after_external_append() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+
+        // A writer that appended without updating the cache
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        let mut file = OpenOptions::new().append(true).open(&items_path).unwrap();
+        let json = serde_json::to_string(&make_item("eg-second0001", "Second")).unwrap();
+        writeln!(file, "{}", json).unwrap();
+        drop(file);
+
+        assert!(storage.get_item("eg-second0001").unwrap().is_some());
+        assert_eq!(storage.count_all_items().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_write_lock_excludes_other_handles() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = Storage::init(temp_dir.path()).unwrap();
+        let lock = storage.lock().unwrap();
+
+        // Nested acquisition on the same handle does not deadlock
+        drop(storage.lock().unwrap());
+
+        let root = temp_dir.path().to_path_buf();
+        let writer = std::thread::spawn(move || {
+            let mut other = Storage::open(&root).unwrap();
+            other.append_item(&make_item("eg-other00001", "Other")).unwrap();
+        });
+
+        std::thread::sleep(Duration::from_millis(100));
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        assert_eq!(fs::read_to_string(&items_path).unwrap(), "");
+
+        drop(lock);
+        writer.join().unwrap();
+        assert!(storage.get_item("eg-other00001").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_buffered_appends_flush_together() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.set_buffered(true).unwrap();
+
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+        storage.append_item(&make_item("eg-second0001", "Second")).unwrap();
+        assert!(storage.get_item("eg-second0001").unwrap().is_some());
+
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        assert_eq!(fs::read_to_string(&items_path).unwrap(), "");
+
+        storage.flush().unwrap();
+        assert_eq!(fs::read_to_string(&items_path).unwrap().lines().count(), 2);
+        insert_sentinel(&storage);
+        drop(storage);
+
+        // Cursors cover the flushed lines, so reopening only replays the tail
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert!(storage.get_item("eg-sentinel01").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_unflushed_cache_rebuilt_on_open() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.set_buffered(true).unwrap();
+        storage.append_item(&make_item("eg-lost000001", "Lost")).unwrap();
+
+        // Simulate a crash: the buffer never reaches disk
+        storage.pending.clear();
+        storage.buffer_lock = None;
+        drop(storage);
+
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert!(storage.get_item("eg-lost000001").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_policy_survives_cache_rebuild() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        assert_eq!(storage.policy().unwrap(), Policy::default());
+
+        let policy = Policy {
+            auto_block: true,
+            ..Policy::default()
+        };
+        storage.set_policy(&policy).unwrap();
+        drop(storage);
+
+        // The cache is disposable; the policy lives next to the JSONL files
+        fs::remove_file(temp_dir.path().join(ENGRAM_DIR).join(DB_FILE)).unwrap();
+        assert!(temp_dir.path().join(ENGRAM_DIR).join(POLICY_FILE).exists());
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert_eq!(storage.policy().unwrap(), policy);
+    }
+
+    #[test]
+    fn test_failed_flush_keeps_unwritten_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+        storage.set_buffered(true).unwrap();
+        storage.append_item(&make_item("eg-second0001", "Second")).unwrap();
+        storage
+            .append_edge(&Edge {
+                from_id: "eg-second0001".to_string(),
+                to_id: "eg-first00001".to_string(),
+                kind: EdgeKind::Blocks,
+                created_at: chrono::Utc::now(),
+                deleted: false,
+            })
+            .unwrap();
+
+        // Make the edges append fail by putting a directory where the file was
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        let edges_path = temp_dir.path().join(ENGRAM_DIR).join(EDGES_FILE);
+        fs::remove_file(&edges_path).unwrap();
+        fs::create_dir(&edges_path).unwrap();
+        assert!(storage.flush().is_err());
+        assert_eq!(storage.pending.len(), 2);
+
+        // The items written before the failure were taken back out
+        assert_eq!(fs::read_to_string(&items_path).unwrap().lines().count(), 1);
+
+        fs::remove_dir(&edges_path).unwrap();
+        storage.flush().unwrap();
+        assert!(storage.pending.is_empty());
+        assert_eq!(fs::read_to_string(&items_path).unwrap().lines().count(), 2);
+        assert_eq!(fs::read_to_string(&edges_path).unwrap().lines().count(), 1);
+    }
+
+    #[test]
+    fn test_buffered_writer_does_not_block_other_writers() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = Storage::init(temp_dir.path()).unwrap();
+        writer.set_buffered(true).unwrap();
+        writer.append_item(&make_item("eg-first00001", "First")).unwrap();
+
+        let mut other = Storage::open(temp_dir.path()).unwrap();
+        other.append_item(&make_item("eg-second0001", "Second")).unwrap();
+        let mut renamed = make_item("eg-first00001", "Renamed");
+        renamed.updated_at = chrono::Utc::now();
+        other.append_item(&renamed).unwrap();
+
+        // The buffered record is flushed after the other writes, so it wins
+        writer.flush().unwrap();
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        assert_eq!(fs::read_to_string(&items_path).unwrap().lines().count(), 3);
+        assert_eq!(writer.get_item("eg-first00001").unwrap().unwrap().title, "First");
+        assert!(writer.get_item("eg-second0001").unwrap().is_some());
+        drop(writer);
+
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert_eq!(storage.get_item("eg-first00001").unwrap().unwrap().title, "First");
+    }
+
+    #[test]
+    fn test_open_keeps_buffered_writer_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = Storage::init(temp_dir.path()).unwrap();
+        writer.append_item(&make_item("eg-first00001", "First")).unwrap();
+        writer.set_buffered(true).unwrap();
+        writer.append_item(&make_item("eg-second0001", "Second")).unwrap();
+
+        // Opening must not rebuild the writer's unflushed records away
+        let reader = Storage::open(temp_dir.path()).unwrap();
+        assert!(reader.get_item("eg-first00001").unwrap().is_some());
+        assert!(reader.get_item("eg-second0001").unwrap().is_some());
+        drop(Storage::init(temp_dir.path()).unwrap());
+        assert!(reader.get_item("eg-second0001").unwrap().is_some());
+
+        writer.flush().unwrap();
+        assert!(reader.get_item("eg-second0001").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_open_checks_schema_while_writer_buffers() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut writer = Storage::init(temp_dir.path()).unwrap();
+        writer.set_buffered(true).unwrap();
+        writer.append_item(&make_item("eg-first00001", "First")).unwrap();
+
+        writer
+            .db
+            .execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?)",
+                params![(SCHEMA_VERSION + 1).to_string()],
+            )
+            .unwrap();
+        assert!(Storage::open(temp_dir.path()).is_err());
+        assert!(Storage::init(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_open_rebuilds_when_prefix_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+        insert_sentinel(&storage);
+        drop(storage);
+
+        // Rewrite history, as a git checkout would
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        let json = serde_json::to_string(&make_item("eg-other00001", "Other")).unwrap();
+        fs::write(&items_path, format!("{}\n", json)).unwrap();
+
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert!(storage.get_item("eg-first00001").unwrap().is_none());
+        assert!(storage.get_item("eg-other00001").unwrap().is_some());
+        assert!(storage.get_item("eg-sentinel01").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_replay_skips_partial_trailing_line() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+        drop(storage);
+
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        let json = serde_json::to_string(&make_item("eg-second0001", "Second")).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&items_path).unwrap();
+        write!(file, "{}", json).unwrap();
+        drop(file);
+
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert!(storage.get_item("eg-second0001").unwrap().is_none());
+        drop(storage);
+
+        // Once the line is completed it is picked up
+        let mut file = OpenOptions::new().append(true).open(&items_path).unwrap();
+        writeln!(file).unwrap();
+        drop(file);
+
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert!(storage.get_item("eg-first00001").unwrap().is_some());
+        assert!(storage.get_item("eg-second0001").unwrap().is_some());
+    }
+
+    fn meta_value(storage: &Storage, key: &str) -> Option<String> {
+        storage
+            .db
+            .query_row("SELECT value FROM meta WHERE key = ?", params![key], |row| row.get(0))
+            .optional()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_init_records_versions() {
+        let (temp_dir, mut storage) = setup_test_storage();
+        assert_eq!(meta_value(&storage, "schema_version"), Some(SCHEMA_VERSION.to_string()));
+
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+        let json = fs::read_to_string(temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE)).unwrap();
+        assert!(json.starts_with(&format!("{{\"v\":{},", RECORD_VERSION)));
+    }
+
+    #[test]
+    fn test_cache_migration_applies_and_rebuilds() {
+        let (_temp_dir, mut storage) = setup_test_storage();
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+        insert_sentinel(&storage);
+
+        let mut migrations = CACHE_MIGRATIONS.to_vec();
+        migrations.push(CacheMigration {
+            version: SCHEMA_VERSION + 1,
+            description: "add items.note",
+            sql: "ALTER TABLE items ADD COLUMN note TEXT;",
+            rebuild: true,
+        });
+        storage.migrate_cache(&migrations).unwrap();
+        assert_eq!(
+            meta_value(&storage, "schema_version"),
+            Some((SCHEMA_VERSION + 1).to_string())
+        );
+
+        // Applying again is a no-op
+        storage.migrate_cache(&migrations).unwrap();
+
+        storage.sync_with_jsonl().unwrap();
+        assert!(storage.get_item("eg-first00001").unwrap().is_some());
+        assert!(storage.get_item("eg-sentinel01").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_newer_cache_schema_rejected() {
+        let (temp_dir, storage) = setup_test_storage();
+        storage
+            .db
+            .execute(
+                "INSERT OR REPLACE INTO meta (key, value) VALUES ('schema_version', ?)",
+                params![(SCHEMA_VERSION + 1).to_string()],
+            )
+            .unwrap();
+        drop(storage);
+
+        assert!(Storage::open(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_open_upgrades_unversioned_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let engram_dir = temp_dir.path().join(ENGRAM_DIR);
+        fs::create_dir_all(&engram_dir).unwrap();
+
+        // A store written before records carried a version
+        let legacy = serde_json::to_string(&make_item("eg-legacy0001", "Legacy")).unwrap();
+        assert!(!legacy.contains("\"v\""));
+        fs::write(engram_dir.join(ITEMS_FILE), format!("{}\n", legacy)).unwrap();
+
+        let storage = Storage::open(temp_dir.path()).unwrap();
+        assert!(storage.get_item("eg-legacy0001").unwrap().is_some());
+
+        let rewritten = fs::read_to_string(engram_dir.join(ITEMS_FILE)).unwrap();
+        assert!(rewritten.starts_with(&format!("{{\"v\":{},", RECORD_VERSION)));
+        assert_eq!(meta_value(&storage, "record_version"), Some(RECORD_VERSION.to_string()));
+    }
+
+    #[test]
+    fn test_newer_records_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let engram_dir = temp_dir.path().join(ENGRAM_DIR);
+        fs::create_dir_all(&engram_dir).unwrap();
+
+        let mut record = serde_json::to_value(make_item("eg-future0001", "Future")).unwrap();
+        record["v"] = (RECORD_VERSION + 1).into();
+        fs::write(engram_dir.join(ITEMS_FILE), format!("{}\n", record)).unwrap();
+
+        assert!(Storage::open(temp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn test_replay_rejects_newer_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut storage = Storage::init(temp_dir.path()).unwrap();
+        storage.append_item(&make_item("eg-first00001", "First")).unwrap();
+
+        // A newer engram appends after this cache was upgraded
+        let items_path = temp_dir.path().join(ENGRAM_DIR).join(ITEMS_FILE);
+        let mut file = OpenOptions::new().append(true).open(&items_path).unwrap();
+        let mut record = serde_json::to_value(make_item("eg-future0001", "Future")).unwrap();
+        record["v"] = (RECORD_VERSION + 1).into();
+        writeln!(file, "{}", record).unwrap();
+        writeln!(file, "not json").unwrap();
+        drop(file);
+
+        assert!(storage.refresh().is_err());
+        assert!(Storage::open(temp_dir.path()).is_err());
+    }
 }
@@ -2,12 +2,15 @@
 
 use crate::id::{generate_event_id, generate_id};
 use crate::storage::Storage;
-use crate::types::{Edge, EdgeKind, Event, EventFilter, Item, Status, ValidationError};
-use chrono::Utc;
+use crate::types::{Change, Edge, EdgeKind, Event, EventFilter, Item, ParentPolicy, Policy, Status, ValidationError};
+use chrono::{DateTime, Duration, Utc};
 use eyre::{Context, Result};
 use std::collections::HashSet;
 use std::path::Path;
 
+/// Lease length used when a caller does not specify one, in seconds.
+pub const DEFAULT_LEASE_TTL_SECS: u64 = 300;
+
 /// Errors that can occur during store operations.
 #[derive(Debug)]
 pub enum StoreError {
@@ -21,6 +24,12 @@ pub enum StoreError {
     InvalidStatusTransition { from: Status, to: Status },
     /// Validation error.
     Validation(ValidationError),
+    /// Item is in progress under another agent's unexpired claim.
+    AlreadyClaimed { id: String, assignee: Option<String> },
+    /// Agent does not hold the claim it tried to renew.
+    LeaseNotHeld { id: String, agent: String },
+    /// Item changed since the version the caller expected.
+    Conflict { current: Box<Item> },
 }
 
 impl std::fmt::Display for StoreError {
@@ -33,6 +42,17 @@ impl std::fmt::Display for StoreError {
                 write!(f, "invalid status transition from {:?} to {:?}", from, to)
             }
             StoreError::Validation(e) => write!(f, "validation error: {}", e),
+            StoreError::AlreadyClaimed { id, assignee } => match assignee {
+                Some(assignee) => write!(f, "item {} is already claimed by {}", id, assignee),
+                None => write!(f, "item {} is already in progress", id),
+            },
+            StoreError::LeaseNotHeld { id, agent } => write!(f, "{} does not hold a claim on {}", agent, id),
+            StoreError::Conflict { current } => write!(
+                f,
+                "item {} was modified concurrently (now at version {})",
+                current.id,
+                current.updated_at.to_rfc3339()
+            ),
         }
     }
 }
@@ -62,6 +82,63 @@ impl Store {
         &self.storage
     }
 
+    /// Buffer writes in memory until `flush` (see `Storage::set_buffered`).
+    pub(crate) fn set_write_buffering(&mut self, buffered: bool) -> Result<()> {
+        self.storage.set_buffered(buffered)
+    }
+
+    /// Start collecting changes for `take_changes`.
+    pub(crate) fn track_changes(&mut self) {
+        self.storage.track_changes();
+    }
+
+    /// Drain changes made since the last call (empty unless tracking).
+    pub(crate) fn take_changes(&mut self) -> Vec<Change> {
+        self.storage.take_changes()
+    }
+
+    /// Write any buffered records to disk.
+    pub fn flush(&mut self) -> Result<()> {
+        self.storage.flush()
+    }
+
+    /// Get the store's automation policy.
+    pub fn policy(&self) -> Result<Policy> {
+        self.storage.policy()
+    }
+
+    /// Replace the store's automation policy.
+    ///
+    /// Takes effect for later writes only; existing items are not revisited.
+    pub fn set_policy(&mut self, policy: &Policy) -> Result<()> {
+        self.storage.set_policy(policy)
+    }
+
+    /// Run `f` as a single transaction.
+    ///
+    /// Writes inside `f` are validated against the store as `f` left it, so
+    /// later steps can use items and edges created by earlier ones. Nothing
+    /// reaches the JSONL files unless `f` returns `Ok`; the records are then
+    /// appended together, one write per file. On error every write is
+    /// discarded. A nested call joins the enclosing transaction.
+    pub fn transaction<T>(&mut self, f: impl FnOnce(&mut Store) -> Result<T>) -> Result<T> {
+        if self.storage.in_transaction() {
+            return f(self);
+        }
+
+        self.storage.begin_transaction()?;
+        match f(self) {
+            Ok(value) => {
+                self.storage.commit_transaction()?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.storage.rollback_transaction()?;
+                Err(e)
+            }
+        }
+    }
+
     /// Create a new item.
     pub fn create(&mut self, title: &str, priority: u8, labels: &[&str], description: Option<&str>) -> Result<Item> {
         let now = Utc::now();
@@ -78,6 +155,8 @@ impl Store {
             updated_at: now,
             closed_at: None,
             close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
         };
 
         // Validate before persisting
@@ -102,10 +181,28 @@ impl Store {
         priority: Option<u8>,
         labels: Option<&[&str]>,
     ) -> Result<Item> {
+        self.update_checked(id, None, title, description, priority, labels)
+    }
+
+    /// Update an item's fields if it is still at `expected_version`.
+    ///
+    /// An item's version is its `updated_at`. Fails with `Conflict`, carrying
+    /// the current item, if it has changed since; `None` skips the check.
+    pub fn update_checked(
+        &mut self,
+        id: &str,
+        expected_version: Option<DateTime<Utc>>,
+        title: Option<&str>,
+        description: Option<Option<&str>>,
+        priority: Option<u8>,
+        labels: Option<&[&str]>,
+    ) -> Result<Item> {
+        let _lock = self.storage.lock()?;
         let existing = self
             .storage
             .get_item(id)?
             .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(id.to_string())))?;
+        check_version(&existing, expected_version)?;
 
         let now = Utc::now();
         let updated = Item {
@@ -124,6 +221,8 @@ impl Store {
             updated_at: now,
             closed_at: existing.closed_at,
             close_reason: existing.close_reason,
+            assignee: existing.assignee,
+            lease_expires_at: existing.lease_expires_at,
         };
 
         // Validate before persisting
@@ -138,10 +237,24 @@ impl Store {
 
     /// Change an item's status.
     pub fn set_status(&mut self, id: &str, status: Status) -> Result<Item> {
+        self.set_status_checked(id, None, status)
+    }
+
+    /// Change an item's status if it is still at `expected_version`.
+    ///
+    /// See `update_checked`.
+    pub fn set_status_checked(
+        &mut self,
+        id: &str,
+        expected_version: Option<DateTime<Utc>>,
+        status: Status,
+    ) -> Result<Item> {
+        let _lock = self.storage.lock()?;
         let existing = self
             .storage
             .get_item(id)?
             .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(id.to_string())))?;
+        check_version(&existing, expected_version)?;
 
         if !existing.status.can_transition_to(&status) {
             return Err(eyre::eyre!(StoreError::InvalidStatusTransition {
@@ -150,10 +263,19 @@ impl Store {
             }));
         }
 
+        // Claims only hold while in progress; reopening also drops the assignee
+        let (assignee, lease_expires_at) = match status {
+            Status::InProgress => (existing.assignee.clone(), existing.lease_expires_at),
+            Status::Open => (None, None),
+            _ => (existing.assignee.clone(), None),
+        };
+
         let now = Utc::now();
         let updated = Item {
             status,
             updated_at: now,
+            assignee,
+            lease_expires_at,
             ..existing
         };
 
@@ -161,15 +283,46 @@ impl Store {
             .append_item(&updated)
             .context("Failed to persist status change")?;
 
+        if status == Status::Closed {
+            self.after_close(&updated.id)?;
+        }
+
         Ok(updated)
     }
 
     /// Close an item with an optional reason.
+    ///
+    /// With `Policy::auto_block` on, items left without an open blocker move
+    /// from Blocked back to Open. Parents whose children are now all Closed
+    /// are handled according to `Policy::on_children_closed`.
     pub fn close(&mut self, id: &str, reason: Option<&str>) -> Result<Item> {
+        self.close_checked(id, None, reason)
+    }
+
+    /// Close an item if it is still at `expected_version`.
+    ///
+    /// See `update_checked`.
+    pub fn close_checked(
+        &mut self,
+        id: &str,
+        expected_version: Option<DateTime<Utc>>,
+        reason: Option<&str>,
+    ) -> Result<Item> {
+        let _lock = self.storage.lock()?;
+        let updated = self.close_item(id, expected_version, reason)?;
+        self.after_close(&updated.id)?;
+
+        Ok(updated)
+    }
+
+    /// Close an item without applying the close policies.
+    fn close_item(&mut self, id: &str, expected_version: Option<DateTime<Utc>>, reason: Option<&str>) -> Result<Item> {
+        let _lock = self.storage.lock()?;
         let existing = self
             .storage
             .get_item(id)?
             .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(id.to_string())))?;
+        check_version(&existing, expected_version)?;
 
         if !existing.status.can_transition_to(&Status::Closed) {
             return Err(eyre::eyre!(StoreError::InvalidStatusTransition {
@@ -184,6 +337,7 @@ impl Store {
             updated_at: now,
             closed_at: Some(now),
             close_reason: reason.map(String::from),
+            lease_expires_at: None,
             ..existing
         };
 
@@ -192,6 +346,48 @@ impl Store {
         Ok(updated)
     }
 
+    /// Close an item and all of its open descendants.
+    ///
+    /// Descendants are found by following `ParentChild` edges and are closed
+    /// before the item itself, each through the usual transition checks, in a
+    /// single transaction. Returns the items this call closed, all with
+    /// `reason`. The close policies run once the whole tree is closed, so they
+    /// only act on dependents and parents outside it.
+    pub fn close_tree(&mut self, id: &str, reason: Option<&str>) -> Result<Vec<Item>> {
+        self.transaction(|store| {
+            if store.storage.get_item(id)?.is_none() {
+                return Err(eyre::eyre!(StoreError::ItemNotFound(id.to_string())));
+            }
+
+            // Breadth-first, so walking the list backwards visits children first
+            let mut order = vec![id.to_string()];
+            let mut seen = HashSet::from([id.to_string()]);
+            let mut next = 0;
+            while next < order.len() {
+                for child in store.storage.edge_sources(&order[next], EdgeKind::ParentChild)? {
+                    if seen.insert(child.clone()) {
+                        order.push(child);
+                    }
+                }
+                next += 1;
+            }
+
+            let mut closed = Vec::new();
+            for item_id in order.iter().rev() {
+                if let Some(item) = store.storage.get_item(item_id)?
+                    && item.status != Status::Closed
+                {
+                    closed.push(store.close_item(item_id, None, reason)?);
+                }
+            }
+            for item in &closed {
+                store.after_close(&item.id)?;
+            }
+
+            Ok(closed)
+        })
+    }
+
     /// List items with optional status filter.
     pub fn list(&self, status_filter: Option<Status>) -> Result<Vec<Item>> {
         self.storage.list_items(status_filter)
@@ -208,12 +404,18 @@ impl Store {
     }
 
     /// Add an edge between items.
+    ///
+    /// With `Policy::auto_block` on, a `Blocks` edge to an unclosed item moves
+    /// an Open `from_id` to Blocked.
     pub fn add_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<Edge> {
         // No self-referential edges
         if from_id == to_id {
             return Err(eyre::eyre!(StoreError::SelfReferentialEdge));
         }
 
+        // Hold the write lock so validation sees the same graph we append to
+        let _lock = self.storage.lock()?;
+
         // Both items must exist
         if self.storage.get_item(from_id)?.is_none() {
             return Err(eyre::eyre!(StoreError::ItemNotFound(from_id.to_string())));
@@ -235,7 +437,7 @@ impl Store {
         }
 
         // For blocking edges, check for cycles
-        if kind.is_blocking() && self.would_create_cycle(from_id, to_id)? {
+        if kind.is_blocking() && self.would_create_cycle(from_id, to_id, kind)? {
             return Err(eyre::eyre!(StoreError::CycleDetected));
         }
 
@@ -250,11 +452,18 @@ impl Store {
 
         self.storage.append_edge(&edge).context("Failed to persist edge")?;
 
+        if kind == EdgeKind::Blocks {
+            self.auto_block(from_id, to_id)?;
+        }
+
         Ok(edge)
     }
 
     /// Remove an edge between items.
     pub fn remove_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<()> {
+        let _lock = self.storage.lock()?;
+        let removed_blocker = kind == EdgeKind::Blocks && self.storage.edge_exists(from_id, to_id, kind)?;
+
         let now = Utc::now();
         let edge = Edge {
             from_id: from_id.to_string(),
@@ -268,30 +477,311 @@ impl Store {
             .append_edge(&edge)
             .context("Failed to persist edge removal")?;
 
+        if removed_blocker {
+            self.auto_unblock(from_id, to_id, "edge_removed")?;
+        }
+
         Ok(())
     }
 
-    /// Check if adding an edge would create a cycle in the blocking graph.
-    fn would_create_cycle(&self, from_id: &str, to_id: &str) -> Result<bool> {
-        // DFS from 'to_id' to see if we can reach 'from_id'
-        // If yes, adding from->to would create a cycle
+    /// Check if adding an edge would create a cycle of items waiting on each
+    /// other. Blocks edges wait along the edge and ParentChild edges against
+    /// it (the parent waits on the child), as in `ready()`.
+    fn would_create_cycle(&self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<bool> {
+        // DFS from the holder through what it waits on; reaching the waiter
+        // means the new edge would close a loop
+        let (waiter, holder) = kind.waiter_and_holder(from_id, to_id);
         let mut visited = HashSet::new();
-        let mut stack = vec![to_id.to_string()];
+        let mut stack = vec![holder.to_string()];
 
         while let Some(node) = stack.pop() {
-            if node == from_id {
+            if node == waiter {
                 return Ok(true);
             }
             if visited.insert(node.clone()) {
                 for edge in self.storage.get_blocking_edges_from(&node)? {
-                    stack.push(edge.to_id);
+                    if edge.kind == EdgeKind::Blocks {
+                        stack.push(edge.to_id);
+                    }
                 }
+                stack.extend(self.storage.edge_sources(&node, EdgeKind::ParentChild)?);
             }
         }
 
         Ok(false)
     }
 
+    // === Blocked policy ===
+
+    /// Move `id` to Blocked now that it waits on `blocker_id`, if the policy
+    /// asks for it. Only Open items move, so claims in progress are left alone.
+    fn auto_block(&mut self, id: &str, blocker_id: &str) -> Result<()> {
+        if !self.storage.policy()?.auto_block {
+            return Ok(());
+        }
+
+        let _lock = self.storage.lock()?;
+        let (Some(item), Some(blocker)) = (self.storage.get_item(id)?, self.storage.get_item(blocker_id)?) else {
+            return Ok(());
+        };
+        if item.status != Status::Open || blocker.status == Status::Closed {
+            return Ok(());
+        }
+
+        self.set_status(id, Status::Blocked)?;
+        let payload = serde_json::json!({ "reason": "edge_added" });
+        self.record_event("auto_blocked", Some(id), Some(blocker_id), payload)?;
+        Ok(())
+    }
+
+    /// Return `id` to Open if it is Blocked and no longer has an open blocker,
+    /// after `blocker_id` stopped blocking it for `reason`.
+    fn auto_unblock(&mut self, id: &str, blocker_id: &str, reason: &str) -> Result<()> {
+        if !self.storage.policy()?.auto_block {
+            return Ok(());
+        }
+
+        let _lock = self.storage.lock()?;
+        let Some(item) = self.storage.get_item(id)? else {
+            return Ok(());
+        };
+        if item.status != Status::Blocked || !self.storage.open_blockers(id)?.is_empty() {
+            return Ok(());
+        }
+
+        self.set_status(id, Status::Open)?;
+        let payload = serde_json::json!({ "reason": reason });
+        self.record_event("auto_unblocked", Some(id), Some(blocker_id), payload)?;
+        Ok(())
+    }
+
+    /// Apply the store policy after `id` has closed.
+    fn after_close(&mut self, id: &str) -> Result<()> {
+        self.release_dependents(id)?;
+        self.complete_parents(id)
+    }
+
+    /// Unblock the items waiting on `blocker_id`, which has just closed.
+    fn release_dependents(&mut self, blocker_id: &str) -> Result<()> {
+        if !self.storage.policy()?.auto_block {
+            return Ok(());
+        }
+
+        for id in self.storage.edge_sources(blocker_id, EdgeKind::Blocks)? {
+            self.auto_unblock(&id, blocker_id, "blocker_closed")?;
+        }
+        Ok(())
+    }
+
+    /// Apply `Policy::on_children_closed` to the parents of `child_id`, which
+    /// has just closed.
+    fn complete_parents(&mut self, child_id: &str) -> Result<()> {
+        let action = self.storage.policy()?.on_children_closed;
+        if action == ParentPolicy::Nothing {
+            return Ok(());
+        }
+
+        let _lock = self.storage.lock()?;
+        let parents: Vec<String> = self
+            .storage
+            .get_blocking_edges_from(child_id)?
+            .into_iter()
+            .filter(|e| e.kind == EdgeKind::ParentChild)
+            .map(|e| e.to_id)
+            .collect();
+
+        for parent_id in parents {
+            let Some(parent) = self.storage.get_item(&parent_id)? else {
+                continue;
+            };
+            if !self.children_closed(&parent_id)? {
+                continue;
+            }
+
+            match action {
+                ParentPolicy::Ready => {
+                    // Only a Blocked parent needs moving, and only if nothing else blocks it
+                    if parent.status != Status::Blocked
+                        || !parent.status.can_transition_to(&Status::Open)
+                        || !self.storage.open_blockers(&parent_id)?.is_empty()
+                    {
+                        continue;
+                    }
+                    self.set_status(&parent_id, Status::Open)?;
+                    self.record_event("auto_ready", Some(&parent_id), Some(child_id), serde_json::Value::Null)?;
+                }
+                ParentPolicy::Close => {
+                    // Leave parents that still wait on a blocker, or that an agent is working on
+                    if parent.status == Status::Closed
+                        || !parent.status.can_transition_to(&Status::Closed)
+                        || !self.storage.open_blockers(&parent_id)?.is_empty()
+                        || (parent.status == Status::InProgress
+                            && parent.lease_expires_at.is_some_and(|expires| expires > Utc::now()))
+                    {
+                        continue;
+                    }
+                    self.close(&parent_id, Some("All children closed"))?;
+                    self.record_event("auto_closed", Some(&parent_id), Some(child_id), serde_json::Value::Null)?;
+                }
+                ParentPolicy::Nothing => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether every child of `parent_id` is Closed.
+    fn children_closed(&self, parent_id: &str) -> Result<bool> {
+        for child_id in self.storage.edge_sources(parent_id, EdgeKind::ParentChild)? {
+            if self
+                .storage
+                .get_item(&child_id)?
+                .is_some_and(|child| child.status != Status::Closed)
+            {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // === Claim API ===
+
+    /// Claim an item for `agent`, moving it to InProgress with a lease of `ttl`.
+    ///
+    /// Succeeds if the item is Open, already claimed by `agent` (the lease is
+    /// renewed), or held under a lease that has expired but not yet been
+    /// swept. Fails with `AlreadyClaimed` while another agent holds it, and
+    /// with `InvalidStatusTransition` for any other state, including an
+    /// InProgress item nobody claimed.
+    pub fn claim(&mut self, id: &str, agent: &str, ttl: Duration) -> Result<Item> {
+        let _lock = self.storage.lock()?;
+        let existing = self
+            .storage
+            .get_item(id)?
+            .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(id.to_string())))?;
+
+        let now = Utc::now();
+        match existing.status {
+            Status::Open => {}
+            Status::InProgress if existing.assignee.as_deref() == Some(agent) || lease_expired(&existing, now) => {}
+            Status::InProgress if existing.assignee.is_some() => {
+                return Err(eyre::eyre!(StoreError::AlreadyClaimed {
+                    id: id.to_string(),
+                    assignee: existing.assignee,
+                }));
+            }
+            _ => {
+                return Err(eyre::eyre!(StoreError::InvalidStatusTransition {
+                    from: existing.status,
+                    to: Status::InProgress
+                }));
+            }
+        }
+
+        let updated = Item {
+            status: Status::InProgress,
+            updated_at: now,
+            assignee: Some(agent.to_string()),
+            lease_expires_at: Some(lease_expiry(now, ttl)?),
+            ..existing
+        };
+
+        self.storage.append_item(&updated).context("Failed to persist claim")?;
+
+        Ok(updated)
+    }
+
+    /// Claim the highest-priority ready item for `agent`.
+    ///
+    /// Candidates come from `ready()` in its usual order, optionally limited to
+    /// items carrying any of `labels` and with priority at most `max_priority`.
+    /// Selection and claim happen under one lock, so concurrent callers never
+    /// get the same item. Returns None if nothing matches.
+    pub fn claim_next(
+        &mut self,
+        agent: &str,
+        labels: &[&str],
+        max_priority: Option<u8>,
+        ttl: Duration,
+    ) -> Result<Option<Item>> {
+        let _lock = self.storage.lock()?;
+
+        let next = self.storage.ready()?.into_iter().find(|item| {
+            max_priority.is_none_or(|max| item.priority <= max)
+                && (labels.is_empty() || item.labels.iter().any(|l| labels.contains(&l.as_str())))
+        });
+
+        match next {
+            Some(item) => self.claim(&item.id, agent, ttl).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Renew `agent`'s claim on an item for another `ttl`.
+    pub fn heartbeat(&mut self, id: &str, agent: &str, ttl: Duration) -> Result<Item> {
+        let _lock = self.storage.lock()?;
+        let existing = self
+            .storage
+            .get_item(id)?
+            .ok_or_else(|| eyre::eyre!(StoreError::ItemNotFound(id.to_string())))?;
+
+        if existing.status != Status::InProgress || existing.assignee.as_deref() != Some(agent) {
+            return Err(eyre::eyre!(StoreError::LeaseNotHeld {
+                id: id.to_string(),
+                agent: agent.to_string(),
+            }));
+        }
+
+        let now = Utc::now();
+        let updated = Item {
+            updated_at: now,
+            lease_expires_at: Some(lease_expiry(now, ttl)?),
+            ..existing
+        };
+
+        self.storage
+            .append_item(&updated)
+            .context("Failed to persist heartbeat")?;
+
+        Ok(updated)
+    }
+
+    /// Return items whose lease has expired to Open.
+    ///
+    /// Records a `lease_expired` event for each released item and returns the
+    /// released items.
+    pub fn expire_leases(&mut self) -> Result<Vec<Item>> {
+        let _lock = self.storage.lock()?;
+        let now = Utc::now();
+
+        let mut released = Vec::new();
+        for item in self.storage.list_items(Some(Status::InProgress))? {
+            if !lease_expired(&item, now) {
+                continue;
+            }
+
+            let payload = serde_json::json!({
+                "assignee": item.assignee,
+                "lease_expires_at": item.lease_expires_at,
+            });
+            let updated = Item {
+                status: Status::Open,
+                updated_at: now,
+                assignee: None,
+                lease_expires_at: None,
+                ..item
+            };
+
+            self.storage
+                .append_item(&updated)
+                .context("Failed to persist lease expiry")?;
+            self.record_event("lease_expired", Some(&updated.id), None, payload)?;
+            released.push(updated);
+        }
+
+        Ok(released)
+    }
+
     // === Event API ===
 
     /// Record an event.
@@ -346,6 +836,35 @@ impl Store {
     }
 }
 
+/// Fail with `Conflict` unless `item` is at `expected` (if given).
+fn check_version(item: &Item, expected: Option<DateTime<Utc>>) -> Result<()> {
+    match expected {
+        Some(version) if version != item.updated_at => Err(eyre::eyre!(StoreError::Conflict {
+            current: Box::new(item.clone())
+        })),
+        _ => Ok(()),
+    }
+}
+
+/// Convert a lease TTL in seconds, as sent over the wire, to a `Duration`.
+pub fn lease_ttl(secs: u64) -> Result<Duration> {
+    i64::try_from(secs)
+        .ok()
+        .and_then(Duration::try_seconds)
+        .ok_or_else(|| eyre::eyre!(StoreError::Validation(ValidationError::InvalidLeaseTtl)))
+}
+
+/// When a lease of `ttl` taken at `now` runs out.
+fn lease_expiry(now: DateTime<Utc>, ttl: Duration) -> Result<DateTime<Utc>> {
+    now.checked_add_signed(ttl)
+        .ok_or_else(|| eyre::eyre!(StoreError::Validation(ValidationError::InvalidLeaseTtl)))
+}
+
+/// Whether an item's lease ran out before `now` (false if it has none).
+fn lease_expired(item: &Item, now: DateTime<Utc>) -> bool {
+    item.lease_expires_at.is_some_and(|expires| expires <= now)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -468,6 +987,12 @@ mod tests {
         // C -> A would create a cycle
         let result = store.add_edge(&c.id, &a.id, EdgeKind::Blocks);
         assert!(result.is_err());
+
+        // A parent waits on its child, so making A a child of C (which A
+        // waits on) deadlocks too; making A's blocker B its child does not
+        let result = store.add_edge(&a.id, &c.id, EdgeKind::ParentChild);
+        assert!(result.is_err());
+        store.add_edge(&b.id, &a.id, EdgeKind::ParentChild).unwrap();
     }
 
     #[test]
@@ -535,4 +1060,356 @@ mod tests {
         let started = store.query_events(EventFilter::new().kind("task_started")).unwrap();
         assert_eq!(started.len(), 2);
     }
+
+    #[test]
+    fn test_transaction_commits_together() {
+        let (temp_dir, mut store) = setup_test_store();
+
+        let (parent, child) = store
+            .transaction(|tx| {
+                let parent = tx.create("Parent", 1, &[], None)?;
+                let child = tx.create("Child", 2, &[], None)?;
+                tx.add_edge(&child.id, &parent.id, EdgeKind::ParentChild)?;
+                Ok((parent, child))
+            })
+            .unwrap();
+
+        assert_eq!(store.ready().unwrap()[0].id, child.id);
+        let items = std::fs::read_to_string(temp_dir.path().join(".engram/items.jsonl")).unwrap();
+        assert_eq!(items.lines().count(), 2);
+
+        // A fresh handle replays the committed records
+        let reopened = Store::open(temp_dir.path()).unwrap();
+        assert!(reopened.get(&parent.id).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_transaction_rolls_back_on_error() {
+        let (temp_dir, mut store) = setup_test_store();
+        let a = store.create("A", 2, &[], None).unwrap();
+        let b = store.create("B", 2, &[], None).unwrap();
+        store.add_edge(&a.id, &b.id, EdgeKind::Blocks).unwrap();
+
+        let err = store
+            .transaction(|tx| {
+                tx.create("Orphan", 2, &[], None)?;
+                tx.add_edge(&b.id, &a.id, EdgeKind::Blocks)
+            })
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::CycleDetected)
+        ));
+
+        assert_eq!(store.list(None).unwrap().len(), 2);
+        let items = std::fs::read_to_string(temp_dir.path().join(".engram/items.jsonl")).unwrap();
+        assert_eq!(items.lines().count(), 2);
+        assert_eq!(Store::open(temp_dir.path()).unwrap().list(None).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_checked_writes_detect_conflicts() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let item = store.create("Shared", 2, &[], None).unwrap();
+
+        // Another agent edits the item first
+        let theirs = store
+            .update_checked(&item.id, Some(item.updated_at), Some("Theirs"), None, None, None)
+            .unwrap();
+
+        let err = store
+            .update_checked(&item.id, Some(item.updated_at), Some("Ours"), None, None, None)
+            .unwrap_err();
+        match err.downcast_ref::<StoreError>() {
+            Some(StoreError::Conflict { current }) => assert_eq!(current.title, "Theirs"),
+            other => panic!("Expected Conflict, got {:?}", other),
+        }
+        assert!(
+            store
+                .set_status_checked(&item.id, Some(item.updated_at), Status::InProgress)
+                .is_err()
+        );
+        assert!(store.close_checked(&item.id, Some(item.updated_at), None).is_err());
+
+        // Retrying against the current version succeeds
+        let closed = store.close_checked(&item.id, Some(theirs.updated_at), None).unwrap();
+        assert_eq!(closed.status, Status::Closed);
+        assert_eq!(store.get(&item.id).unwrap().unwrap().title, "Theirs");
+    }
+
+    #[test]
+    fn test_auto_block_policy() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let first = store.create("First blocker", 1, &[], None).unwrap();
+        let second = store.create("Second blocker", 1, &[], None).unwrap();
+        let task = store.create("Task", 2, &[], None).unwrap();
+
+        // Off by default: the status is left alone
+        store.add_edge(&task.id, &first.id, EdgeKind::Blocks).unwrap();
+        assert_eq!(store.get(&task.id).unwrap().unwrap().status, Status::Open);
+        store.remove_edge(&task.id, &first.id, EdgeKind::Blocks).unwrap();
+
+        store
+            .set_policy(&Policy {
+                auto_block: true,
+                ..Policy::default()
+            })
+            .unwrap();
+        assert!(store.policy().unwrap().auto_block);
+
+        store.add_edge(&task.id, &first.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&task.id, &second.id, EdgeKind::Blocks).unwrap();
+        assert_eq!(store.get(&task.id).unwrap().unwrap().status, Status::Blocked);
+
+        // Still waiting on the second blocker
+        store.close(&first.id, None).unwrap();
+        assert_eq!(store.get(&task.id).unwrap().unwrap().status, Status::Blocked);
+
+        store.remove_edge(&task.id, &second.id, EdgeKind::Blocks).unwrap();
+        assert_eq!(store.get(&task.id).unwrap().unwrap().status, Status::Open);
+
+        let kinds: Vec<String> = store
+            .task_events(&task.id, 10)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(kinds.iter().filter(|k| *k == "auto_blocked").count(), 1);
+        assert_eq!(kinds.iter().filter(|k| *k == "auto_unblocked").count(), 1);
+
+        // Closing the last blocker also unblocks
+        store.add_edge(&task.id, &second.id, EdgeKind::Blocks).unwrap();
+        assert_eq!(store.get(&task.id).unwrap().unwrap().status, Status::Blocked);
+        store.set_status(&second.id, Status::Closed).unwrap();
+        assert_eq!(store.get(&task.id).unwrap().unwrap().status, Status::Open);
+    }
+
+    #[test]
+    fn test_close_tree() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let root = store.create("Epic", 1, &[], None).unwrap();
+        let story = store.create("Story", 2, &[], None).unwrap();
+        let task = store.create("Task", 2, &[], None).unwrap();
+        let done = store.create("Done already", 2, &[], None).unwrap();
+        store.add_edge(&story.id, &root.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&task.id, &story.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&done.id, &root.id, EdgeKind::ParentChild).unwrap();
+        store.close(&done.id, None).unwrap();
+
+        let closed: Vec<String> = store
+            .close_tree(&root.id, Some("Shipped"))
+            .unwrap()
+            .into_iter()
+            .map(|i| i.id)
+            .collect();
+
+        assert_eq!(closed, vec![task.id.clone(), story.id.clone(), root.id.clone()]);
+        assert!(store.list(Some(Status::Closed)).unwrap().len() == 4);
+        assert_eq!(
+            store.get(&task.id).unwrap().unwrap().close_reason.as_deref(),
+            Some("Shipped")
+        );
+        assert!(store.close_tree("eg-missing", None).is_err());
+
+        // Closing parents is left to close_tree, not `on_children_closed`
+        store
+            .set_policy(&Policy {
+                on_children_closed: ParentPolicy::Close,
+                ..Policy::default()
+            })
+            .unwrap();
+        let root = store.create("Epic", 1, &[], None).unwrap();
+        let story = store.create("Story", 2, &[], None).unwrap();
+        store.add_edge(&story.id, &root.id, EdgeKind::ParentChild).unwrap();
+
+        let closed = store.close_tree(&root.id, Some("Shipped")).unwrap();
+        assert_eq!(closed.len(), 2);
+        assert!(closed.iter().all(|i| i.close_reason.as_deref() == Some("Shipped")));
+        assert!(
+            store
+                .task_events(&root.id, 10)
+                .unwrap()
+                .iter()
+                .all(|e| e.kind != "auto_closed")
+        );
+    }
+
+    #[test]
+    fn test_parent_policy() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let parent = store.create("Parent", 1, &[], None).unwrap();
+        let first = store.create("First", 2, &[], None).unwrap();
+        let second = store.create("Second", 2, &[], None).unwrap();
+        store.add_edge(&first.id, &parent.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&second.id, &parent.id, EdgeKind::ParentChild).unwrap();
+
+        store
+            .set_policy(&Policy {
+                on_children_closed: ParentPolicy::Ready,
+                ..Policy::default()
+            })
+            .unwrap();
+        store.set_status(&parent.id, Status::Blocked).unwrap();
+        store.close(&first.id, None).unwrap();
+        assert_eq!(store.get(&parent.id).unwrap().unwrap().status, Status::Blocked);
+        store.close(&second.id, None).unwrap();
+        assert_eq!(store.get(&parent.id).unwrap().unwrap().status, Status::Open);
+        assert_eq!(store.ready().unwrap()[0].id, parent.id);
+
+        store
+            .set_policy(&Policy {
+                on_children_closed: ParentPolicy::Close,
+                ..Policy::default()
+            })
+            .unwrap();
+        store.set_status(&second.id, Status::Open).unwrap();
+        store.close(&second.id, None).unwrap();
+
+        let parent = store.get(&parent.id).unwrap().unwrap();
+        assert_eq!(parent.status, Status::Closed);
+        assert_eq!(parent.close_reason.as_deref(), Some("All children closed"));
+        assert_eq!(store.task_events(&parent.id, 10).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_parent_policy_close_skips_blocked_and_claimed_parents() {
+        let (_temp_dir, mut store) = setup_test_store();
+        store
+            .set_policy(&Policy {
+                on_children_closed: ParentPolicy::Close,
+                ..Policy::default()
+            })
+            .unwrap();
+
+        let waiting = store.create("Waiting", 1, &[], None).unwrap();
+        let blocker = store.create("Blocker", 1, &[], None).unwrap();
+        let claimed = store.create("Claimed", 1, &[], None).unwrap();
+        let first = store.create("First", 2, &[], None).unwrap();
+        let second = store.create("Second", 2, &[], None).unwrap();
+        store.add_edge(&waiting.id, &blocker.id, EdgeKind::Blocks).unwrap();
+        store.add_edge(&first.id, &waiting.id, EdgeKind::ParentChild).unwrap();
+        store.add_edge(&second.id, &claimed.id, EdgeKind::ParentChild).unwrap();
+        store.claim(&claimed.id, "agent-a", Duration::seconds(60)).unwrap();
+
+        store.close(&first.id, None).unwrap();
+        store.close(&second.id, None).unwrap();
+        assert_eq!(store.get(&waiting.id).unwrap().unwrap().status, Status::Open);
+        assert_eq!(store.get(&claimed.id).unwrap().unwrap().status, Status::InProgress);
+        assert!(store.task_events(&waiting.id, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_claim_and_heartbeat() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let item = store.create("Task", 2, &[], None).unwrap();
+
+        let claimed = store.claim(&item.id, "agent-a", Duration::seconds(60)).unwrap();
+        assert_eq!(claimed.status, Status::InProgress);
+        assert_eq!(claimed.assignee.as_deref(), Some("agent-a"));
+
+        // Another agent cannot take a live claim
+        let err = store.claim(&item.id, "agent-b", Duration::seconds(60)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::AlreadyClaimed { .. })
+        ));
+
+        // Leases that would overflow the clock are rejected, not panicked on
+        assert!(lease_ttl(u64::MAX).is_err());
+        let err = store.heartbeat(&item.id, "agent-a", Duration::MAX).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::Validation(ValidationError::InvalidLeaseTtl))
+        ));
+
+        // Only the holder can renew
+        let renewed = store.heartbeat(&item.id, "agent-a", Duration::seconds(120)).unwrap();
+        assert!(renewed.lease_expires_at > claimed.lease_expires_at);
+        let err = store.heartbeat(&item.id, "agent-b", Duration::seconds(60)).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::LeaseNotHeld { .. })
+        ));
+
+        // Closing keeps the assignee but drops the lease
+        let closed = store.close(&item.id, None).unwrap();
+        assert_eq!(closed.assignee.as_deref(), Some("agent-a"));
+        assert!(closed.lease_expires_at.is_none());
+    }
+
+    #[test]
+    fn test_claim_rejects_blocked_and_unclaimed_in_progress() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let blocked = store.create("Blocked", 2, &[], None).unwrap();
+        let started = store.create("Started", 2, &[], None).unwrap();
+        store.set_status(&blocked.id, Status::Blocked).unwrap();
+        store.set_status(&started.id, Status::InProgress).unwrap();
+
+        for id in [&blocked.id, &started.id] {
+            let err = store.claim(id, "agent-a", Duration::seconds(60)).unwrap_err();
+            assert!(matches!(
+                err.downcast_ref::<StoreError>(),
+                Some(StoreError::InvalidStatusTransition {
+                    to: Status::InProgress,
+                    ..
+                })
+            ));
+            assert!(store.get(id).unwrap().unwrap().assignee.is_none());
+        }
+    }
+
+    #[test]
+    fn test_expire_leases() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let stale = store.create("Stale", 2, &[], None).unwrap();
+        let live = store.create("Live", 2, &[], None).unwrap();
+
+        store.claim(&stale.id, "agent-a", Duration::zero()).unwrap();
+        store.claim(&live.id, "agent-b", Duration::seconds(60)).unwrap();
+
+        // An expired claim can be taken over before the sweep runs
+        let taken = store.claim(&stale.id, "agent-c", Duration::zero()).unwrap();
+        assert_eq!(taken.assignee.as_deref(), Some("agent-c"));
+
+        let released = store.expire_leases().unwrap();
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].id, stale.id);
+
+        let stale = store.get(&stale.id).unwrap().unwrap();
+        assert_eq!(stale.status, Status::Open);
+        assert!(stale.assignee.is_none());
+        assert_eq!(store.get(&live.id).unwrap().unwrap().status, Status::InProgress);
+
+        let events = store.query_events(EventFilter::new().kind("lease_expired")).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].source_task.as_deref(), Some(stale.id.as_str()));
+        assert_eq!(events[0].payload["assignee"], "agent-c");
+    }
+
+    #[test]
+    fn test_claim_next() {
+        let (_temp_dir, mut store) = setup_test_store();
+        let low = store.create("Low", 3, &["backend"], None).unwrap();
+        let high = store.create("High", 1, &["frontend"], None).unwrap();
+        let blocked = store.create("Blocked", 0, &["backend"], None).unwrap();
+        store.add_edge(&blocked.id, &low.id, EdgeKind::Blocks).unwrap();
+
+        let ttl = Duration::seconds(60);
+        let first = store.claim_next("agent-a", &[], None, ttl).unwrap().unwrap();
+        assert_eq!(first.id, high.id);
+        assert_eq!(first.assignee.as_deref(), Some("agent-a"));
+
+        // Label and priority filters narrow the candidates
+        assert!(store.claim_next("agent-b", &["frontend"], None, ttl).unwrap().is_none());
+        assert!(
+            store
+                .claim_next("agent-b", &["backend"], Some(2), ttl)
+                .unwrap()
+                .is_none()
+        );
+
+        let second = store.claim_next("agent-b", &["backend"], None, ttl).unwrap().unwrap();
+        assert_eq!(second.id, low.id);
+        assert!(store.claim_next("agent-c", &[], None, ttl).unwrap().is_none());
+    }
 }
@@ -0,0 +1,335 @@
+//! A common interface over the ways of reaching a task graph.
+//!
+//! Code written against [`TaskGraph`] works the same whether it talks to a
+//! [`Store`] directly, to a running daemon through a [`Client`], or to a
+//! [`MemoryGraph`] in tests.
+//!
+//! [`MemoryGraph`]: crate::memory::MemoryGraph
+
+use crate::client::Client;
+use crate::daemon::is_daemon_running;
+use crate::query::StoreQueryExt;
+use crate::store::Store;
+use crate::types::{Edge, EdgeKind, Event, EventFilter, Filter, Item, Status};
+use eyre::{Context, Result};
+use std::path::Path;
+
+/// Operations shared by every task graph backend.
+pub trait TaskGraph {
+    /// Create a new item.
+    fn create(&mut self, title: &str, priority: u8, labels: &[&str], description: Option<&str>) -> Result<Item>;
+
+    /// Get an item by ID.
+    fn get(&self, id: &str) -> Result<Option<Item>>;
+
+    /// Update an item's fields.
+    fn update(
+        &mut self,
+        id: &str,
+        title: Option<&str>,
+        description: Option<Option<&str>>,
+        priority: Option<u8>,
+        labels: Option<&[&str]>,
+    ) -> Result<Item>;
+
+    /// Change an item's status.
+    fn set_status(&mut self, id: &str, status: Status) -> Result<Item>;
+
+    /// Close an item with an optional reason.
+    fn close(&mut self, id: &str, reason: Option<&str>) -> Result<Item>;
+
+    /// List items with optional status filter.
+    fn list(&self, status_filter: Option<Status>) -> Result<Vec<Item>>;
+
+    /// Get items that are ready to work on.
+    fn ready(&self) -> Result<Vec<Item>>;
+
+    /// Get items that are currently blocked.
+    fn blocked(&self) -> Result<Vec<Item>>;
+
+    /// Add an edge between items.
+    fn add_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<Edge>;
+
+    /// Remove an edge between items.
+    fn remove_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<()>;
+
+    /// Query items matching a filter.
+    fn query_items(&self, filter: &Filter) -> Result<Vec<Item>>;
+
+    /// Count items matching a filter (ignores limit and offset).
+    fn count_items(&self, filter: &Filter) -> Result<usize>;
+
+    /// Record an event.
+    fn record_event(
+        &mut self,
+        kind: &str,
+        source_task: Option<&str>,
+        target_task: Option<&str>,
+        payload: serde_json::Value,
+    ) -> Result<Event>;
+
+    /// Get an event by ID.
+    fn get_event(&self, id: &str) -> Result<Option<Event>>;
+
+    /// Query events with filters.
+    fn query_events(&self, filter: EventFilter) -> Result<Vec<Event>>;
+
+    /// Get recent events.
+    fn recent_events(&self, limit: usize) -> Result<Vec<Event>>;
+
+    /// Get events for a specific task (as source or target).
+    fn task_events(&self, task_id: &str, limit: usize) -> Result<Vec<Event>>;
+}
+
+/// Open the task graph at `root`, through the daemon if one is running.
+///
+/// Falls back to opening the store directly when no daemon is serving it.
+pub fn connect_or_open(root: &Path) -> Result<Box<dyn TaskGraph>> {
+    if is_daemon_running(root)
+        && let Ok(client) = Client::connect(root, false)
+    {
+        return Ok(Box::new(client));
+    }
+
+    let store = Store::open(root).context("Failed to open store")?;
+    Ok(Box::new(store))
+}
+
+impl TaskGraph for Store {
+    fn create(&mut self, title: &str, priority: u8, labels: &[&str], description: Option<&str>) -> Result<Item> {
+        Store::create(self, title, priority, labels, description)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Item>> {
+        Store::get(self, id)
+    }
+
+    fn update(
+        &mut self,
+        id: &str,
+        title: Option<&str>,
+        description: Option<Option<&str>>,
+        priority: Option<u8>,
+        labels: Option<&[&str]>,
+    ) -> Result<Item> {
+        Store::update(self, id, title, description, priority, labels)
+    }
+
+    fn set_status(&mut self, id: &str, status: Status) -> Result<Item> {
+        Store::set_status(self, id, status)
+    }
+
+    fn close(&mut self, id: &str, reason: Option<&str>) -> Result<Item> {
+        Store::close(self, id, reason)
+    }
+
+    fn list(&self, status_filter: Option<Status>) -> Result<Vec<Item>> {
+        Store::list(self, status_filter)
+    }
+
+    fn ready(&self) -> Result<Vec<Item>> {
+        Store::ready(self)
+    }
+
+    fn blocked(&self) -> Result<Vec<Item>> {
+        Store::blocked(self)
+    }
+
+    fn add_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<Edge> {
+        Store::add_edge(self, from_id, to_id, kind)
+    }
+
+    fn remove_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<()> {
+        Store::remove_edge(self, from_id, to_id, kind)
+    }
+
+    fn query_items(&self, filter: &Filter) -> Result<Vec<Item>> {
+        self.query_with_filter(filter)
+    }
+
+    fn count_items(&self, filter: &Filter) -> Result<usize> {
+        self.storage().count_items(filter)
+    }
+
+    fn record_event(
+        &mut self,
+        kind: &str,
+        source_task: Option<&str>,
+        target_task: Option<&str>,
+        payload: serde_json::Value,
+    ) -> Result<Event> {
+        Store::record_event(self, kind, source_task, target_task, payload)
+    }
+
+    fn get_event(&self, id: &str) -> Result<Option<Event>> {
+        Store::get_event(self, id)
+    }
+
+    fn query_events(&self, filter: EventFilter) -> Result<Vec<Event>> {
+        Store::query_events(self, filter)
+    }
+
+    fn recent_events(&self, limit: usize) -> Result<Vec<Event>> {
+        Store::recent_events(self, limit)
+    }
+
+    fn task_events(&self, task_id: &str, limit: usize) -> Result<Vec<Event>> {
+        Store::task_events(self, task_id, limit)
+    }
+}
+
+impl TaskGraph for Client {
+    fn create(&mut self, title: &str, priority: u8, labels: &[&str], description: Option<&str>) -> Result<Item> {
+        Client::create(self, title, priority, labels, description)
+    }
+
+    fn get(&self, id: &str) -> Result<Option<Item>> {
+        Client::get(self, id)
+    }
+
+    fn update(
+        &mut self,
+        id: &str,
+        title: Option<&str>,
+        description: Option<Option<&str>>,
+        priority: Option<u8>,
+        labels: Option<&[&str]>,
+    ) -> Result<Item> {
+        Client::update(self, id, title, description, priority, labels)
+    }
+
+    fn set_status(&mut self, id: &str, status: Status) -> Result<Item> {
+        Client::set_status(self, id, status)
+    }
+
+    fn close(&mut self, id: &str, reason: Option<&str>) -> Result<Item> {
+        Client::close(self, id, reason)
+    }
+
+    fn list(&self, status_filter: Option<Status>) -> Result<Vec<Item>> {
+        Client::list(self, status_filter)
+    }
+
+    fn ready(&self) -> Result<Vec<Item>> {
+        Client::ready(self)
+    }
+
+    fn blocked(&self) -> Result<Vec<Item>> {
+        Client::blocked(self)
+    }
+
+    fn add_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<Edge> {
+        Client::add_edge(self, from_id, to_id, kind)
+    }
+
+    fn remove_edge(&mut self, from_id: &str, to_id: &str, kind: EdgeKind) -> Result<()> {
+        Client::remove_edge(self, from_id, to_id, kind)
+    }
+
+    fn query_items(&self, filter: &Filter) -> Result<Vec<Item>> {
+        self.query_with_filter(filter)
+    }
+
+    fn count_items(&self, filter: &Filter) -> Result<usize> {
+        self.count(filter)
+    }
+
+    fn record_event(
+        &mut self,
+        kind: &str,
+        source_task: Option<&str>,
+        target_task: Option<&str>,
+        payload: serde_json::Value,
+    ) -> Result<Event> {
+        Client::record_event(self, kind, source_task, target_task, payload)
+    }
+
+    fn get_event(&self, id: &str) -> Result<Option<Event>> {
+        Client::get_event(self, id)
+    }
+
+    fn query_events(&self, filter: EventFilter) -> Result<Vec<Event>> {
+        Client::query_events(self, filter)
+    }
+
+    fn recent_events(&self, limit: usize) -> Result<Vec<Event>> {
+        Client::recent_events(self, limit)
+    }
+
+    fn task_events(&self, task_id: &str, limit: usize) -> Result<Vec<Event>> {
+        Client::task_events(self, task_id, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryGraph;
+    use crate::types::{SortColumn, SortDirection};
+    use tempfile::TempDir;
+
+    /// Exercise the shared behaviour every backend must agree on.
+    fn exercise(graph: &mut dyn TaskGraph) {
+        let blocker = graph.create("Blocker", 1, &["core"], None).unwrap();
+        let blocked = graph.create("Blocked", 2, &[], Some("Waits")).unwrap();
+        graph.add_edge(&blocked.id, &blocker.id, EdgeKind::Blocks).unwrap();
+
+        assert!(graph.add_edge(&blocker.id, &blocked.id, EdgeKind::Blocks).is_err());
+        assert_eq!(
+            graph.get(&blocked.id).unwrap().unwrap().description.as_deref(),
+            Some("Waits")
+        );
+
+        let ready: Vec<String> = graph.ready().unwrap().into_iter().map(|i| i.id).collect();
+        assert_eq!(ready, vec![blocker.id.clone()]);
+        assert_eq!(graph.blocked().unwrap().len(), 1);
+
+        let filter = Filter::new().label("core");
+        assert_eq!(graph.query_items(&filter).unwrap().len(), 1);
+        assert_eq!(graph.count_items(&Filter::new()).unwrap(), 2);
+
+        let filter = Filter::new()
+            .without_labels(["core"])
+            .description_contains("wait")
+            .created_after(blocker.created_at);
+        assert_eq!(graph.query_items(&filter).unwrap()[0].id, blocked.id);
+        let by_title = Filter::new().order_by(SortColumn::Title, SortDirection::Desc);
+        let titles: Vec<String> = graph
+            .query_items(&by_title)
+            .unwrap()
+            .into_iter()
+            .map(|i| i.title)
+            .collect();
+        assert_eq!(titles, vec!["Blocker", "Blocked"]);
+
+        graph.close(&blocker.id, Some("Done")).unwrap();
+        assert_eq!(graph.ready().unwrap().len(), 1);
+        assert_eq!(graph.list(Some(Status::Closed)).unwrap().len(), 1);
+
+        let event = graph
+            .record_event("task_completed", Some(&blocker.id), None, serde_json::Value::Null)
+            .unwrap();
+        assert_eq!(graph.get_event(&event.id).unwrap().unwrap().kind, "task_completed");
+        assert_eq!(graph.task_events(&blocker.id, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_store_and_memory_graph_agree() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut store = Store::init(temp_dir.path()).unwrap();
+        exercise(&mut store);
+
+        let mut memory = MemoryGraph::new();
+        exercise(&mut memory);
+    }
+
+    #[test]
+    fn test_connect_or_open_without_daemon() {
+        let temp_dir = TempDir::new().unwrap();
+        Store::init(temp_dir.path()).unwrap();
+
+        let mut graph = connect_or_open(temp_dir.path()).unwrap();
+        let item = graph.create("Direct", 2, &[], None).unwrap();
+        assert!(graph.get(&item.id).unwrap().is_some());
+    }
+}
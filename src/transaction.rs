@@ -0,0 +1,310 @@
+//! Transactions expressed as data.
+//!
+//! `Store::transaction` takes a closure, which cannot cross the daemon
+//! socket. A [`TxOp`] list describes the same kind of work: the ops run in
+//! order inside one transaction and either all commit or none do.
+//!
+//! An ID field of the form `"$N"` refers to the item created by op `N` of the
+//! same list, so a client can build a subgraph without knowing the generated
+//! IDs up front.
+
+use crate::store::Store;
+use crate::types::{Edge, EdgeKind, Event, Item, Status};
+use chrono::{DateTime, Utc};
+use eyre::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// One operation in a transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum TxOp {
+    /// Create a new item.
+    Create {
+        title: String,
+        priority: u8,
+        labels: Vec<String>,
+        description: Option<String>,
+    },
+
+    /// Update an existing item.
+    Update {
+        id: String,
+        title: Option<String>,
+        description: Option<Option<String>>,
+        priority: Option<u8>,
+        labels: Option<Vec<String>>,
+        #[serde(default)]
+        expected_version: Option<DateTime<Utc>>,
+    },
+
+    /// Change item status.
+    SetStatus {
+        id: String,
+        status: Status,
+        #[serde(default)]
+        expected_version: Option<DateTime<Utc>>,
+    },
+
+    /// Close an item.
+    Close {
+        id: String,
+        reason: Option<String>,
+        #[serde(default)]
+        expected_version: Option<DateTime<Utc>>,
+    },
+
+    /// Add an edge.
+    AddEdge {
+        from_id: String,
+        to_id: String,
+        kind: EdgeKind,
+    },
+
+    /// Remove an edge.
+    RemoveEdge {
+        from_id: String,
+        to_id: String,
+        kind: EdgeKind,
+    },
+
+    /// Record an event.
+    RecordEvent {
+        kind: String,
+        source_task: Option<String>,
+        target_task: Option<String>,
+        #[serde(default)]
+        payload: serde_json::Value,
+    },
+}
+
+/// The outcome of one `TxOp`, in op order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum TxResult {
+    /// The created or updated item.
+    Item { item: Item },
+    /// The added edge.
+    Edge { edge: Edge },
+    /// The recorded event.
+    Event { event: Event },
+    /// The op has no result (edge removal).
+    Ok,
+}
+
+/// Extension trait to run `TxOp` lists against Store.
+pub trait StoreTransactionExt {
+    /// Run `ops` in order as one transaction.
+    fn apply_ops(&mut self, ops: Vec<TxOp>) -> Result<Vec<TxResult>>;
+}
+
+impl StoreTransactionExt for Store {
+    fn apply_ops(&mut self, ops: Vec<TxOp>) -> Result<Vec<TxResult>> {
+        self.transaction(|tx| {
+            // ID of the item created by each op, for "$N" references
+            let mut created: Vec<Option<String>> = Vec::with_capacity(ops.len());
+            let mut results = Vec::with_capacity(ops.len());
+
+            for (index, op) in ops.into_iter().enumerate() {
+                let result = apply_op(tx, op, &created).wrap_err_with(|| format!("Transaction op {} failed", index))?;
+                created.push(match &result {
+                    TxResult::Item { item } => Some(item.id.clone()),
+                    _ => None,
+                });
+                results.push(result);
+            }
+
+            Ok(results)
+        })
+    }
+}
+
+/// Run a single op, resolving ID references against earlier creates.
+fn apply_op(store: &mut Store, op: TxOp, created: &[Option<String>]) -> Result<TxResult> {
+    let resolve = |id: &str| resolve_id(id, created);
+
+    Ok(match op {
+        TxOp::Create {
+            title,
+            priority,
+            labels,
+            description,
+        } => {
+            let labels: Vec<&str> = labels.iter().map(|s| s.as_str()).collect();
+            let item = store.create(&title, priority, &labels, description.as_deref())?;
+            TxResult::Item { item }
+        }
+        TxOp::Update {
+            id,
+            title,
+            description,
+            priority,
+            labels,
+            expected_version,
+        } => {
+            let labels: Option<Vec<&str>> = labels.as_ref().map(|l| l.iter().map(|s| s.as_str()).collect());
+            let description = description.as_ref().map(|d| d.as_deref());
+            let item = store.update_checked(
+                &resolve(&id)?,
+                expected_version,
+                title.as_deref(),
+                description,
+                priority,
+                labels.as_deref(),
+            )?;
+            TxResult::Item { item }
+        }
+        TxOp::SetStatus {
+            id,
+            status,
+            expected_version,
+        } => TxResult::Item {
+            item: store.set_status_checked(&resolve(&id)?, expected_version, status)?,
+        },
+        TxOp::Close {
+            id,
+            reason,
+            expected_version,
+        } => TxResult::Item {
+            item: store.close_checked(&resolve(&id)?, expected_version, reason.as_deref())?,
+        },
+        TxOp::AddEdge { from_id, to_id, kind } => TxResult::Edge {
+            edge: store.add_edge(&resolve(&from_id)?, &resolve(&to_id)?, kind)?,
+        },
+        TxOp::RemoveEdge { from_id, to_id, kind } => {
+            store.remove_edge(&resolve(&from_id)?, &resolve(&to_id)?, kind)?;
+            TxResult::Ok
+        }
+        TxOp::RecordEvent {
+            kind,
+            source_task,
+            target_task,
+            payload,
+        } => {
+            let source = source_task.as_deref().map(resolve).transpose()?;
+            let target = target_task.as_deref().map(resolve).transpose()?;
+            let event = store.record_event(&kind, source.as_deref(), target.as_deref(), payload)?;
+            TxResult::Event { event }
+        }
+    })
+}
+
+/// Turn a `"$N"` reference into the ID created by op `N`; other IDs pass through.
+fn resolve_id(id: &str, created: &[Option<String>]) -> Result<String> {
+    let Some(index) = id.strip_prefix('$').and_then(|n| n.parse::<usize>().ok()) else {
+        return Ok(id.to_string());
+    };
+
+    match created.get(index) {
+        Some(Some(created_id)) => Ok(created_id.clone()),
+        Some(None) => bail!("{} refers to op {}, which did not create an item", id, index),
+        None => bail!("{} refers to op {}, which has not run yet", id, index),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::StoreError;
+    use tempfile::TempDir;
+
+    fn setup_test_store() -> (TempDir, Store) {
+        let temp_dir = TempDir::new().unwrap();
+        let store = Store::init(temp_dir.path()).unwrap();
+        (temp_dir, store)
+    }
+
+    fn create(title: &str) -> TxOp {
+        TxOp::Create {
+            title: title.to_string(),
+            priority: 2,
+            labels: vec![],
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_ops_resolves_references() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        let results = store
+            .apply_ops(vec![
+                create("Design"),
+                create("Build"),
+                TxOp::AddEdge {
+                    from_id: "$1".to_string(),
+                    to_id: "$0".to_string(),
+                    kind: EdgeKind::Blocks,
+                },
+            ])
+            .unwrap();
+
+        let (TxResult::Item { item: design }, TxResult::Edge { edge }) = (&results[0], &results[2]) else {
+            panic!("Unexpected results: {:?}", results);
+        };
+        assert_eq!(&edge.to_id, &design.id);
+        assert_eq!(store.blocked().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_apply_ops_is_all_or_nothing() {
+        let (_temp_dir, mut store) = setup_test_store();
+
+        let err = store
+            .apply_ops(vec![
+                create("Kept only if everything succeeds"),
+                TxOp::AddEdge {
+                    from_id: "$0".to_string(),
+                    to_id: "$0".to_string(),
+                    kind: EdgeKind::Blocks,
+                },
+            ])
+            .unwrap_err();
+
+        assert!(err.to_string().contains("op 1"));
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::SelfReferentialEdge)
+        ));
+        assert!(store.list(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_failed_commit_writes_nothing() {
+        let (temp_dir, mut store) = setup_test_store();
+
+        // Make the edges append fail: every write to /dev/full does
+        let engram_dir = temp_dir.path().join(".engram");
+        let edges_path = engram_dir.join("edges.jsonl");
+        std::fs::remove_file(&edges_path).unwrap();
+        std::os::unix::fs::symlink("/dev/full", &edges_path).unwrap();
+
+        let ops = vec![
+            create("Design"),
+            create("Build"),
+            TxOp::AddEdge {
+                from_id: "$1".to_string(),
+                to_id: "$0".to_string(),
+                kind: EdgeKind::Blocks,
+            },
+        ];
+        assert!(store.apply_ops(ops.clone()).is_err());
+        assert!(store.list(None).unwrap().is_empty());
+        assert_eq!(std::fs::read_to_string(engram_dir.join("items.jsonl")).unwrap(), "");
+
+        // Nothing from the failed transaction is left to be flushed later
+        std::fs::remove_file(&edges_path).unwrap();
+        std::fs::File::create(&edges_path).unwrap();
+        store.apply_ops(vec![create("Later")]).unwrap();
+        assert_eq!(store.list(None).unwrap().len(), 1);
+        drop(store);
+        let store = Store::open(temp_dir.path()).unwrap();
+        assert_eq!(store.list(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reference_to_later_op_fails() {
+        assert!(resolve_id("$3", &[None]).is_err());
+        assert!(resolve_id("$0", &[None]).is_err());
+        assert_eq!(resolve_id("eg-abc", &[]).unwrap(), "eg-abc");
+    }
+}
@@ -39,6 +39,14 @@ pub struct Item {
     /// Why it was closed (optional context)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub close_reason: Option<String>,
+
+    /// Agent that claimed the item
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+
+    /// When the assignee's claim lapses unless renewed (while InProgress)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lease_expires_at: Option<DateTime<Utc>>,
 }
 
 /// Item status states.
@@ -82,6 +90,24 @@ impl Status {
     }
 }
 
+impl std::str::FromStr for Status {
+    type Err = String;
+
+    /// Parse the snake_case name used in JSONL, queries and CLI flags.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Status::Open),
+            "in_progress" => Ok(Status::InProgress),
+            "blocked" => Ok(Status::Blocked),
+            "closed" => Ok(Status::Closed),
+            _ => Err(format!(
+                "unknown status '{}' (expected open, in_progress, blocked or closed)",
+                s
+            )),
+        }
+    }
+}
+
 /// Relationships between items.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Edge {
@@ -121,10 +147,21 @@ impl EdgeKind {
     pub fn is_blocking(&self) -> bool {
         matches!(self, EdgeKind::Blocks | EdgeKind::ParentChild)
     }
+
+    /// For an edge `from_id -> to_id` of this kind, the item that waits and
+    /// the one it waits on: a blocked item waits on its blocker, a parent on
+    /// its child.
+    pub(crate) fn waiter_and_holder<'a>(&self, from_id: &'a str, to_id: &'a str) -> (&'a str, &'a str) {
+        match self {
+            EdgeKind::ParentChild => (to_id, from_id),
+            _ => (from_id, to_id),
+        }
+    }
 }
 
 /// Validation errors for items.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ValidationError {
     EmptyTitle,
     TitleTooLong,
@@ -133,6 +170,7 @@ pub enum ValidationError {
     InvalidLabel(String),
     InvalidTimestamp,
     ClosedAtWithoutClosedStatus,
+    InvalidLeaseTtl,
 }
 
 impl std::fmt::Display for ValidationError {
@@ -153,6 +191,7 @@ impl std::fmt::Display for ValidationError {
             ValidationError::ClosedAtWithoutClosedStatus => {
                 write!(f, "closed_at set but status is not Closed")
             }
+            ValidationError::InvalidLeaseTtl => write!(f, "lease ttl is out of range"),
         }
     }
 }
@@ -225,7 +264,8 @@ pub struct Event {
 }
 
 /// Filter for querying events.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct EventFilter {
     /// Filter by event kinds.
     pub kinds: Option<Vec<String>>,
@@ -284,22 +324,51 @@ impl EventFilter {
 }
 
 /// Filter criteria for querying items.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Filter {
     /// Filter by status.
     pub status: Option<Status>,
+    /// Filter by statuses (any match).
+    pub statuses: Option<Vec<Status>>,
     /// Filter by labels (any match).
     pub labels: Option<Vec<String>>,
+    /// Filter by labels (all must match).
+    pub all_labels: Option<Vec<String>>,
+    /// Exclude items with any of these labels.
+    pub without_labels: Option<Vec<String>>,
     /// Filter by minimum priority (inclusive).
     pub min_priority: Option<u8>,
     /// Filter by maximum priority (inclusive).
     pub max_priority: Option<u8>,
     /// Filter by title substring (case-insensitive).
     pub title_contains: Option<String>,
+    /// Filter by description substring (case-insensitive).
+    pub description_contains: Option<String>,
+    /// Filter by close reason substring (case-insensitive).
+    pub close_reason_contains: Option<String>,
+    /// Created at or after this time.
+    pub created_after: Option<DateTime<Utc>>,
+    /// Created before this time.
+    pub created_before: Option<DateTime<Utc>>,
+    /// Updated at or after this time.
+    pub updated_after: Option<DateTime<Utc>>,
+    /// Updated before this time.
+    pub updated_before: Option<DateTime<Utc>>,
+    /// Closed at or after this time (excludes unclosed items).
+    pub closed_after: Option<DateTime<Utc>>,
+    /// Closed before this time (excludes unclosed items).
+    pub closed_before: Option<DateTime<Utc>>,
+    /// Sort keys, most significant first. Ties, or an empty list, fall back
+    /// to priority, then creation time.
+    pub order_by: Vec<OrderBy>,
     /// Limit number of results.
     pub limit: Option<usize>,
     /// Skip first N results.
     pub offset: Option<usize>,
+    /// Arbitrary condition, typically parsed from the query language (see
+    /// `query::parse_query`). Combined with the other criteria by AND.
+    pub predicate: Option<Predicate>,
 }
 
 impl Filter {
@@ -314,6 +383,12 @@ impl Filter {
         self
     }
 
+    /// Filter by statuses (items must have any of these).
+    pub fn statuses(mut self, statuses: impl IntoIterator<Item = Status>) -> Self {
+        self.statuses.get_or_insert_with(Vec::new).extend(statuses);
+        self
+    }
+
     /// Filter by label (items must have this label).
     pub fn label(mut self, label: impl Into<String>) -> Self {
         self.labels.get_or_insert_with(Vec::new).push(label.into());
@@ -327,6 +402,20 @@ impl Filter {
         self
     }
 
+    /// Filter by labels (items must have all of these).
+    pub fn all_labels(mut self, labels: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let label_vec = self.all_labels.get_or_insert_with(Vec::new);
+        label_vec.extend(labels.into_iter().map(|l| l.into()));
+        self
+    }
+
+    /// Exclude items with any of these labels.
+    pub fn without_labels(mut self, labels: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let label_vec = self.without_labels.get_or_insert_with(Vec::new);
+        label_vec.extend(labels.into_iter().map(|l| l.into()));
+        self
+    }
+
     /// Filter by minimum priority (0=critical).
     pub fn min_priority(mut self, priority: u8) -> Self {
         self.min_priority = Some(priority);
@@ -352,6 +441,60 @@ impl Filter {
         self
     }
 
+    /// Filter by description substring (case-insensitive).
+    pub fn description_contains(mut self, substring: impl Into<String>) -> Self {
+        self.description_contains = Some(substring.into());
+        self
+    }
+
+    /// Filter by close reason substring (case-insensitive).
+    pub fn close_reason_contains(mut self, substring: impl Into<String>) -> Self {
+        self.close_reason_contains = Some(substring.into());
+        self
+    }
+
+    /// Created at or after `time`.
+    pub fn created_after(mut self, time: DateTime<Utc>) -> Self {
+        self.created_after = Some(time);
+        self
+    }
+
+    /// Created before `time`.
+    pub fn created_before(mut self, time: DateTime<Utc>) -> Self {
+        self.created_before = Some(time);
+        self
+    }
+
+    /// Updated at or after `time`.
+    pub fn updated_after(mut self, time: DateTime<Utc>) -> Self {
+        self.updated_after = Some(time);
+        self
+    }
+
+    /// Updated before `time`.
+    pub fn updated_before(mut self, time: DateTime<Utc>) -> Self {
+        self.updated_before = Some(time);
+        self
+    }
+
+    /// Closed at or after `time`.
+    pub fn closed_after(mut self, time: DateTime<Utc>) -> Self {
+        self.closed_after = Some(time);
+        self
+    }
+
+    /// Closed before `time`.
+    pub fn closed_before(mut self, time: DateTime<Utc>) -> Self {
+        self.closed_before = Some(time);
+        self
+    }
+
+    /// Sort by `column` after any sort keys already added.
+    pub fn order_by(mut self, column: SortColumn, direction: SortDirection) -> Self {
+        self.order_by.push(OrderBy { column, direction });
+        self
+    }
+
     /// Limit results.
     pub fn limit(mut self, limit: usize) -> Self {
         self.limit = Some(limit);
@@ -363,6 +506,329 @@ impl Filter {
         self.offset = Some(offset);
         self
     }
+
+    /// Require `predicate` to hold, in addition to any existing predicate.
+    pub fn matching(mut self, predicate: Predicate) -> Self {
+        self.predicate = Some(match self.predicate.take() {
+            Some(Predicate::And(mut all)) => {
+                all.push(predicate);
+                Predicate::And(all)
+            }
+            Some(existing) => Predicate::And(vec![existing, predicate]),
+            None => predicate,
+        });
+        self
+    }
+}
+
+/// One sort key in `Filter::order_by`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderBy {
+    pub column: SortColumn,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+/// Item column to sort by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortColumn {
+    Id,
+    Title,
+    /// Lifecycle order: open, in_progress, blocked, closed.
+    Status,
+    Priority,
+    CreatedAt,
+    UpdatedAt,
+    /// Unclosed items sort before closed ones.
+    ClosedAt,
+    /// Unassigned items sort before assigned ones.
+    Assignee,
+}
+
+impl SortColumn {
+    /// SQL expression for the column on items `i`.
+    pub(crate) fn sql(self) -> &'static str {
+        match self {
+            SortColumn::Id => "i.id",
+            SortColumn::Title => "i.title",
+            SortColumn::Status => {
+                "CASE i.status WHEN 'open' THEN 0 WHEN 'in_progress' THEN 1 WHEN 'blocked' THEN 2 ELSE 3 END"
+            }
+            SortColumn::Priority => "i.priority",
+            SortColumn::CreatedAt => "i.created_at",
+            SortColumn::UpdatedAt => "i.updated_at",
+            // Never NULL, so pagination cursors can compare with '='
+            SortColumn::ClosedAt => "COALESCE(i.closed_at, '')",
+            SortColumn::Assignee => "COALESCE(i.assignee, '')",
+        }
+    }
+
+    /// Compare two items by this column, ascending.
+    pub fn compare(self, a: &Item, b: &Item) -> std::cmp::Ordering {
+        let rank = |status: Status| status as u8;
+        match self {
+            SortColumn::Id => a.id.cmp(&b.id),
+            SortColumn::Title => a.title.cmp(&b.title),
+            SortColumn::Status => rank(a.status).cmp(&rank(b.status)),
+            SortColumn::Priority => a.priority.cmp(&b.priority),
+            SortColumn::CreatedAt => a.created_at.cmp(&b.created_at),
+            SortColumn::UpdatedAt => a.updated_at.cmp(&b.updated_at),
+            SortColumn::ClosedAt => a.closed_at.cmp(&b.closed_at),
+            SortColumn::Assignee => a.assignee.cmp(&b.assignee),
+        }
+    }
+}
+
+/// Sort direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// A boolean condition on items.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Predicate {
+    /// Every condition holds (true if empty).
+    And(Vec<Predicate>),
+    /// At least one condition holds (false if empty).
+    Or(Vec<Predicate>),
+    /// The condition does not hold.
+    Not(Box<Predicate>),
+    /// Item has this status.
+    Status(Status),
+    /// Item has this label.
+    Label(String),
+    /// Item is assigned to this agent.
+    Assignee(String),
+    /// Title contains this substring (case-insensitive).
+    Title(String),
+    /// Title or description contains this substring (case-insensitive).
+    Text(String),
+    /// Priority compares to a value.
+    Priority { cmp: Comparison, value: u8 },
+    /// Creation time compares to a timestamp.
+    Created { cmp: Comparison, at: DateTime<Utc> },
+    /// Last update time compares to a timestamp.
+    Updated { cmp: Comparison, at: DateTime<Utc> },
+    /// Item is closed and its close time compares to a timestamp.
+    Closed { cmp: Comparison, at: DateTime<Utc> },
+}
+
+impl Predicate {
+    /// Whether `item` satisfies the predicate.
+    pub fn matches(&self, item: &Item) -> bool {
+        let contains = |text: &str, needle: &str| text.to_lowercase().contains(&needle.to_lowercase());
+        match self {
+            Predicate::And(all) => all.iter().all(|p| p.matches(item)),
+            Predicate::Or(any) => any.iter().any(|p| p.matches(item)),
+            Predicate::Not(inner) => !inner.matches(item),
+            Predicate::Status(status) => item.status == *status,
+            Predicate::Label(label) => item.labels.contains(label),
+            Predicate::Assignee(agent) => item.assignee.as_ref() == Some(agent),
+            Predicate::Title(text) => contains(&item.title, text),
+            Predicate::Text(text) => {
+                contains(&item.title, text) || item.description.as_deref().is_some_and(|d| contains(d, text))
+            }
+            Predicate::Priority { cmp, value } => cmp.holds(item.priority.cmp(value)),
+            Predicate::Created { cmp, at } => cmp.holds(item.created_at.cmp(at)),
+            Predicate::Updated { cmp, at } => cmp.holds(item.updated_at.cmp(at)),
+            Predicate::Closed { cmp, at } => item.closed_at.is_some_and(|closed| cmp.holds(closed.cmp(at))),
+        }
+    }
+}
+
+/// Comparison operator in a `Predicate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparison {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    /// Whether a value ordered `ordering` against the operand satisfies the comparison.
+    pub fn holds(self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            Comparison::Eq => ordering == Equal,
+            Comparison::Lt => ordering == Less,
+            Comparison::Le => ordering != Greater,
+            Comparison::Gt => ordering == Greater,
+            Comparison::Ge => ordering != Less,
+        }
+    }
+
+    /// The SQL operator.
+    pub(crate) fn sql(self) -> &'static str {
+        match self {
+            Comparison::Eq => "=",
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+        }
+    }
+}
+
+/// One page of results from a paginated query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    /// Results on this page, in query order.
+    pub results: Vec<T>,
+    /// Opaque cursor for the next page, or None if this is the last.
+    pub next: Option<String>,
+}
+
+/// An item matched by full-text search.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit {
+    /// The matching item.
+    pub item: Item,
+    /// BM25 relevance score; lower is a better match.
+    pub rank: f64,
+    /// Excerpt around the match, with matched words wrapped in `**`.
+    pub snippet: String,
+}
+
+/// Per-store automation settings.
+///
+/// Every option is off by default; a store behaves exactly as it always has
+/// until one is switched on with `Store::set_policy`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Policy {
+    /// Move an Open item to Blocked when a `Blocks` edge to an unclosed item is
+    /// added, and back to Open when its last open blocker goes away.
+    pub auto_block: bool,
+    /// What happens to a parent once all of its children are Closed.
+    pub on_children_closed: ParentPolicy,
+}
+
+/// Action taken on a parent when its last open child closes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParentPolicy {
+    /// Leave the parent as it is.
+    #[default]
+    Nothing,
+    /// Move a Blocked parent back to Open so it shows up in `ready()`.
+    Ready,
+    /// Close the parent, unless it has open blockers or a live claim.
+    Close,
+}
+
+/// A change to the store, as streamed to subscribers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum Change {
+    /// A new item was created.
+    ItemCreated { item: Item },
+    /// An existing item was modified (other than closing it).
+    ItemUpdated { item: Item },
+    /// An item moved to Closed.
+    ItemClosed { item: Item },
+    /// An edge was added.
+    EdgeAdded { edge: Edge },
+    /// An edge was removed.
+    EdgeRemoved { edge: Edge },
+    /// An event was recorded.
+    EventRecorded { event: Event },
+}
+
+impl Change {
+    /// The item this change carries, for item changes.
+    pub fn item(&self) -> Option<&Item> {
+        match self {
+            Change::ItemCreated { item } | Change::ItemUpdated { item } | Change::ItemClosed { item } => Some(item),
+            _ => None,
+        }
+    }
+}
+
+/// Filter for change subscriptions.
+///
+/// Each criterion that is set must match. A criterion that does not apply to
+/// a kind of change excludes it: filtering by label yields only item changes,
+/// and filtering by event kind yields only events.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangeFilter {
+    /// Items with any of these labels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    /// Changes touching any of these items (edge endpoints, event source or target).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item_ids: Option<Vec<String>>,
+    /// Events of any of these kinds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_kinds: Option<Vec<String>>,
+}
+
+impl ChangeFilter {
+    /// Create a new empty filter (matches every change).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Filter by label.
+    pub fn label(mut self, label: impl Into<String>) -> Self {
+        self.labels.get_or_insert_with(Vec::new).push(label.into());
+        self
+    }
+
+    /// Filter by item ID.
+    pub fn item(mut self, id: impl Into<String>) -> Self {
+        self.item_ids.get_or_insert_with(Vec::new).push(id.into());
+        self
+    }
+
+    /// Filter by event kind.
+    pub fn event_kind(mut self, kind: impl Into<String>) -> Self {
+        self.event_kinds.get_or_insert_with(Vec::new).push(kind.into());
+        self
+    }
+
+    /// Check whether a change passes the filter.
+    pub fn matches(&self, change: &Change) -> bool {
+        if let Some(labels) = &self.labels {
+            match change.item() {
+                Some(item) if item.labels.iter().any(|l| labels.contains(l)) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(kinds) = &self.event_kinds {
+            match change {
+                Change::EventRecorded { event } if kinds.contains(&event.kind) => {}
+                _ => return false,
+            }
+        }
+
+        if let Some(ids) = &self.item_ids {
+            let touches = |id: &String| ids.contains(id);
+            let touched = match change {
+                Change::ItemCreated { item } | Change::ItemUpdated { item } | Change::ItemClosed { item } => {
+                    touches(&item.id)
+                }
+                Change::EdgeAdded { edge } | Change::EdgeRemoved { edge } => {
+                    touches(&edge.from_id) || touches(&edge.to_id)
+                }
+                Change::EventRecorded { event } => event.source_task.iter().chain(&event.target_task).any(touches),
+            };
+            if !touched {
+                return false;
+            }
+        }
+
+        true
+    }
 }
 
 #[cfg(test)]
@@ -382,6 +848,8 @@ mod tests {
             updated_at: now,
             closed_at: None,
             close_reason: None,
+            assignee: None,
+            lease_expires_at: None,
         }
     }
 
@@ -433,6 +901,19 @@ mod tests {
         assert_eq!(item.validate(), Err(ValidationError::ClosedAtWithoutClosedStatus));
     }
 
+    #[test]
+    fn test_status_from_str() {
+        assert_eq!("in_progress".parse::<Status>(), Ok(Status::InProgress));
+        assert_eq!("closed".parse::<Status>(), Ok(Status::Closed));
+        assert!(
+            "bogus"
+                .parse::<Status>()
+                .unwrap_err()
+                .contains("unknown status 'bogus'")
+        );
+        assert!("Open".parse::<Status>().is_err());
+    }
+
     #[test]
     fn test_status_transitions() {
         use Status::*;
@@ -523,4 +1004,33 @@ mod tests {
         assert_eq!(filter.source_task, Some("eg-abc123".to_string()));
         assert_eq!(filter.limit, Some(10));
     }
+
+    #[test]
+    fn test_change_filter() {
+        let mut item = make_item("Labelled");
+        item.labels = vec!["backend".to_string()];
+        let created = Change::ItemCreated { item: item.clone() };
+        let event = Change::EventRecorded {
+            event: Event {
+                id: "eg-evt-1".to_string(),
+                kind: "task_started".to_string(),
+                source_task: Some(item.id.clone()),
+                target_task: None,
+                payload: serde_json::Value::Null,
+                timestamp: Utc::now(),
+            },
+        };
+
+        assert!(ChangeFilter::new().matches(&created));
+        assert!(ChangeFilter::new().label("backend").matches(&created));
+        assert!(!ChangeFilter::new().label("frontend").matches(&created));
+        assert!(!ChangeFilter::new().label("backend").matches(&event));
+
+        assert!(ChangeFilter::new().event_kind("task_started").matches(&event));
+        assert!(!ChangeFilter::new().event_kind("task_started").matches(&created));
+
+        assert!(ChangeFilter::new().item(&item.id).matches(&created));
+        assert!(ChangeFilter::new().item(&item.id).matches(&event));
+        assert!(!ChangeFilter::new().item("eg-other").matches(&event));
+    }
 }